@@ -0,0 +1,11 @@
+//! The emulator core as a library. Without the `std` feature it only links `core` and
+//! `alloc`, building it for a target without std checks that it stays that way:
+//! `cargo build --lib --no-default-features --target thumbv7em-none-eabi`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+#![feature(bigint_helper_methods)]
+
+extern crate alloc;
+
+#[path = "lib/mod.rs"]
+pub mod lib;
@@ -0,0 +1,331 @@
+use alloc::{
+    format,
+    string::ToString,
+    vec,
+    vec::Vec,
+};
+
+use super::{
+    error::{ErrorType, Result},
+    instruction::Instruction,
+    memory::registers::SFR,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    A,
+    C,
+    AB,
+    Dptr,
+    /// `@DPTR`
+    AtDptr,
+    /// `@A+DPTR`
+    AtADptr,
+    /// `@A+PC`
+    AtAPc,
+    /// `@R0` or `@R1`
+    AtR(u8),
+    /// `R0` - `R7`
+    R(u8),
+    /// `#data`
+    Imm(i32),
+    /// `/bit`
+    NotBit(i32),
+    /// A direct, bit or code address, or a relative displacement, depending on the instruction.
+    Value(i32),
+}
+
+impl Instruction {
+    /// Assembles a single line of MCS-51 assembly into its opcode and operand bytes.
+    ///
+    /// Numbers can be given in decimal, as `0x42` or as `42h`. Direct addresses can also be SFR
+    /// names (`P1`), bit addresses can be given as `byte.bit` (`P1.0`, `0x20.3`). `DB byte`
+    /// emits a single byte. Relative displacements are taken as-is (`SJMP -2`), as there is no
+    /// location counter to resolve targets against. For the same reason AJMP and ACALL only
+    /// encode the low 11 bits of their target, see [`Instruction::assemble_at`] for absolute
+    /// targets. Anything following a `;` is a comment.
+    pub fn assemble(line: &str) -> Result<Vec<u8>> {
+        assemble_line(line, None)
+    }
+
+    /// Assembles `line` like [`Instruction::assemble`], for an instruction located at `addr`.
+    /// Relative jumps, AJMP and ACALL take absolute targets, as
+    /// [`disassemble`](super::disasm::disassemble) prints them, and fail if the target is out
+    /// of their reach.
+    pub fn assemble_at(line: &str, addr: u16) -> Result<Vec<u8>> {
+        assemble_line(line, Some(addr))
+    }
+}
+
+/// Assembles `line`, resolving jump targets against `addr` if it is given.
+fn assemble_line(line: &str, addr: Option<u16>) -> Result<Vec<u8>> {
+    let line = line.split(';').next().unwrap().trim().to_uppercase();
+    let (mnemonic, operands) = match line.split_once(char::is_whitespace) {
+        Some((mnemonic, operands)) => (mnemonic, operands.trim()),
+        None => (line.as_str(), ""),
+    };
+
+    let ops = if operands.is_empty() {
+        Vec::new()
+    } else {
+        operands.split(',').map(|op| parse_operand(op.trim())).collect::<Result<Vec<_>>>()?
+    };
+
+    // the address following an instruction of `len` bytes
+    let next = |len: u16| addr.map(|addr| addr.wrapping_add(len));
+    let rel = |target: i32, len: u16| rel8(target, next(len));
+
+    use Operand::*;
+
+    let bytes = match (mnemonic, ops.as_slice()) {
+        ("NOP", []) => vec![0x00],
+        ("DB", [Value(byte)]) => vec![imm8(*byte)?],
+        ("AJMP", [Value(target)]) => addr11(0x01, *target, next(2))?,
+        ("ACALL", [Value(target)]) => addr11(0x11, *target, next(2))?,
+        ("LJMP", [Value(addr)]) => addr16(0x02, *addr)?,
+        ("LCALL", [Value(addr)]) => addr16(0x12, *addr)?,
+        ("JMP", [AtADptr]) => vec![0x73],
+        ("SJMP", [Value(target)]) => vec![0x80, rel(*target, 2)?],
+        ("JC", [Value(target)]) => vec![0x40, rel(*target, 2)?],
+        ("JNC", [Value(target)]) => vec![0x50, rel(*target, 2)?],
+        ("JZ", [Value(target)]) => vec![0x60, rel(*target, 2)?],
+        ("JNZ", [Value(target)]) => vec![0x70, rel(*target, 2)?],
+        ("JBC", [Value(bit), Value(target)]) => vec![0x10, addr8(*bit)?, rel(*target, 3)?],
+        ("JB", [Value(bit), Value(target)]) => vec![0x20, addr8(*bit)?, rel(*target, 3)?],
+        ("JNB", [Value(bit), Value(target)]) => vec![0x30, addr8(*bit)?, rel(*target, 3)?],
+        ("RET", []) => vec![0x22],
+        ("RETI", []) => vec![0x32],
+
+        ("RR", [A]) => vec![0x03],
+        ("RRC", [A]) => vec![0x13],
+        ("RL", [A]) => vec![0x23],
+        ("RLC", [A]) => vec![0x33],
+        ("SWAP", [A]) => vec![0xc4],
+        ("DA", [A]) => vec![0xd4],
+        ("MUL", [AB]) => vec![0xa4],
+        ("DIV", [AB]) => vec![0x84],
+
+        ("INC", [A]) => vec![0x04],
+        ("INC", [Value(direct)]) => vec![0x05, addr8(*direct)?],
+        ("INC", [AtR(i)]) => vec![0x06 + i],
+        ("INC", [R(n)]) => vec![0x08 + n],
+        ("INC", [Dptr]) => vec![0xa3],
+        ("DEC", [A]) => vec![0x14],
+        ("DEC", [Value(direct)]) => vec![0x15, addr8(*direct)?],
+        ("DEC", [AtR(i)]) => vec![0x16 + i],
+        ("DEC", [R(n)]) => vec![0x18 + n],
+
+        ("ADD", ops) => alu(0x20, ops)?,
+        ("ADDC", ops) => alu(0x30, ops)?,
+        ("SUBB", ops) => alu(0x90, ops)?,
+        ("ORL", [C, Value(bit)]) => vec![0x72, addr8(*bit)?],
+        ("ORL", [C, NotBit(bit)]) => vec![0xa0, addr8(*bit)?],
+        ("ORL", ops) => logical(0x40, ops)?,
+        ("ANL", [C, Value(bit)]) => vec![0x82, addr8(*bit)?],
+        ("ANL", [C, NotBit(bit)]) => vec![0xb0, addr8(*bit)?],
+        ("ANL", ops) => logical(0x50, ops)?,
+        ("XRL", ops) => logical(0x60, ops)?,
+
+        ("MOV", [A, Imm(data)]) => vec![0x74, imm8(*data)?],
+        ("MOV", [A, Value(direct)]) => vec![0xe5, addr8(*direct)?],
+        ("MOV", [A, AtR(i)]) => vec![0xe6 + i],
+        ("MOV", [A, R(n)]) => vec![0xe8 + n],
+        ("MOV", [Value(direct), A]) => vec![0xf5, addr8(*direct)?],
+        ("MOV", [AtR(i), A]) => vec![0xf6 + i],
+        ("MOV", [R(n), A]) => vec![0xf8 + n],
+        ("MOV", [C, Value(bit)]) => vec![0xa2, addr8(*bit)?],
+        ("MOV", [Value(bit), C]) => vec![0x92, addr8(*bit)?],
+        ("MOV", [Dptr, Imm(data)]) => addr16(0x90, *data)?,
+        ("MOV", [Value(direct), Imm(data)]) => vec![0x75, addr8(*direct)?, imm8(*data)?],
+        ("MOV", [AtR(i), Imm(data)]) => vec![0x76 + i, imm8(*data)?],
+        ("MOV", [R(n), Imm(data)]) => vec![0x78 + n, imm8(*data)?],
+        // the source comes first in the encoding
+        ("MOV", [Value(dest), Value(src)]) => vec![0x85, addr8(*src)?, addr8(*dest)?],
+        ("MOV", [Value(direct), AtR(i)]) => vec![0x86 + i, addr8(*direct)?],
+        ("MOV", [Value(direct), R(n)]) => vec![0x88 + n, addr8(*direct)?],
+        ("MOV", [AtR(i), Value(direct)]) => vec![0xa6 + i, addr8(*direct)?],
+        ("MOV", [R(n), Value(direct)]) => vec![0xa8 + n, addr8(*direct)?],
+        ("MOVC", [A, AtAPc]) => vec![0x83],
+        ("MOVC", [A, AtADptr]) => vec![0x93],
+        ("MOVX", [A, AtDptr]) => vec![0xe0],
+        ("MOVX", [A, AtR(i)]) => vec![0xe2 + i],
+        ("MOVX", [AtDptr, A]) => vec![0xf0],
+        ("MOVX", [AtR(i), A]) => vec![0xf2 + i],
+        ("PUSH", [Value(direct)]) => vec![0xc0, addr8(*direct)?],
+        ("POP", [Value(direct)]) => vec![0xd0, addr8(*direct)?],
+        ("XCH", [A, Value(direct)]) => vec![0xc5, addr8(*direct)?],
+        ("XCH", [A, AtR(i)]) => vec![0xc6 + i],
+        ("XCH", [A, R(n)]) => vec![0xc8 + n],
+        ("XCHD", [A, AtR(i)]) => vec![0xd6 + i],
+
+        ("CPL", [A]) => vec![0xf4],
+        ("CPL", [C]) => vec![0xb3],
+        ("CPL", [Value(bit)]) => vec![0xb2, addr8(*bit)?],
+        ("CLR", [A]) => vec![0xe4],
+        ("CLR", [C]) => vec![0xc3],
+        ("CLR", [Value(bit)]) => vec![0xc2, addr8(*bit)?],
+        ("SETB", [C]) => vec![0xd3],
+        ("SETB", [Value(bit)]) => vec![0xd2, addr8(*bit)?],
+
+        ("CJNE", [A, Imm(data), Value(target)]) => vec![0xb4, imm8(*data)?, rel(*target, 3)?],
+        ("CJNE", [A, Value(direct), Value(target)]) => {
+            vec![0xb5, addr8(*direct)?, rel(*target, 3)?]
+        },
+        ("CJNE", [AtR(i), Imm(data), Value(target)]) => {
+            vec![0xb6 + i, imm8(*data)?, rel(*target, 3)?]
+        },
+        ("CJNE", [R(n), Imm(data), Value(target)]) => {
+            vec![0xb8 + n, imm8(*data)?, rel(*target, 3)?]
+        },
+        ("DJNZ", [Value(direct), Value(target)]) => vec![0xd5, addr8(*direct)?, rel(*target, 3)?],
+        ("DJNZ", [R(n), Value(target)]) => vec![0xd8 + n, rel(*target, 2)?],
+
+        _ => return Err(invalid(&format!("unsupported instruction \"{}\"", line))),
+    };
+
+    Ok(bytes)
+}
+
+fn invalid(msg: &str) -> ErrorType {
+    ErrorType::Assembly(msg.to_string())
+}
+
+/// ADD, ADDC and SUBB, `base` being the opcode of the `A,@R0` form minus 6.
+fn alu(base: u8, ops: &[Operand]) -> Result<Vec<u8>> {
+    match ops {
+        [Operand::A, Operand::Imm(data)] => Ok(vec![base + 0x04, imm8(*data)?]),
+        [Operand::A, Operand::Value(direct)] => Ok(vec![base + 0x05, addr8(*direct)?]),
+        [Operand::A, Operand::AtR(i)] => Ok(vec![base + 0x06 + i]),
+        [Operand::A, Operand::R(n)] => Ok(vec![base + 0x08 + n]),
+        _ => Err(invalid("unsupported operands")),
+    }
+}
+
+/// ORL, ANL and XRL, which add the `direct,A` and `direct,#data` forms to [`alu`].
+fn logical(base: u8, ops: &[Operand]) -> Result<Vec<u8>> {
+    match ops {
+        [Operand::Value(direct), Operand::A] => Ok(vec![base + 0x02, addr8(*direct)?]),
+        [Operand::Value(direct), Operand::Imm(data)] => {
+            Ok(vec![base + 0x03, addr8(*direct)?, imm8(*data)?])
+        },
+        ops => alu(base, ops),
+    }
+}
+
+fn addr8(val: i32) -> Result<u8> {
+    u8::try_from(val).map_err(|_| invalid(&format!("address {} out of range", val)))
+}
+
+fn imm8(val: i32) -> Result<u8> {
+    match val {
+        -128..=255 => Ok(val as u8),
+        _ => Err(invalid(&format!("immediate {} out of range", val))),
+    }
+}
+
+/// A relative displacement, or the displacement reaching the absolute target `val` from `next`.
+fn rel8(val: i32, next: Option<u16>) -> Result<u8> {
+    let rel = match next {
+        Some(next) => code_addr(val)?.wrapping_sub(next) as i16 as i32,
+        None => val,
+    };
+
+    match rel {
+        -128..=127 => Ok(rel as u8),
+        _ => Err(invalid(&format!("displacement {} out of range", rel))),
+    }
+}
+
+fn code_addr(val: i32) -> Result<u16> {
+    u16::try_from(val).map_err(|_| invalid(&format!("address {} out of range", val)))
+}
+
+fn addr16(op: u8, val: i32) -> Result<Vec<u8>> {
+    let addr = code_addr(val)?;
+
+    Ok(vec![op, (addr >> 8) as u8, addr as u8])
+}
+
+/// AJMP and ACALL, which can only reach targets in the 2K page of `next`, if given.
+fn addr11(op: u8, val: i32, next: Option<u16>) -> Result<Vec<u8>> {
+    let addr = code_addr(val)?;
+
+    if next.is_some_and(|next| next & 0xf800 != addr & 0xf800) {
+        return Err(invalid(&format!("target {:#06x} out of reach", addr)));
+    }
+
+    Ok(vec![(((addr >> 8) & 0x07) << 5) as u8 | op, addr as u8])
+}
+
+fn parse_operand(op: &str) -> Result<Operand> {
+    let operand = match op {
+        "A" => Operand::A,
+        "C" => Operand::C,
+        "AB" => Operand::AB,
+        "DPTR" => Operand::Dptr,
+        "@DPTR" => Operand::AtDptr,
+        "@A+DPTR" => Operand::AtADptr,
+        "@A+PC" => Operand::AtAPc,
+        "@R0" | "@R1" => Operand::AtR(op.as_bytes()[2] - b'0'),
+        "R0" | "R1" | "R2" | "R3" | "R4" | "R5" | "R6" | "R7" => {
+            Operand::R(op.as_bytes()[1] - b'0')
+        },
+        _ => {
+            if let Some(data) = op.strip_prefix('#') {
+                Operand::Imm(parse_value(data)?)
+            } else if let Some(bit) = op.strip_prefix('/') {
+                Operand::NotBit(parse_value(bit)?)
+            } else {
+                Operand::Value(parse_value(op)?)
+            }
+        },
+    };
+
+    Ok(operand)
+}
+
+/// Parses a number, an SFR name or a `byte.bit` bit address.
+fn parse_value(val: &str) -> Result<i32> {
+    if let Some((byte, bit)) = val.split_once('.') {
+        let byte = parse_value(byte)?;
+        let bit = parse_number(bit).filter(|bit| (0..8).contains(bit));
+
+        return match (byte, bit) {
+            (0x20..=0x2f, Some(bit)) => Ok((byte - 0x20) * 8 + bit),
+            (0x80..=0xff, Some(bit)) if byte % 8 == 0 => Ok(byte + bit),
+            _ => Err(invalid(&format!("invalid bit address \"{}\"", val))),
+        };
+    }
+
+    if let Some(number) = parse_number(val) {
+        return Ok(number);
+    }
+
+    (0x80..=0xffu8)
+        .filter_map(|addr| SFR::try_from(addr).ok())
+        .find(|sfr| format!("{:?}", sfr) == val)
+        .map(|sfr| u8::from(sfr) as i32)
+        .ok_or_else(|| invalid(&format!("invalid operand \"{}\"", val)))
+}
+
+fn parse_number(val: &str) -> Option<i32> {
+    let (negative, val) = match val.strip_prefix('-') {
+        Some(val) => (true, val),
+        None => (false, val.strip_prefix('+').unwrap_or(val)),
+    };
+
+    let number = if let Some(hex) = val.strip_prefix("0X") {
+        i32::from_str_radix(hex, 16).ok()?
+    } else if let Some(hex) = val.strip_suffix('H') {
+        // a leading digit tells hex numbers apart from SFR names ending in H, like DPH
+        if !hex.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+
+        i32::from_str_radix(hex, 16).ok()?
+    } else {
+        val.parse().ok()?
+    };
+
+    Some(if negative { -number } else { number })
+}
@@ -0,0 +1,201 @@
+use alloc::{vec, vec::Vec};
+
+use super::{cpu::CPU, error::Result, interrupt::InterruptSource};
+
+/// A port pin of one of the CPUs on a [`Bus`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pin {
+    /// Index of the CPU, as returned by [`Bus::add_cpu`].
+    pub cpu: usize,
+    pub port: u8,
+    pub bit: u8,
+}
+
+/// Where a wire delivers the level of its source pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sink {
+    /// Drives the other pin to the level from the outside, see [`CPU::drive_pin`]. The
+    /// receiving CPU's latch is left alone.
+    Pin(Pin),
+    /// Requests `source` on `cpu` on every falling edge (e.g. an edge triggered INT0).
+    Interrupt { cpu: usize, source: InterruptSource },
+}
+
+/// Something the [`Bus`] noticed while propagating, see [`Bus::take_events`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinEvent {
+    /// `low` drives its net low while `high` drives it high, a short on real hardware.
+    Contention { low: Pin, high: Pin },
+}
+
+struct Wire {
+    from: Pin,
+    to: Sink,
+    /// Level of `from` after the last propagation, for edge detection.
+    level: bool,
+}
+
+/// Several CPUs stepped in lockstep by machine cycle, with port pins wired to each other.
+#[derive(Default)]
+pub struct Bus {
+    cpus: Vec<CPU>,
+    /// Machine cycles the bus has been stepped by, see [`Bus::step_all`].
+    cycles: u64,
+    wires: Vec<Wire>,
+    events: Vec<PinEvent>,
+    /// Contentions seen on the last propagation, so each is only reported when it starts.
+    contentions: Vec<PinEvent>,
+}
+
+impl Bus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `cpu` to the bus, returning the index used to refer to it.
+    pub fn add_cpu(&mut self, cpu: CPU) -> usize {
+        self.cpus.push(cpu);
+        self.cpus.len() - 1
+    }
+
+    pub fn cpu(&self, idx: usize) -> &CPU {
+        &self.cpus[idx]
+    }
+
+    pub fn cpu_mut(&mut self, idx: usize) -> &mut CPU {
+        &mut self.cpus[idx]
+    }
+
+    /// Wires `from` to `to`. The current level is delivered on the next propagation.
+    pub fn connect(&mut self, from: Pin, to: Sink) {
+        let level = self.cpus[from.cpu].port_bit(from.port, from.bit);
+
+        self.wires.push(Wire { from, to, level });
+    }
+
+    /// Advances the bus by one machine cycle, then propagates the resulting pin levels along
+    /// the wires.
+    ///
+    /// Every CPU, in the order they were added, executes instructions until its
+    /// [`CPU::cycle_count`] reaches the bus' cycle count. A CPU in the middle of a multi-cycle
+    /// instruction is ahead and waits, a halted or finished one is left behind.
+    ///
+    /// A CPU failing a cycle is left behind as well, the others are still stepped and the pins
+    /// propagated. The first error is returned afterwards.
+    pub fn step_all(&mut self) -> Result<()> {
+        self.cycles += 1;
+
+        let mut result = Ok(());
+
+        for cpu in self.cpus.iter_mut() {
+            while cpu.cycle_count() < self.cycles {
+                let before = cpu.cycle_count();
+
+                if let Err(err) = cpu.cycle() {
+                    result = result.and(Err(err));
+                    break;
+                }
+
+                if cpu.cycle_count() == before {
+                    break;
+                }
+            }
+        }
+
+        self.propagate();
+
+        result
+    }
+
+    /// Machine cycles the bus has been stepped by.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// The contentions going on since the last propagation, each reported once as a
+    /// [`PinEvent`] when it started.
+    pub fn contentions(&self) -> &[PinEvent] {
+        &self.contentions
+    }
+
+    /// Returns the events recorded since the last call.
+    pub fn take_events(&mut self) -> Vec<PinEvent> {
+        core::mem::take(&mut self.events)
+    }
+
+    /// Groups the pins joined by pin to pin wires into nets.
+    fn nets(&self) -> Vec<Vec<Pin>> {
+        let mut nets: Vec<Vec<Pin>> = Vec::new();
+
+        for wire in self.wires.iter() {
+            let Sink::Pin(to) = wire.to else {
+                continue;
+            };
+
+            let mut net = vec![wire.from, to];
+
+            nets.retain(|other| {
+                if !other.contains(&wire.from) && !other.contains(&to) {
+                    return true;
+                }
+
+                for pin in other {
+                    if !net.contains(pin) {
+                        net.push(*pin);
+                    }
+                }
+
+                false
+            });
+            nets.push(net);
+        }
+
+        nets
+    }
+
+    /// Records a [`PinEvent::Contention`] for every net driven both low and high.
+    fn detect_contention(&mut self) {
+        let mut contentions = Vec::new();
+
+        for net in self.nets() {
+            let driving = |level| {
+                net.iter()
+                    .copied()
+                    .find(|pin| self.cpus[pin.cpu].pin_drive(pin.port, pin.bit) == Some(level))
+            };
+
+            if let (Some(low), Some(high)) = (driving(false), driving(true)) {
+                let event = PinEvent::Contention { low, high };
+
+                if !self.contentions.contains(&event) {
+                    self.events.push(event);
+                }
+
+                contentions.push(event);
+            }
+        }
+
+        self.contentions = contentions;
+    }
+
+    fn propagate(&mut self) {
+        // from what every pin drives itself, which the levels delivered below don't change
+        self.detect_contention();
+
+        for wire in self.wires.iter_mut() {
+            let from = wire.from;
+            let level = self.cpus[from.cpu].port_bit(from.port, from.bit);
+
+            match wire.to {
+                Sink::Pin(to) => self.cpus[to.cpu].drive_pin(to.port, to.bit, level),
+                Sink::Interrupt { cpu, source } => {
+                    if wire.level && !level {
+                        self.cpus[cpu].request_interrupt(source);
+                    }
+                },
+            }
+
+            wire.level = level;
+        }
+    }
+}
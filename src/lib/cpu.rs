@@ -1,15 +1,44 @@
-use std::ops::Sub;
-use log::{debug, error, info};
+#[cfg(feature = "std")]
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+
+use alloc::{boxed::Box, format, string::String, vec, vec::Vec};
+#[cfg(feature = "serde")]
+use alloc::string::ToString;
+use core::{
+    fmt::{Display, Formatter},
+    ops::Range,
+};
+
+use log::{debug, error, info, warn};
 use bitflags::bitflags;
 
 use super::{
+    disasm,
+    error::{ErrorType, Result},
     instruction::Instruction,
+    interrupt::InterruptSource,
     memory::{
-        memory::Memory,
-        registers::{Register, SFR},
+        fault::Fault,
+        memory::{Memory, MEMORY_FLASH_SIZE},
+        registers::{PSWFlags, Register, SFR},
     },
-    ops::arithmetics::BitOps,
+    ops::{
+        arithmetics::{self, BitOps},
+        bytes::ArithmeticOpFlags,
+        checksum::{self, ChecksumAlgo},
+    },
+    ports::Ports,
+    script::{Script, ScriptEvent},
+    state::CpuState,
+    timers::Timer,
+    trace::{Trace, TraceEntry},
+    uart::{SerialBackend, Uart, UartFrame},
 };
+#[cfg(feature = "serde")]
+use super::state::SavedState;
 
 bitflags! {
     struct PCState: u8 {
@@ -20,17 +49,250 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// Reset sources, as flagged in [`SFR::RSTSRC`].
+    pub struct ResetSource: u8 {
+        const PIN = 1 << 0;
+        const POWER_ON = 1 << 1;
+        const MISSING_CLOCK = 1 << 2;
+        const WATCHDOG = 1 << 3;
+        const SOFTWARE = 1 << 4;
+        const COMPARATOR0 = 1 << 5;
+        const FLASH_ERROR = 1 << 6;
+        const USB = 1 << 7;
+    }
+}
+
+/// The reason [`CPU::run`] handed control back to its caller.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExitReason {
+    /// The CPU halted, the cause has been logged.
+    Halted,
+    /// The instruction budget set by [`CPU::break_after_instructions`] ran out.
+    InstructionCount,
+    /// The machine cycle budget set by [`CPU::break_after_cycles`] ran out.
+    CycleCount,
+    /// An instruction sent the PC to 0x0000 without a reset, see [`CPU::set_trap_on_reentry`].
+    UnexpectedReset,
+    /// Executing the next instruction failed, the CPU has been halted.
+    Error(ErrorType),
+    /// An INC wrapped from 0xFF to 0x00, or a DEC from 0x00 to 0xFF, while
+    /// [`CPU::set_overflow_trap`] was on.
+    CounterOverflow,
+    /// The PC reached the sequence set with [`CPU::set_exit_sentinel`].
+    Finished,
+    /// The wall-clock time given to [`CPU::run_for`] ran out.
+    TimeBudget,
+    /// The CPU halted on the reserved opcode 0xA5, which usually means it ran into data or
+    /// miscompiled code. Only under [`UnimplementedPolicy::Halt`], the other policies skip it.
+    ReservedOpcode,
+    /// The PC reached one of the addresses given to [`CPU::run_until`].
+    Breakpoint,
+}
+
+/// Why the CPU halted, see [`CPU::halt_reason`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HaltReason {
+    /// A known instruction without an implementation, under [`UnimplementedPolicy::Halt`].
+    Unimplemented(Instruction),
+    /// An opcode that doesn't decode, under [`UnimplementedPolicy::Halt`].
+    UnknownOpcode(u8),
+    /// The reserved opcode 0xA5, see [`ExitReason::ReservedOpcode`].
+    ReservedOpcode,
+    /// The instruction addressed a bit outside the bit addressable space.
+    InvalidBitAddress(Instruction, u8),
+    /// Executing an instruction failed, see [`ExitReason::Error`].
+    Error(ErrorType),
+    /// The host called [`CPU::stop`].
+    Stopped,
+}
+
+impl Display for HaltReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            HaltReason::Unimplemented(insn) => {
+                write!(f, "Unimplemented Instruction at Instruction::{:?}", insn)
+            },
+            HaltReason::UnknownOpcode(opcode) => write!(f, "Unknown opcode {:#04x}", opcode),
+            HaltReason::ReservedOpcode => write!(f, "reserved opcode"),
+            HaltReason::InvalidBitAddress(insn, addr) => {
+                write!(f, "invalid bit address {:#04x} at Instruction::{:?}", addr, insn)
+            },
+            HaltReason::Error(err) => write!(f, "{}", err),
+            HaltReason::Stopped => write!(f, "stopped"),
+        }
+    }
+}
+
+/// What [`CPU::step`] executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepOutcome {
+    /// Address of the instruction.
+    pub addr: u16,
+    pub instruction: Instruction,
+    /// The PC after the instruction.
+    pub pc: u16,
+}
+
+/// Instructions [`CPU::run_for`] executes between two looks at the clock.
+#[cfg(feature = "std")]
+pub const RUN_FOR_CHECK_INTERVAL: u32 = 1024;
+
+/// What is known about a byte of code, see [`CPU::set_instruction_alignment_check`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CodeByte {
+    Unknown,
+    Opcode,
+    Operand,
+}
+
+/// How the CPU deals with opcodes it can't execute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnimplementedPolicy {
+    /// Halt the CPU (the default).
+    Halt,
+    /// Skip the instruction as if it was a NOP.
+    SkipAsNop,
+    /// Log a warning, then skip the instruction.
+    LogAndSkip,
+}
+
+/// An SFR whose value differs from what [`CPU::assert_registers`] expected.
+#[cfg(test)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterMismatch {
+    pub sfr: SFR,
+    pub expected: u8,
+    pub actual: u8,
+}
+
+/// Settings fixed when the CPU is built, see [`CPU::with_config`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CpuConfig {
+    /// Number of code banks above 0x8000, one or less for an unbanked part, see
+    /// [`CPU::set_code_banks`].
+    pub code_banks: usize,
+    /// Direct address of the bank select SFR, only used with more than one bank.
+    pub bank_select: u8,
+}
+
 pub struct CPU {
     pc: usize,
     data: Memory,
-    halt: bool,
+    /// Set once the CPU halted, [`CPU::cycle`] does nothing from then on.
+    halt: Option<HaltReason>,
+    /// Number of instructions retired since initialization.
+    retired: u64,
+    /// Machine cycles spent since initialization, see [`Instruction::cycles`].
+    cycles: u64,
+    /// Retired instruction count at which [`CPU::run`] returns [`ExitReason::InstructionCount`].
+    insn_break: Option<u64>,
+    /// Machine cycle count at which [`CPU::run`] returns [`ExitReason::CycleCount`].
+    cycle_break: Option<u64>,
+    /// Source of the last reset, reported through [`SFR::RSTSRC`] reads.
+    reset_source: ResetSource,
+    /// Whether the missing clock detector has been enabled through [`SFR::RSTSRC`].
+    mcd_enabled: bool,
+    unimplemented_policy: UnimplementedPolicy,
+    trap_on_reentry: bool,
+    /// Size of the executable code space, starting at 0x0000.
+    code_size: usize,
+    /// External events still to be applied.
+    script: Script,
+    overflow_trap: bool,
+    /// Set when an INC or DEC wrapped while `overflow_trap` is on.
+    counter_overflow: bool,
+    /// Set when an instruction sent the PC to 0x0000 while `trap_on_reentry` is on.
+    reentered: bool,
+    /// SP value applied on reset.
+    stack_start: u8,
+    /// The return addresses RET and RETI accept, `None` if unchecked.
+    stack_guard: Option<Range<usize>>,
+    /// Error raised by the instruction being executed, returned from [`CPU::cycle`].
+    fault: Option<ErrorType>,
+    uarts: [Uart; 2],
+    /// Whether a crystal or clock is connected to the external oscillator pins.
+    ext_osc_present: bool,
+    ext_osc_lock_delay: u64,
+    loop_acceleration: bool,
+    /// Machine cycles left until the external oscillator reports XTLVLD.
+    ext_osc_countdown: Option<u64>,
+    ext_osc_locked: bool,
+    /// Code bytes that end the run when the PC reaches them, empty if disabled.
+    exit_sentinel: Vec<u8>,
+    /// Set when the PC reached `exit_sentinel`.
+    finished: bool,
+    timers: [Timer; 2],
+    /// Machine cycle count the timers were last advanced to.
+    timers_ticked: u64,
+    atomic_timer_reads: bool,
+    /// Timer byte latched by the read of its partner: address, value and the retired count of
+    /// the reading instruction.
+    timer_latch: Option<(u8, u8, u64)>,
+    ports: Ports,
+    /// What execution revealed about each code address, empty while the alignment check is off.
+    code_map: Vec<CodeByte>,
+    /// Priority levels of the interrupts being serviced, innermost last, `true` for high.
+    in_service: Vec<bool>,
+    /// Set by RETI, at least one more instruction runs before the next interrupt is serviced.
+    interrupt_hold: bool,
+    /// The last executed instructions, `None` unless [`CPU::enable_trace`] was called.
+    trace: Option<Trace>,
 }
 
 impl CPU {
+    /// Builds a CPU for a part configured by `config`, see [`CPU::init`].
+    pub fn with_config(config: CpuConfig) -> Self {
+        let mut cpu = CPU::init();
+
+        cpu.set_code_banks(config.code_banks, config.bank_select);
+
+        cpu
+    }
+
     pub fn init() -> Self {
         info!("Initializing CPU.");
 
-        let cpu: CPU = CPU { pc: 0, data: Memory::init(), halt: false };
+        let mut cpu: CPU = CPU {
+            pc: 0,
+            data: Memory::init(),
+            halt: None,
+            retired: 0,
+            cycles: 0,
+            insn_break: None,
+            cycle_break: None,
+            reset_source: ResetSource::empty(),
+            mcd_enabled: false,
+            unimplemented_policy: UnimplementedPolicy::Halt,
+            trap_on_reentry: false,
+            code_size: MEMORY_FLASH_SIZE,
+            script: Script::default(),
+            overflow_trap: false,
+            counter_overflow: false,
+            reentered: false,
+            stack_start: 0x07,
+            stack_guard: None,
+            fault: None,
+            uarts: [Uart::new(0), Uart::new(1)],
+            ext_osc_present: true,
+            ext_osc_lock_delay: 16,
+            loop_acceleration: false,
+            ext_osc_countdown: None,
+            ext_osc_locked: false,
+            exit_sentinel: Vec::new(),
+            finished: false,
+            timers: [Timer::new(0), Timer::new(1)],
+            timers_ticked: 0,
+            atomic_timer_reads: false,
+            timer_latch: None,
+            ports: Ports::new(),
+            code_map: Vec::new(),
+            in_service: Vec::new(),
+            interrupt_hold: false,
+            trace: None,
+        };
+
+        cpu.reset(ResetSource::POWER_ON);
 
         info!("CPU initialized.");
 
@@ -52,46 +314,1341 @@ impl CPU {
         &mut self.data
     }
 
-    pub fn load_from_file(&mut self, file_name: &str) -> bool {
-        self.data.load_from_file(file_name)
+    /// Resets the CPU, flagging `source` in [`SFR::RSTSRC`].
+    ///
+    /// The SFRs return to their reset values and the missing clock detector is disabled, RAM and
+    /// flash are left untouched.
+    pub fn reset(&mut self, source: ResetSource) {
+        info!("Reset ({:?}).", source);
+
+        self.pc = 0;
+        self.data.reset_sfrs();
+        self.reset_source = source;
+        self.mcd_enabled = false;
+        self.data.set_sfr_reg(SFR::RSTSRC, source.bits());
+        self.data.set_sfr_reg(SFR::SP, self.stack_start);
+        self.data.take_writes();
+        self.ext_osc_countdown = None;
+        self.ext_osc_locked = false;
+        self.timers_ticked = self.cycles;
+        self.timer_latch = None;
+        self.in_service.clear();
+        self.interrupt_hold = false;
+
+        for uart in &mut self.uarts {
+            uart.reset();
+        }
+    }
+
+    pub fn reset_source(&self) -> ResetSource {
+        self.reset_source
+    }
+
+    /// Whether the currently selected system clock source is running.
+    ///
+    /// Stop mode ([`SFR::PCON`] bit 1) halts every oscillator.
+    pub fn clock_running(&self) -> bool {
+        if self.data.get_sfr_reg(SFR::PCON).is_bit_set(1) {
+            return false;
+        }
+
+        match self.data.get_sfr_reg(SFR::CLKSEL) & 0x07 {
+            // internal oscillator (IOSCEN)
+            0b000 => self.data.get_sfr_reg(SFR::OSCICN).is_bit_set(7),
+            // external oscillator (XOSCMD != off)
+            0b001 => self.ext_osc_present && self.data.get_sfr_reg(SFR::OSCXCN) & 0x70 != 0,
+            // 4x clock multiplier, with and without the divider (MULEN)
+            0b010 | 0b011 => self.data.get_sfr_reg(SFR::CLKMUL).is_bit_set(7),
+            // low-frequency oscillator (OSCLEN)
+            0b100 => self.data.get_sfr_reg(SFR::OSCLCN).is_bit_set(7),
+            // reserved selections don't clock anything
+            _ => false,
+        }
+    }
+
+    /// Connects or disconnects the external crystal. Without one the external oscillator never
+    /// reports XTLVLD in [`SFR::OSCXCN`] and doesn't clock the core.
+    pub fn set_ext_osc_present(&mut self, present: bool) {
+        self.ext_osc_present = present;
+    }
+
+    /// Sets the number of machine cycles the external oscillator takes to report XTLVLD after
+    /// being enabled, 16 by default.
+    pub fn set_ext_osc_lock_delay(&mut self, cycles: u64) {
+        self.ext_osc_lock_delay = cycles;
+    }
+
+    /// Handles a firmware write to [`SFR::OSCXCN`]: XTLVLD (bit 7) is read-only, enabling the
+    /// oscillator (XOSCMD != off) starts it up.
+    fn write_oscxcn(&mut self) {
+        let mut oscxcn = self.data.get_sfr_reg(SFR::OSCXCN);
+
+        if oscxcn & 0x70 == 0 {
+            self.ext_osc_countdown = None;
+            self.ext_osc_locked = false;
+        } else if self.ext_osc_present && !self.ext_osc_locked && self.ext_osc_countdown.is_none() {
+            self.ext_osc_countdown = Some(self.ext_osc_lock_delay);
+        }
+
+        if self.ext_osc_locked {
+            oscxcn.set_bit(7);
+        } else {
+            oscxcn.clear_bit(7);
+        }
+
+        self.data.set_sfr_reg_internal(SFR::OSCXCN, oscxcn);
+    }
+
+    /// Advances the external oscillator's start-up by `cycles` machine cycles.
+    fn tick_ext_osc(&mut self, cycles: u64) {
+        match self.ext_osc_countdown {
+            Some(remaining) if remaining > cycles => {
+                self.ext_osc_countdown = Some(remaining - cycles);
+            },
+            Some(_) => {
+                let mut oscxcn = self.data.get_sfr_reg(SFR::OSCXCN);
+
+                oscxcn.set_bit(7);
+                self.data.set_sfr_reg_internal(SFR::OSCXCN, oscxcn);
+                self.ext_osc_countdown = None;
+                self.ext_osc_locked = true;
+            },
+            None => (),
+        }
+    }
+
+    fn psw_flag(&self, flag: PSWFlags) -> bool {
+        PSWFlags::from_bits_truncate(self.psw()).contains(flag)
+    }
+
+    fn set_psw_flag(&mut self, flag: PSWFlags, value: bool) {
+        let mut psw = PSWFlags::from_bits_truncate(self.psw());
+
+        psw.set(flag, value);
+        self.data.set_sfr_reg(SFR::PSW, psw.bits());
+    }
+
+    pub fn carry(&self) -> bool {
+        self.psw_flag(PSWFlags::CY)
+    }
+
+    pub fn set_carry(&mut self, value: bool) {
+        self.set_psw_flag(PSWFlags::CY, value);
+    }
+
+    pub fn aux_carry(&self) -> bool {
+        self.psw_flag(PSWFlags::AC)
+    }
+
+    pub fn set_aux_carry(&mut self, value: bool) {
+        self.set_psw_flag(PSWFlags::AC, value);
+    }
+
+    pub fn overflow(&self) -> bool {
+        self.psw_flag(PSWFlags::OV)
+    }
+
+    pub fn set_overflow(&mut self, value: bool) {
+        self.set_psw_flag(PSWFlags::OV, value);
+    }
+
+    pub fn f0(&self) -> bool {
+        self.psw_flag(PSWFlags::F0)
+    }
+
+    pub fn set_f0(&mut self, value: bool) {
+        self.set_psw_flag(PSWFlags::F0, value);
+    }
+
+    pub fn f1(&self) -> bool {
+        self.psw_flag(PSWFlags::F1)
+    }
+
+    pub fn set_f1(&mut self, value: bool) {
+        self.set_psw_flag(PSWFlags::F1, value);
+    }
+
+    pub fn parity(&self) -> bool {
+        self.psw_flag(PSWFlags::P)
+    }
+
+    pub fn set_parity(&mut self, value: bool) {
+        self.set_psw_flag(PSWFlags::P, value);
+    }
+
+    /// Writes ACC, updating P to its parity. Every instruction that writes ACC goes through here.
+    pub fn set_acc(&mut self, val: u8) {
+        self.data.set_sfr_reg(SFR::ACC, val);
+        self.update_parity();
+    }
+
+    /// Recomputes P from ACC: set when ACC holds an odd number of ones, so ACC and P together
+    /// always have even parity.
+    pub fn update_parity(&mut self) {
+        self.set_parity(self.acc().count_ones() % 2 == 1);
     }
 
-    pub fn run(&mut self) {
+    pub fn acc(&self) -> u8 {
+        self.data.core().acc
+    }
+
+    pub fn b(&self) -> u8 {
+        self.data.core().b
+    }
+
+    pub fn psw(&self) -> u8 {
+        self.data.core().psw
+    }
+
+    pub fn sp(&self) -> u8 {
+        self.data.core().sp
+    }
+
+    /// The register bank (0-3) selected by RS1/RS0.
+    pub fn reg_bank(&self) -> u8 {
+        self.data.cur_reg_bank()
+    }
+
+    pub fn set_reg_bank(&mut self, bank: u8) {
+        self.set_psw_flag(PSWFlags::RS0, bank & 0b01 != 0);
+        self.set_psw_flag(PSWFlags::RS1, bank & 0b10 != 0);
+    }
+
+    /// Direct address of `reg` in the bank selected by PSW RS1/RS0, `bank * 8 + n` for Rn.
+    pub fn gpr_addr(&self, reg: Register) -> u8 {
+        self.data.gpr_addr(reg)
+    }
+
+    /// Reads `reg` from the bank selected by PSW.
+    pub fn read_gpr(&self, reg: Register) -> u8 {
+        self.data.get_gpr_reg(reg)
+    }
+
+    /// Writes `reg` in the bank selected by PSW.
+    pub fn write_gpr(&mut self, reg: Register, val: u8) {
+        self.data.set_gpr_reg(reg, val);
+    }
+
+    /// Computes a checksum over `len` bytes of flash starting at `start`, for comparing against
+    /// the value computed by self-test firmware. Reads through the selected code bank.
+    pub fn flash_checksum(&self, start: u16, len: usize, algo: ChecksumAlgo) -> u16 {
+        checksum::checksum(&self.data.flash_range(start, len), algo)
+    }
+
+    /// Captures the PC and the direct address space, for comparing with [`CpuState::diff`].
+    pub fn state(&self) -> CpuState {
+        CpuState { pc: self.pc, memory: self.data.direct_memory().to_vec() }
+    }
+
+    /// Saves the session in a compact binary format, to be picked up again with
+    /// [`CPU::restore`]. See [`SavedState`] for what is saved.
+    #[cfg(feature = "serde")]
+    pub fn snapshot(&self) -> Vec<u8> {
+        let state = SavedState {
+            pc: self.pc,
+            retired: self.retired,
+            cycles: self.cycles,
+            timers_ticked: self.timers_ticked,
+            reset_source: self.reset_source.bits(),
+            mcd_enabled: self.mcd_enabled,
+            in_service: self.in_service.clone(),
+            interrupt_hold: self.interrupt_hold,
+            port_inputs: self.ports.inputs(),
+            ext_osc_countdown: self.ext_osc_countdown,
+            ext_osc_locked: self.ext_osc_locked,
+            uart_rx_buffers: self.uarts.each_ref().map(|uart| uart.rx_buffer()),
+            uart_tx_remaining: self.uarts.each_ref().map(|uart| uart.tx_remaining()),
+            timer_latch: self.timer_latch,
+            memory: self.data.image(),
+        };
+
+        // serializing into a Vec can't fail
+        postcard::to_allocvec(&state).unwrap()
+    }
+
+    /// Restores a session saved by [`CPU::snapshot`]. Fails with [`ErrorType::InvalidState`],
+    /// leaving the CPU alone, if `bytes` doesn't hold a state saved by this build.
+    #[cfg(feature = "serde")]
+    pub fn restore(&mut self, bytes: &[u8]) -> Result<()> {
+        let state: SavedState =
+            postcard::from_bytes(bytes).map_err(|err| ErrorType::InvalidState(err.to_string()))?;
+
+        self.data.load_image(state.memory)?;
+        self.pc = state.pc;
+        self.retired = state.retired;
+        self.cycles = state.cycles;
+        self.timers_ticked = state.timers_ticked;
+        self.reset_source = ResetSource::from_bits_truncate(state.reset_source);
+        self.mcd_enabled = state.mcd_enabled;
+        self.in_service = state.in_service;
+        self.interrupt_hold = state.interrupt_hold;
+        self.ports.set_inputs(state.port_inputs);
+        self.ext_osc_countdown = state.ext_osc_countdown;
+        self.ext_osc_locked = state.ext_osc_locked;
+
+        for (i, uart) in self.uarts.iter_mut().enumerate() {
+            uart.restore(state.uart_rx_buffers[i], state.uart_tx_remaining[i]);
+        }
+
+        self.timer_latch = state.timer_latch;
+        self.halt = None;
+        self.data.take_writes();
+
+        Ok(())
+    }
+
+    pub fn dptr(&self) -> u16 {
+        let core = self.data.core();
+
+        u16::from_be_bytes([core.dph, core.dpl])
+    }
+
+    /// Reads the external data space. The on-chip XRAM is aliased across all 64K of it.
+    pub fn read_xram(&self, addr: u16) -> u8 {
+        self.data.read_xram(addr)
+    }
+
+    pub fn write_xram(&mut self, addr: u16, val: u8) {
+        self.data.write_xram(addr, val);
+    }
+
+    /// The XRAM address of a `MOVX @Ri`: EMI0CN selects the page, `reg` the byte within it.
+    fn paged_xram_addr(&self, reg: Register) -> u16 {
+        u16::from_be_bytes([self.data.get_sfr_reg(SFR::EMI0CN), self.read_gpr(reg)])
+    }
+
+    /// Returns `window` bytes of flash and XRAM centered on DPTR, for following table walks.
+    ///
+    /// The window starts `window / 2` bytes before DPTR, or at 0 if that would underflow. The
+    /// flash window is cut short at the end of flash, XRAM wraps like MOVX does.
+    pub fn view_around_dptr(&self, window: usize) -> (Vec<u8>, Vec<u8>) {
+        let start = self.dptr().saturating_sub((window / 2) as u16);
+
+        let flash = self.data.flash_range(start, window);
+        let xram = (0..window)
+            .map(|offset| self.data.read_xram(start.wrapping_add(offset as u16)))
+            .collect();
+
+        (flash, xram)
+    }
+
+    #[cfg(test)]
+    pub fn set_registers(&mut self, regs: &[(SFR, u8)]) {
+        for (sfr, val) in regs {
+            self.data.set_sfr_reg(*sfr, *val);
+        }
+    }
+
+    #[cfg(test)]
+    pub fn set_ram(&mut self, bytes: &[(u8, u8)]) {
+        for (addr, val) in bytes {
+            self.data.write(*addr, *val);
+        }
+    }
+
+    /// Compares the given SFRs against their expected values, returning every mismatch.
+    #[cfg(test)]
+    pub fn assert_registers(&self, expected: &[(SFR, u8)]) -> Vec<RegisterMismatch> {
+        expected
+            .iter()
+            .map(|(sfr, val)| RegisterMismatch {
+                sfr: *sfr,
+                expected: *val,
+                actual: self.data.get_sfr_reg(*sfr),
+            })
+            .filter(|mismatch| mismatch.expected != mismatch.actual)
+            .collect()
+    }
+
+    /// Loads the Intel HEX file `file_name` into flash, leaving flash alone if it is malformed.
+    #[cfg(feature = "std")]
+    pub fn load_from_file(&mut self, file_name: &str) -> Result<()> {
+        let load_error = |msg: String| ErrorType::FileLoad(file_name.to_string(), msg);
+        let file = std::fs::File::open(file_name).map_err(|err| load_error(err.to_string()))?;
+
+        self.load_hex_from_reader(file).map_err(|err| match err {
+            ErrorType::Io(msg) => load_error(msg),
+            err => err,
+        })
+    }
+
+    /// Reads an Intel HEX file from `reader` to the end and loads it, see [`CPU::load_hex_str`].
+    #[cfg(feature = "std")]
+    pub fn load_hex_from_reader<R: std::io::Read>(&mut self, mut reader: R) -> Result<()> {
+        let mut src = String::new();
+
+        reader.read_to_string(&mut src).map_err(|err| ErrorType::Io(err.to_string()))?;
+
+        debug!("HEX FILE CONTENTS:\n{}", src);
+
+        self.load_hex_str(&src)
+    }
+
+    /// Loads an Intel HEX file held in memory into flash, leaving flash alone if it is malformed.
+    pub fn load_hex_str(&mut self, src: &str) -> Result<()> {
+        self.data.load_hex(src)
+    }
+
+    /// Number of instructions retired since initialization.
+    pub fn instructions_retired(&self) -> u64 {
+        self.retired
+    }
+
+    /// Machine cycles spent since initialization, the sum of [`Instruction::cycles`] of the
+    /// retired instructions plus those of the interrupt calls.
+    pub fn cycle_count(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Makes [`CPU::run`] return [`ExitReason::InstructionCount`] after exactly `n` more
+    /// retired instructions, regardless of address. A count of zero clears the break.
+    pub fn break_after_instructions(&mut self, n: u64) {
+        self.insn_break = if n == 0 { None } else { Some(self.retired + n) };
+    }
+
+    /// Makes [`CPU::run`] return [`ExitReason::CycleCount`] once `n` more machine cycles have
+    /// been spent. The instruction crossing the limit completes. A count of zero clears the
+    /// break.
+    pub fn break_after_cycles(&mut self, n: u64) {
+        self.cycle_break = if n == 0 { None } else { Some(self.cycles + n) };
+    }
+
+    /// Makes [`CPU::run`] return [`ExitReason::Finished`] once the PC reaches `sentinel` in
+    /// code memory, instead of executing it. Test firmware can end with e.g. `A5 A5`, which
+    /// isn't a valid instruction stream. An empty sentinel disables the check.
+    pub fn set_exit_sentinel(&mut self, sentinel: &[u8]) {
+        self.exit_sentinel = sentinel.to_vec();
+    }
+
+    pub fn run(&mut self) -> ExitReason {
+        loop {
+            if let Some(reason) = self.run_step() {
+                return reason;
+            }
+        }
+    }
+
+    /// Runs until the PC reaches one of `breakpoints` or [`CPU::run`] would return. At least one
+    /// instruction is executed, so a run can resume from the breakpoint it stopped at.
+    #[cfg(feature = "std")]
+    pub fn run_until(&mut self, breakpoints: &HashSet<u16>) -> ExitReason {
         loop {
-            if self.halt {
-                break;
+            if let Some(reason) = self.run_step() {
+                return reason;
             }
 
-            let insn = Instruction::try_from(self.data.read_flash(self.pc as u16)).unwrap();
+            if breakpoints.contains(&(self.pc as u16)) {
+                return ExitReason::Breakpoint;
+            }
+        }
+    }
 
-            info!("Current Instruction: {:?}", insn);
+    /// Runs until [`CPU::run`] would return or roughly `duration` of wall-clock time has passed,
+    /// whichever comes first. Returns why it stopped and the machine cycles spent.
+    ///
+    /// The clock is only checked every [`RUN_FOR_CHECK_INTERVAL`] instructions, so the slice
+    /// can overshoot `duration` by that many instructions.
+    #[cfg(feature = "std")]
+    pub fn run_for(&mut self, duration: Duration) -> (ExitReason, u64) {
+        let start = Instant::now();
+        let cycles = self.cycles;
+
+        let reason = 'run: loop {
+            for _ in 0..RUN_FOR_CHECK_INTERVAL {
+                if let Some(reason) = self.run_step() {
+                    break 'run reason;
+                }
+            }
 
-            let insn_size = Instruction::get_num_bytes(&insn);
+            if start.elapsed() >= duration {
+                break ExitReason::TimeBudget;
+            }
+        };
 
-            let (arg0, arg1): (u8, u8) = match insn_size {
-                1 => (0, 0),
-                2 => (self.data.read_flash((self.pc + 1) as u16), 0),
-                3 => (
-                    self.data.read_flash((self.pc + 1) as u16),
-                    self.data.read_flash((self.pc + 2) as u16),
-                ),
-                _ => {
-                    self.halt("invalid instruction size", insn);
-                    (0, 0)
-                },
-            };
+        (reason, self.cycles - cycles)
+    }
+
+    /// Executes one instruction, returning why [`CPU::run`] should stop, if it should.
+    fn run_step(&mut self) -> Option<ExitReason> {
+        if self.halt.is_none() {
+            if let Err(err) = self.cycle() {
+                self.halt(HaltReason::Error(err));
+            }
+        }
 
-            match self.run_instruction(insn, arg0, arg1) {
-                PCState::ADVANCE => {
-                    self.pc += insn_size;
+        if let Some(reason) = &self.halt {
+            return Some(match reason {
+                HaltReason::ReservedOpcode => ExitReason::ReservedOpcode,
+                HaltReason::Error(err) => ExitReason::Error(err.clone()),
+                _ => ExitReason::Halted,
+            });
+        }
+
+        if self.finished {
+            self.finished = false;
+            return Some(ExitReason::Finished);
+        }
 
-                    if self.pc >= 0xFFFF {
-                        self.pc -= 0xFFFF
-                    }
+        if self.reentered {
+            self.reentered = false;
+            return Some(ExitReason::UnexpectedReset);
+        }
+
+        if self.counter_overflow {
+            self.counter_overflow = false;
+            return Some(ExitReason::CounterOverflow);
+        }
+
+        if self.insn_break.is_some_and(|target| self.retired >= target) {
+            self.insn_break = None;
+            return Some(ExitReason::InstructionCount);
+        }
+
+        if self.cycle_break.is_some_and(|target| self.cycles >= target) {
+            self.cycle_break = None;
+            return Some(ExitReason::CycleCount);
+        }
+
+        None
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halt.is_some()
+    }
+
+    /// Why the CPU halted, `None` while it runs.
+    pub fn halt_reason(&self) -> Option<&HaltReason> {
+        self.halt.as_ref()
+    }
+
+    /// Halts the CPU on behalf of the host, e.g. a debugger's stop button.
+    pub fn stop(&mut self) {
+        self.halt(HaltReason::Stopped);
+    }
+
+    /// Schedules the events of `script`, replacing any previously loaded script.
+    pub fn load_script(&mut self, script: Script) {
+        self.script = script;
+    }
+
+    fn apply_script_events(&mut self) {
+        while let Some(event) = self.script.next_due(self.cycles) {
+            debug!("Script event: {:?}", event);
+
+            match event {
+                ScriptEvent::Pin { port, bit, level } => self.drive_pin(port, bit, level),
+                ScriptEvent::Uart0Rx(byte) => self.uart0_feed(byte),
+                ScriptEvent::Interrupt(source) => self.request_interrupt(source),
+            }
+        }
+    }
+
+    /// Receives `frame` on UART`uart`, see [`Uart::receive`].
+    pub fn uart_receive(&mut self, uart: u8, frame: UartFrame) {
+        self.uarts[uart as usize].receive(&mut self.data, frame);
+    }
+
+    /// Receives `byte` on UART0 into [`SFR::SBUF0`] and flags RI, without a 9th bit.
+    pub fn uart0_feed(&mut self, byte: u8) {
+        self.uart_receive(0, UartFrame { data: byte, ninth: None });
+    }
+
+    /// Returns the bytes UART0 transmitted since the last call, dropping any 9th bits. Empty
+    /// once a [`SerialBackend`] is connected, which gets them instead.
+    pub fn uart0_drain(&mut self) -> Vec<u8> {
+        self.take_uart_tx(0).into_iter().map(|frame| frame.data).collect()
+    }
+
+    /// Connects UART`uart` to `backend`, which from then on receives transmitted frames and
+    /// supplies received ones.
+    pub fn set_serial_backend(&mut self, uart: u8, backend: Box<dyn SerialBackend>) {
+        self.uarts[uart as usize].set_backend(backend);
+    }
+
+    /// How many oscillator clocks UART`uart` takes to transmit a frame in its current
+    /// configuration, see [`Uart::frame_clocks`].
+    pub fn uart_frame_clocks(&self, uart: u8) -> Option<u64> {
+        self.uarts[uart as usize].frame_clocks(&self.data)
+    }
+
+    /// Returns the frames UART`uart` transmitted since the last call.
+    pub fn take_uart_tx(&mut self, uart: u8) -> Vec<UartFrame> {
+        self.uarts[uart as usize].take_tx()
+    }
+
+    /// Level of `Pport.bit`.
+    pub fn port_bit(&self, port: u8, bit: u8) -> bool {
+        self.ports.pins(&self.data, port).is_bit_set(bit)
+    }
+
+    /// What `Pport.bit` drives onto its pin, see [`Ports::output`].
+    pub fn pin_drive(&self, port: u8, bit: u8) -> Option<bool> {
+        Ports::output(&self.data, port, bit)
+    }
+
+    /// Drives `Pport.bit` to `level` from the outside, leaving the latch alone. Driving high
+    /// releases the pin, it then follows the latch again.
+    pub fn drive_pin(&mut self, port: u8, bit: u8, level: bool) {
+        self.ports.drive(port, bit, level);
+    }
+
+    /// Drives `Pport.bit` to `level`.
+    pub fn set_port_bit(&mut self, port: u8, bit: u8, level: bool) {
+        let mut latch = Ports::latch(&self.data, port);
+
+        if level {
+            latch.set_bit(bit);
+        } else {
+            latch.clear_bit(bit);
+        }
+
+        self.set_port_latch(port, latch);
+    }
+
+    /// The latch of `Pport`, what firmware last wrote to it.
+    pub fn port_latch(&self, port: u8) -> u8 {
+        Ports::latch(&self.data, port)
+    }
+
+    /// Sets the whole latch of `Pport`, see [`CPU::set_port_bit`].
+    pub fn set_port_latch(&mut self, port: u8, value: u8) {
+        self.data.set_sfr_reg(Ports::sfr(port), value);
+    }
+
+    /// Flags `source` as pending by setting its interrupt flag.
+    pub fn request_interrupt(&mut self, source: InterruptSource) {
+        match source.flag() {
+            Some((sfr, bit)) => {
+                let mut flags = self.data.get_sfr_reg(sfr);
+
+                flags.set_bit(bit);
+                self.data.set_sfr_reg(sfr, flags);
+            },
+            None => warn!("Interrupt {:?} can't be requested, it has no flag", source),
+        }
+    }
+
+    /// Interrupts whose flag is set, highest priority first.
+    pub fn pending_interrupts(&self) -> Vec<InterruptSource> {
+        InterruptSource::ALL
+            .into_iter()
+            .filter(|source| {
+                source.flags().any(|(sfr, bit)| self.data.get_sfr_reg(sfr).is_bit_set(bit))
+            })
+            .collect()
+    }
+
+    /// The pending interrupt to service next, if any.
+    ///
+    /// Needs EA (IE bit 7) and the source's enable bit. A high priority interrupt (set in IP,
+    /// EIP1 or EIP2) wins over low priority ones and can preempt a low priority handler,
+    /// nothing preempts a high priority handler or a handler of the same level.
+    fn next_interrupt(&self) -> Option<InterruptSource> {
+        if !self.data.get_sfr_reg(SFR::IE).is_bit_set(7) {
+            return None;
+        }
+
+        let is_set = |(sfr, bit): (SFR, u8)| self.data.get_sfr_reg(sfr).is_bit_set(bit);
+        let enabled: Vec<InterruptSource> = self
+            .pending_interrupts()
+            .into_iter()
+            .filter(|source| is_set(source.enable()))
+            .collect();
+        let high = enabled.iter().find(|source| is_set(source.priority())).copied();
+
+        match self.in_service.last() {
+            None => high.or_else(|| enabled.first().copied()),
+            Some(false) => high,
+            Some(true) => None,
+        }
+    }
+
+    /// Vectors to the next interrupt as a hardware LCALL would: pushes the PC and jumps to the
+    /// source's vector. Returns whether an interrupt was serviced.
+    fn service_interrupt(&mut self) -> bool {
+        if self.interrupt_hold {
+            self.interrupt_hold = false;
+            return false;
+        }
+
+        let source = match self.next_interrupt() {
+            Some(source) => source,
+            None => return false,
+        };
+        let (sfr, bit) = source.priority();
+        let tcon = self.data.get_sfr_reg(SFR::TCON);
+
+        debug!("Servicing interrupt {:?} at {:#06x}", source, source.vector());
+
+        if source.cleared_on_vector(tcon) {
+            let (sfr, bit) = source.flag().unwrap();
+            let mut flags = self.data.get_sfr_reg(sfr);
+
+            flags.clear_bit(bit);
+            self.data.set_sfr_reg(sfr, flags);
+        }
+
+        self.in_service.push(self.data.get_sfr_reg(sfr).is_bit_set(bit));
+        self.push_return_address();
+        self.pc = source.vector() as usize;
+        true
+    }
+
+    /// Reads the interrupt vector table, following the LJMP or AJMP placed at each vector.
+    ///
+    /// Returns every source with its resolved handler address and the first instruction there.
+    pub fn interrupt_vectors(&self) -> Vec<(InterruptSource, u16, String)> {
+        InterruptSource::ALL
+            .iter()
+            .map(|source| {
+                let vector = source.vector();
+                let handler = self.resolve_jump(vector);
+
+                (*source, handler, self.describe_instruction(handler))
+            })
+            .collect()
+    }
+
+    /// Follows an LJMP or AJMP at `addr`, any other instruction resolves to `addr` itself.
+    fn resolve_jump(&self, addr: u16) -> u16 {
+        let op = self.data.read_flash(addr);
+        let arg0 = self.data.read_flash(addr.wrapping_add(1));
+
+        match Instruction::try_from(op) {
+            Ok(Instruction::LJMP) => {
+                ((arg0 as u16) << 8) | self.data.read_flash(addr.wrapping_add(2)) as u16
+            },
+            Ok(
+                Instruction::AJMP1
+                | Instruction::AJMP2
+                | Instruction::AJMP3
+                | Instruction::AJMP4
+                | Instruction::AJMP5
+                | Instruction::AJMP6
+                | Instruction::AJMP7
+                | Instruction::AJMP8,
+            ) => (addr.wrapping_add(2) & 0xF800) | ((op & 0xe0) as u16) << 3 | arg0 as u16,
+            _ => addr,
+        }
+    }
+
+    /// Disassembles the instruction at `addr`, reading through the selected code bank.
+    fn describe_instruction(&self, addr: u16) -> String {
+        let byte = |offset: u16| self.data.read_flash(addr.wrapping_add(offset));
+
+        disasm::disassemble_bytes([byte(0), byte(1), byte(2)], addr).0
+    }
+
+    /// Records the last `capacity` executed instructions from now on, see [`CPU::trace`]. A
+    /// capacity of zero turns tracing off.
+    pub fn enable_trace(&mut self, capacity: usize) {
+        self.trace = (capacity > 0).then(|| Trace::new(capacity));
+    }
+
+    /// The instructions recorded since [`CPU::enable_trace`], oldest first. Only retired
+    /// instructions are recorded, not one the CPU halted on.
+    pub fn trace(&self) -> &[TraceEntry] {
+        self.trace.as_ref().map_or(&[], |trace| trace.entries())
+    }
+
+    /// Limits code execution to `[0x0000, size)`, for parts with less code memory.
+    pub fn set_code_size(&mut self, size: usize) {
+        self.code_size = size.min(MEMORY_FLASH_SIZE);
+    }
+
+    /// Injects a memory fault, see [`Memory::inject_fault`].
+    pub fn inject_fault(&mut self, fault: Fault) -> Result<()> {
+        self.data.inject_fault(fault)
+    }
+
+    /// Banks the code space above 0x8000, see [`Memory::set_code_banks`].
+    pub fn set_code_banks(&mut self, banks: usize, select: u8) {
+        self.data.set_code_banks(banks, select);
+    }
+
+    /// Advances everything outside the core by one cycle, always in the same order:
+    ///
+    /// 1. script events due this cycle, in script order
+    /// 2. the UART0 receiver
+    /// 3. the UART1 receiver
+    /// 4. the external oscillator, the UART0 and UART1 transmitters, then Timer 0 and Timer 1,
+    ///    by the machine cycles spent since the last tick
+    ///
+    /// Which interrupt is serviced first on a tie doesn't depend on this order, pending
+    /// interrupts are arbitrated by [`InterruptSource`] priority, see
+    /// [`CPU::pending_interrupts`].
+    fn tick_peripherals(&mut self) {
+        self.apply_script_events();
+
+        for uart in &mut self.uarts {
+            uart.poll(&mut self.data);
+        }
+
+        let elapsed = self.cycles - self.timers_ticked;
+
+        self.tick_ext_osc(elapsed);
+
+        for uart in &mut self.uarts {
+            uart.tick(&mut self.data, elapsed);
+        }
+        self.tick_timers(elapsed);
+        self.timers_ticked = self.cycles;
+    }
+
+    /// Advances Timer 0 and Timer 1 by `cycles` machine cycles. [`CPU::cycle`] does this with
+    /// the cycles spent since the last call, an overflow flags the timer's interrupt.
+    pub fn tick_timers(&mut self, cycles: u64) {
+        for timer in &self.timers {
+            timer.tick(&mut self.data, cycles);
+        }
+    }
+
+    /// Makes reads of a running timer's THn:TLn pair consistent. Reading one byte latches the
+    /// other, and reading that other byte in the next instruction returns the latched value
+    /// even if the timer carried in between.
+    ///
+    /// Off by default, like on real hardware: firmware reading the bytes one after the other
+    /// can see e.g. the old TLn with the already incremented THn unless it stops the timer.
+    pub fn set_atomic_timer_reads(&mut self, atomic: bool) {
+        self.atomic_timer_reads = atomic;
+        self.timer_latch = None;
+    }
+
+    /// Reads the timer byte at `addr`, going through the latch set up by
+    /// [`CPU::set_atomic_timer_reads`].
+    fn read_timer_byte(&mut self, addr: u8) -> Option<u8> {
+        let (timer, (tl, th)) = self
+            .timers
+            .iter()
+            .map(|timer| (timer, timer.registers()))
+            .find(|(_, (tl, th))| addr == u8::from(*tl) || addr == u8::from(*th))?;
+
+        if !self.atomic_timer_reads || !timer.running(&self.data) {
+            return None;
+        }
+
+        if let Some((latched, val, retired)) = self.timer_latch.take() {
+            if latched == addr && retired + 1 == self.retired {
+                return Some(val);
+            }
+        }
+
+        let partner = if addr == u8::from(tl) { th } else { tl };
+
+        self.timer_latch = Some((partner.into(), self.data.get_sfr_reg(partner), self.retired));
+
+        Some(self.data.read(addr))
+    }
+
+    /// Fetches, decodes and executes the instruction at the current PC.
+    ///
+    /// Fails with [`ErrorType::InvalidAddr`] if the PC is outside of the code space. Does
+    /// nothing once the CPU halted, see [`CPU::is_halted`].
+    pub fn cycle(&mut self) -> Result<()> {
+        if self.halt.is_some() {
+            return Ok(());
+        }
+
+        if self.at_exit_sentinel() {
+            self.finished = true;
+            return Ok(());
+        }
+
+        self.tick_peripherals();
+
+        if self.service_interrupt() {
+            self.cycles += Instruction::LCALL.cycles();
+            return Ok(());
+        }
+
+        if self.pc >= self.code_size {
+            return Err(ErrorType::InvalidAddr(self.pc as u16));
+        }
+
+        let addr = self.pc as u16;
+        let opcode = self.data.read_flash(addr);
+
+        if !self.code_map.is_empty() {
+            self.check_alignment(opcode)?;
+        }
+
+        if self.loop_acceleration
+            && self.fast_forward_delay_loop(opcode)
+            && self.service_interrupt()
+        {
+            self.cycles += Instruction::LCALL.cycles();
+            return Ok(());
+        }
+
+        let (insn, cycles) = match Instruction::try_from(opcode) {
+            Ok(insn) => {
+                self.execute(insn);
+                (Some(insn), insn.cycles())
+            },
+            Err(_) => {
+                self.skip_unknown_opcode(opcode);
+                (None, 1)
+            },
+        };
+        let retired = self.halt.is_none();
+
+        if retired {
+            self.retired += 1;
+            self.cycles += cycles;
+        }
+
+        if self.trap_on_reentry && self.pc == 0 {
+            self.reentered = true;
+        }
+
+        self.update_sfrs();
+
+        if let (Some(trace), Some(instruction)) = (&mut self.trace, insn.filter(|_| retired)) {
+            let (acc, psw) = (self.data.get_sfr_reg(SFR::ACC), self.data.get_sfr_reg(SFR::PSW));
+
+            trace.push(TraceEntry { pc: addr, opcode, instruction, acc, psw });
+        }
+
+        if let Some(err) = self.fault.take() {
+            return Err(err);
+        }
+
+        if self.mcd_enabled && !self.clock_running() {
+            self.reset(ResetSource::MISSING_CLOCK);
+        }
+
+        Ok(())
+    }
+
+    /// Makes [`CPU::cycle`] fail with [`ErrorType::MisalignedInstruction`] when the PC lands on a
+    /// byte that was executed as an operand before, e.g. after a computed jump into the middle
+    /// of an instruction. Only what has been executed so far is known, so this can't catch the
+    /// first pass over a byte. Turning the check on or off forgets what was learned.
+    pub fn set_instruction_alignment_check(&mut self, check: bool) {
+        self.code_map = if check { vec![CodeByte::Unknown; 0x10000] } else { Vec::new() };
+    }
+
+    fn check_alignment(&mut self, opcode: u8) -> Result<()> {
+        if self.code_map[self.pc] == CodeByte::Operand {
+            return Err(ErrorType::MisalignedInstruction(self.pc as u16));
+        }
+
+        // with RESERVED_A5, every opcode decodes
+        let size = Instruction::get_num_bytes(&Instruction::try_from(opcode).unwrap());
+
+        self.code_map[self.pc] = CodeByte::Opcode;
+
+        for offset in 1..size {
+            let byte = &mut self.code_map[(self.pc + offset) & 0xffff];
+
+            if *byte == CodeByte::Unknown {
+                *byte = CodeByte::Operand;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Executes exactly one instruction. An interrupt vectored to on the way is entered, and
+    /// the first instruction of its handler is the one executed.
+    pub fn step(&mut self) -> Result<StepOutcome> {
+        self.step_with(|_| ()).map(|(outcome, _)| outcome)
+    }
+
+    /// Executes one instruction like [`CPU::step`], returning its address, the instruction and
+    /// its text, taken right before it ran. `None` if the CPU halted or reached the exit
+    /// sentinel instead of executing an instruction.
+    pub fn step_decoded(&mut self) -> Result<Option<(u16, Instruction, String)>> {
+        let retired = self.retired;
+        let (outcome, text) = self.step_with(|cpu| cpu.describe_instruction(cpu.pc as u16))?;
+
+        Ok((self.retired != retired).then_some((outcome.addr, outcome.instruction, text)))
+    }
+
+    /// The loop behind [`CPU::step`], calling `before` ahead of every [`CPU::cycle`] and
+    /// returning what it returned for the last one.
+    fn step_with<T>(&mut self, mut before: impl FnMut(&Self) -> T) -> Result<(StepOutcome, T)> {
+        let retired = self.retired;
+
+        loop {
+            let (instruction, ..) = self.peek_instruction()?;
+            let addr = self.pc as u16;
+            let extra = before(self);
+
+            self.cycle()?;
+
+            if self.retired != retired || self.halt.is_some() || self.finished {
+                return Ok((StepOutcome { addr, instruction, pc: self.pc as u16 }, extra));
+            }
+        }
+    }
+
+    /// Decodes the instruction at the PC without executing it. Returns the instruction, its
+    /// length in bytes and its operand bytes, `None` past its length.
+    pub fn peek_instruction(&self) -> Result<(Instruction, u8, Option<u8>, Option<u8>)> {
+        if self.pc >= self.code_size {
+            return Err(ErrorType::InvalidAddr(self.pc as u16));
+        }
+
+        let addr = self.pc as u16;
+        // with RESERVED_A5, every opcode decodes
+        let insn = Instruction::try_from(self.data.read_flash(addr)).unwrap();
+        let (arg0, arg1) = self.operands(addr, insn);
+
+        Ok((insn, Instruction::get_num_bytes(&insn) as u8, arg0, arg1))
+    }
+
+    /// The operand bytes of `insn` at `addr`, `None` past its length.
+    fn operands(&self, addr: u16, insn: Instruction) -> (Option<u8>, Option<u8>) {
+        let size = Instruction::get_num_bytes(&insn) as u16;
+        let operand = |n: u16| (size > n).then(|| self.data.read_flash(addr.wrapping_add(n)));
+
+        (operand(1), operand(2))
+    }
+
+    /// Compares the code at the PC with the sentinel byte by byte, a sentinel running past the
+    /// end of flash doesn't match.
+    fn at_exit_sentinel(&self) -> bool {
+        !self.exit_sentinel.is_empty()
+            && self.exit_sentinel.iter().enumerate().all(|(offset, &byte)| {
+                let addr = self.pc + offset;
+
+                addr < MEMORY_FLASH_SIZE && self.data.read_flash(addr as u16) == byte
+            })
+    }
+
+    fn execute(&mut self, insn: Instruction) {
+        info!("Current Instruction: {:?}", insn);
+
+        let insn_size = Instruction::get_num_bytes(&insn);
+
+        let (arg0, arg1): (u8, u8) = match insn_size {
+            1..=3 => {
+                let (arg0, arg1) = self.operands(self.pc as u16, insn);
+
+                (arg0.unwrap_or(0), arg1.unwrap_or(0))
+            },
+            _ => {
+                self.halt(HaltReason::Unimplemented(insn));
+                (0, 0)
+            },
+        };
+
+        match self.run_instruction(insn, arg0, arg1) {
+            PCState::ADVANCE => {
+                self.advance_pc(insn_size);
+            },
+            _ => (),
+        }
+    }
+
+    /// Makes [`CPU::run`] return [`ExitReason::UnexpectedReset`] when an instruction (a jump, a
+    /// return or the PC wrapping around) sends the PC to 0x0000. Actual resets don't trap.
+    pub fn set_trap_on_reentry(&mut self, trap: bool) {
+        self.trap_on_reentry = trap;
+    }
+
+    /// Makes [`CPU::run`] return [`ExitReason::CounterOverflow`] when an INC wraps from 0xFF to
+    /// 0x00 or a DEC from 0x00 to 0xFF, to catch counters overflowing by accident. The wrap
+    /// itself is unaffected.
+    pub fn set_overflow_trap(&mut self, trap: bool) {
+        self.overflow_trap = trap;
+    }
+
+    /// Moves the PC `bytes` ahead, past the current instruction, wrapping around the 64K code
+    /// space.
+    fn advance_pc(&mut self, bytes: usize) {
+        self.pc = (self.pc + bytes) % MEMORY_FLASH_SIZE;
+    }
+
+    /// Adds the signed displacement `rel` to the PC, wrapping around the 64K code space.
+    fn jump_relative(&mut self, rel: u8) {
+        self.pc = (self.pc as u16).wrapping_add(rel as i8 as u16) as usize;
+    }
+
+    /// Reads the direct address `addr` as `insn` sees it. Port SFRs read their pins, unless
+    /// `insn` is a read-modify-write instruction, which reads the latch. Timer bytes can be
+    /// latched, see [`CPU::set_atomic_timer_reads`].
+    fn read_direct(&mut self, addr: u8, insn: Instruction) -> u8 {
+        if let Some(val) = self.read_timer_byte(addr) {
+            return val;
+        }
+
+        match Ports::index(addr) {
+            Some(port) if !Instruction::is_read_modify_write(&insn) => {
+                self.ports.pins(&self.data, port)
+            },
+            _ => self.data.read(addr),
+        }
+    }
+
+    /// Reads the bit at `bit_addr`, halting if it isn't bit addressable. Port bits are read
+    /// like [`CPU::read_direct`] reads their port.
+    fn read_bit(&mut self, bit_addr: u8, insn: Instruction) -> Option<bool> {
+        let bit = match self.data.get_bit(bit_addr) {
+            Some(_) if bit_addr >= 0x80 => {
+                Some(self.read_direct(bit_addr - bit_addr % 8, insn).is_bit_set(bit_addr % 8))
+            },
+            bit => bit.map(|bit| bit == 1),
+        };
+
+        if bit.is_none() {
+            self.halt(HaltReason::InvalidBitAddress(insn, bit_addr));
+        }
+
+        bit
+    }
+
+    /// Writes `value` to the bit at `bit_addr`, halting if it isn't bit addressable. The rest of
+    /// the byte is read back like [`CPU::read_direct`] does for `insn`, so ports keep their latch.
+    fn write_bit(&mut self, bit_addr: u8, value: bool, insn: Instruction) {
+        let addr = if bit_addr < 0x80 { 0x20 + bit_addr / 8 } else { bit_addr - bit_addr % 8 };
+
+        if !addr.is_bit_addressable() {
+            self.halt(HaltReason::InvalidBitAddress(insn, bit_addr));
+            return;
+        }
+
+        let mut data = self.read_direct(addr, insn);
+
+        if value {
+            data.set_bit(bit_addr % 8);
+        } else {
+            data.clear_bit(bit_addr % 8);
+        }
+
+        self.data.write(addr, data);
+    }
+
+    /// Sets the SP value the CPU starts out with after a reset, 0x07 like the hardware by default.
+    /// Applies from the next reset on.
+    pub fn set_stack_start(&mut self, sp: u8) {
+        self.stack_start = sp;
+    }
+
+    /// Makes RET and RETI fail with [`ErrorType::CorruptReturnAddress`] when the address they pop
+    /// lies outside `code`, which usually means the stack got smashed. `None` turns the check
+    /// off again.
+    ///
+    /// The range is independent of [`CPU::set_code_size`], which defaults to the whole 64K and
+    /// would let every address pass.
+    pub fn set_stack_guard(&mut self, code: Option<Range<usize>>) {
+        self.stack_guard = code;
+    }
+
+    /// Pushes the PC onto the stack, low byte first, as ACALL and LCALL do.
+    fn push_return_address(&mut self) {
+        let mut sp = self.sp();
+
+        sp = sp.wrapping_add(1);
+        self.data.write_indirect(sp, (self.pc & 0x00FF) as u8);
+        sp = sp.wrapping_add(1);
+        self.data.write_indirect(sp, ((self.pc & 0xFF00) >> 8) as u8);
+
+        self.data.set_sfr_reg(SFR::SP, sp);
+    }
+
+    /// Pops a return address off the stack into the PC, as RET and RETI do.
+    fn pop_return_address(&mut self) {
+        let mut sp = self.sp();
+
+        self.pc = (self.data.read_indirect(sp) as usize) << 8;
+        sp = sp.wrapping_sub(1);
+        self.pc |= self.data.read_indirect(sp) as usize;
+        sp = sp.wrapping_sub(1);
+
+        debug!("PC: {:#06x}; SP: {:#04x}", self.pc, sp);
+
+        self.data.set_sfr_reg(SFR::SP, sp);
+
+        if self.stack_guard.as_ref().is_some_and(|code| !code.contains(&self.pc)) {
+            warn!("Corrupt return address {:#06x} (SP: {:#04x})", self.pc, sp);
+            self.fault = Some(ErrorType::CorruptReturnAddress(self.pc as u16));
+        }
+    }
+
+    /// Increments `val` with the hardware's wrapping semantics, consulting the overflow trap.
+    fn inc_wrapping(&mut self, val: u8) -> u8 {
+        if val == 0xFF && self.overflow_trap {
+            warn!("Counter overflow (PC: {:#06x})", self.pc);
+            self.counter_overflow = true;
+        }
+
+        val.wrapping_add(1)
+    }
+
+    /// Decrements `val` with the hardware's wrapping semantics, consulting the overflow trap.
+    fn dec_wrapping(&mut self, val: u8) -> u8 {
+        if val == 0x00 && self.overflow_trap {
+            warn!("Counter underflow (PC: {:#06x})", self.pc);
+            self.counter_overflow = true;
+        }
+
+        val.wrapping_sub(1)
+    }
+
+    /// Sets how the CPU deals with opcodes it can't execute, see [`UnimplementedPolicy`].
+    pub fn set_unimplemented_policy(&mut self, policy: UnimplementedPolicy) {
+        self.unimplemented_policy = policy;
+    }
+
+    /// Fallback for known instructions without an implementation. Halting leaves the PC on the
+    /// instruction.
+    fn unimplemented(&mut self, insn: Instruction) -> PCState {
+        match self.unimplemented_policy {
+            UnimplementedPolicy::Halt => {
+                self.halt(HaltReason::Unimplemented(insn));
+                return PCState::HANDLED;
+            },
+            UnimplementedPolicy::SkipAsNop => (),
+            UnimplementedPolicy::LogAndSkip => {
+                warn!("Skipping unimplemented Instruction::{:?} (PC: {:#06x})", insn, self.pc)
+            },
+        }
+
+        PCState::ADVANCE
+    }
+
+    /// Makes [`CPU::cycle`] run all but the last iteration of a delay loop (`DJNZ Rn, $` or
+    /// `DJNZ direct, $` on RAM) in one go. The counter is set to its final value and the
+    /// peripherals are advanced by the cycles of all skipped iterations at once. The skip stops
+    /// at the iteration during which the next peripheral event happens, or at a break, so the
+    /// outcome is the same as stepping through the loop.
+    pub fn set_loop_acceleration(&mut self, enabled: bool) {
+        self.loop_acceleration = enabled;
+    }
+
+    /// Skips ahead to the last iteration if `opcode` at the PC starts a delay loop. Returns
+    /// whether it stopped short because an interrupt became pending, which is then to be
+    /// serviced before the loop's next iteration.
+    fn fast_forward_delay_loop(&mut self, opcode: u8) -> bool {
+        let pc = self.pc as u16;
+        let counter = match Instruction::try_from(opcode) {
+            Ok(Instruction::DJNZ_DATA_CODE) => {
+                let addr = self.data.read_flash(pc.wrapping_add(1));
+
+                if addr >= 0x80 || self.data.read_flash(pc.wrapping_add(2)) != 0xfd {
+                    return false;
+                }
+
+                addr
+            },
+            Ok(
+                Instruction::DJNZ_R0_CODE
+                | Instruction::DJNZ_R1_CODE
+                | Instruction::DJNZ_R2_CODE
+                | Instruction::DJNZ_R3_CODE
+                | Instruction::DJNZ_R4_CODE
+                | Instruction::DJNZ_R5_CODE
+                | Instruction::DJNZ_R6_CODE
+                | Instruction::DJNZ_R7_CODE,
+            ) => {
+                if self.data.read_flash(pc.wrapping_add(1)) != 0xfe {
+                    return false;
+                }
+
+                self.data.gpr_addr(Register::try_from(opcode - 0xd8).unwrap())
+            },
+            _ => return false,
+        };
+
+        // landing on 0x0000 every iteration would trip the reentry trap
+        if self.trap_on_reentry && pc == 0 {
+            return false;
+        }
+
+        let iterations = match self.data.read(counter) {
+            0 => 256,
+            val => val as u64,
+        };
+        let cycles = Instruction::try_from(opcode).unwrap().cycles();
+        let mut skip = iterations - 1;
+
+        // an interrupt held off for one instruction is taken after the first iteration
+        if self.next_interrupt().is_some() {
+            skip = skip.min(1);
+        }
+
+        // the iteration during which an event happens is the last one skipped, the interrupt it
+        // may request is then serviced before the next iteration
+        if let Some(event) = self.cycles_to_next_event() {
+            skip = skip.min(event.div_ceil(cycles));
+        }
+
+        // the iteration crossing a break is the one executed normally
+        if let Some(target) = self.insn_break {
+            skip = skip.min(target.saturating_sub(self.retired + 1));
+        }
+
+        if let Some(target) = self.cycle_break {
+            skip = skip.min(target.saturating_sub(self.cycles).div_ceil(cycles).saturating_sub(1));
+        }
+
+        if skip == 0 {
+            return false;
+        }
+
+        debug!("Fast-forwarding {} iterations of the delay loop at {:#06x}", skip, pc);
+
+        let val = self.data.read(counter).wrapping_sub(skip as u8);
+
+        self.data.write(counter, val);
+        self.retired += skip;
+        self.cycles += skip * cycles;
+        self.tick_peripherals();
+        self.data.take_writes();
+
+        self.next_interrupt().is_some()
+    }
+
+    /// Machine cycles until the next peripheral event: a timer overflow, the end of a UART
+    /// transmission, the external oscillator locking or a script event coming due.
+    fn cycles_to_next_event(&self) -> Option<u64> {
+        let timers = self.timers.iter().filter_map(|timer| timer.cycles_to_overflow(&self.data));
+        let uarts = self.uarts.iter().filter_map(|uart| uart.tx_remaining());
+        let script = self.script.next_time().map(|time| time.saturating_sub(self.cycles));
+
+        timers.chain(uarts).chain(self.ext_osc_countdown).chain(script).min()
+    }
+
+    /// Fallback for opcodes that don't decode to any instruction. These are skipped as one byte.
+    fn skip_unknown_opcode(&mut self, opcode: u8) {
+        match self.unimplemented_policy {
+            UnimplementedPolicy::Halt => {
+                self.halt(HaltReason::UnknownOpcode(opcode));
+                return;
+            },
+            UnimplementedPolicy::SkipAsNop => (),
+            UnimplementedPolicy::LogAndSkip => {
+                warn!("Skipping unknown opcode {:#04x} (PC: {:#06x})", opcode, self.pc)
+            },
+        }
+
+        self.advance_pc(1);
+    }
+
+    /// Applies the side effects of the SFR writes done by the last instruction.
+    fn update_sfrs(&mut self) {
+        for addr in self.data.take_writes() {
+            match SFR::try_from(addr) {
+                Ok(SFR::RSTSRC) => {
+                    let rstsrc = self.data.get_sfr_reg(SFR::RSTSRC);
+
+                    // writing MCDRSF enables the detector, reads keep reporting the reset source
+                    self.mcd_enabled = rstsrc.is_bit_set(2);
+                    self.data.set_sfr_reg(SFR::RSTSRC, self.reset_source.bits());
                 },
+                Ok(SFR::SBUF0) => self.uarts[0].transmit(&mut self.data),
+                Ok(SFR::SBUF1) => self.uarts[1].transmit(&mut self.data),
+                Ok(SFR::OSCXCN) => self.write_oscxcn(),
+                // direct writes to ACC (0xe0) need P updated like set_acc does, and P can't be
+                // written through PSW
+                Ok(SFR::ACC) | Ok(SFR::PSW) => self.update_parity(),
                 _ => (),
             }
         }
+
+        self.data.take_writes();
     }
 
     #[cfg(test)]
@@ -116,7 +1673,7 @@ impl CPU {
             | Instruction::AJMP6
             | Instruction::AJMP7
             | Instruction::AJMP8 => {
-                self.pc += 2;
+                self.advance_pc(2);
 
                 debug!("PC: {:#06x} ({:#018b})", self.pc, self.pc);
                 self.pc &= 0xF800;
@@ -137,29 +1694,35 @@ impl CPU {
             },
             // 0x03
             Instruction::RR_A => {
-                self.data.set_sfr_reg(SFR::ACC, self.data.get_sfr_reg(SFR::ACC) >> 1);
+                self.set_acc(self.acc().rotate_right(1));
                 PCState::ADVANCE
             },
             // 0x04
             Instruction::INC_A => {
-                self.data.set_sfr_reg(SFR::ACC, self.data.get_sfr_reg(SFR::ACC) + 1);
+                let acc = self.inc_wrapping(self.acc());
+                self.set_acc(acc);
                 PCState::ADVANCE
             },
             // 0x05
             Instruction::INC_DATA => {
-                self.data.write(arg0, self.data.read(arg0) + 1);
+                let data = self.read_direct(arg0, insn);
+                let data = self.inc_wrapping(data);
+
+                self.data.write(arg0, data);
                 PCState::ADVANCE
             },
             // 0x06
             Instruction::INC_INDIRECT_R0 => {
-                let addr = self.data.get_gpr_reg(Register::R0);
-                self.data.write(addr, self.data.read(addr) + 1);
+                let addr = self.read_gpr(Register::R0);
+                let data = self.inc_wrapping(self.data.read_indirect(addr));
+                self.data.write_indirect(addr, data);
                 PCState::ADVANCE
             },
             // 0x07
             Instruction::INC_INDIRECT_R1 => {
-                let addr = self.data.get_gpr_reg(Register::R1);
-                self.data.write(addr, self.data.read(addr) + 1);
+                let addr = self.read_gpr(Register::R1);
+                let data = self.inc_wrapping(self.data.read_indirect(addr));
+                self.data.write_indirect(addr, data);
                 PCState::ADVANCE
             },
             // 0x08..=0x0f
@@ -172,7 +1735,8 @@ impl CPU {
             | Instruction::INC_R6
             | Instruction::INC_R7 => {
                 let reg = Register::try_from(op - 0x08).unwrap();
-                self.data.set_gpr_reg(reg, self.data.get_gpr_reg(reg) + 1);
+                let data = self.inc_wrapping(self.read_gpr(reg));
+                self.write_gpr(reg, data);
                 PCState::ADVANCE
             },
             // 0x11, 0x31, 0x51, 0x71, 0x91, 0xb1, 0xd1, 0xf1
@@ -184,70 +1748,174 @@ impl CPU {
             | Instruction::ACALL6
             | Instruction::ACALL7
             | Instruction::ACALL8 => {
-                self.pc += 2;
+                self.advance_pc(2);
+                self.push_return_address();
 
-                let mut sp = self.data.get_sfr_reg(SFR::SP);
+                debug!("PC: {:#06x} ({:#018b})", self.pc, self.pc);
+                self.pc &= 0xF800;
+                debug!("PC: {:#06x} ({:#018b})", self.pc, self.pc);
+                self.pc |= ((op & 0xe0) as usize) << 3;
+                debug!("PC: {:#06x} ({:#018b})", self.pc, self.pc);
+                self.pc |= arg0 as usize;
+                debug!("PC: {:#06x} ({:#018b})", self.pc, self.pc);
+
+                PCState::HANDLED
+            },
+            // 0x12
+            Instruction::LCALL => {
+                self.advance_pc(3);
+                self.push_return_address();
+                self.pc = (arg0 as usize) << 8 | arg1 as usize;
+
+                debug!("PC: {:#06x}", self.pc);
+                PCState::HANDLED
+            },
+            // 0x13
+            Instruction::RRC_A => {
+                let acc = self.acc();
+
+                self.set_acc(acc >> 1 | (self.carry() as u8) << 7);
+                self.set_carry(acc & 0x01 != 0);
+                PCState::ADVANCE
+            },
+            // 0x14
+            Instruction::DEC_A => {
+                let acc = self.dec_wrapping(self.acc());
+
+                self.set_acc(acc);
+                PCState::ADVANCE
+            },
+            // 0x15
+            Instruction::DEC_ADDR => {
+                let data = self.read_direct(arg0, insn);
 
-                sp += 1;
-                self.data.write(sp, (self.pc & 0x00FF) as u8);
-                sp += 1;
-                self.data.write(sp, ((self.pc & 0xFF00) >> 8) as u8);
+                let data = self.dec_wrapping(data);
 
-                self.data.set_sfr_reg(SFR::SP, sp);
+                self.data.write(arg0, data);
+                PCState::ADVANCE
+            },
+            // 0x16, 0x17
+            Instruction::DEC_INDIRECT_R0 | Instruction::DEC_INDIRECT_R1 => {
+                let addr = self.read_gpr(Register::try_from(op - 0x16).unwrap());
 
-                debug!("PC: {:#06x} ({:#018b})", self.pc, self.pc);
-                self.pc &= 0xF800;
-                debug!("PC: {:#06x} ({:#018b})", self.pc, self.pc);
-                self.pc |= ((op & 0xe0) as usize) << 3;
-                debug!("PC: {:#06x} ({:#018b})", self.pc, self.pc);
-                self.pc |= arg0 as usize;
-                debug!("PC: {:#06x} ({:#018b})", self.pc, self.pc);
+                let data = self.dec_wrapping(self.data.read_indirect(addr));
 
-                PCState::HANDLED
+                self.data.write_indirect(addr, data);
+                PCState::ADVANCE
+            },
+            // 0x18..=0x1f
+            Instruction::DEC_R0
+            | Instruction::DEC_R1
+            | Instruction::DEC_R2
+            | Instruction::DEC_R3
+            | Instruction::DEC_R4
+            | Instruction::DEC_R5
+            | Instruction::DEC_R6
+            | Instruction::DEC_R7 => {
+                let reg = Register::try_from(op - 0x18).unwrap();
+
+                let data = self.dec_wrapping(self.read_gpr(reg));
+
+                self.write_gpr(reg, data);
+                PCState::ADVANCE
             },
             // 0x20
             Instruction::JB_BIT_CODE => {
-                self.pc += 3;
-
-                let bit = self.data.get_bit(arg0).unwrap();
+                self.advance_pc(3);
 
-                if bit == 1 {
-                    self.pc += arg1 as usize;
+                if let Some(true) = self.read_bit(arg0, insn) {
+                    self.jump_relative(arg1);
                 }
 
                 PCState::HANDLED
             },
             // 0x22
             Instruction::RET => {
-                let mut sp = self.data.get_sfr_reg(SFR::SP);
-
-                self.pc = (self.data.read(sp) as usize) << 8;
-                sp -= 1;
-                self.pc |= self.data.read(sp) as usize;
-                sp -= 1;
-
-                debug!("PC: {:#06x}; SP: {:#04x}", self.pc, sp);
-
-                self.data.set_sfr_reg(SFR::SP, sp);
+                self.pop_return_address();
                 PCState::HANDLED
             },
             // 0x23
             Instruction::RL_A => {
-                self.data.set_sfr_reg(SFR::ACC, self.data.get_sfr_reg(SFR::ACC) << 1);
+                self.set_acc(self.acc().rotate_left(1));
+                PCState::ADVANCE
+            },
+            // 0x24
+            Instruction::ADD_A_CONST => {
+                self.add(self.acc(), arg0, false);
+                PCState::ADVANCE
+            },
+            // 0x25
+            Instruction::ADD_A_DATA => {
+                let data = self.read_direct(arg0, insn);
+
+                self.add(self.acc(), data, false);
+                PCState::ADVANCE
+            },
+            // 0x26, 0x27
+            Instruction::ADD_A_INDIRECT_R0 | Instruction::ADD_A_INDIRECT_R1 => {
+                let addr = self.read_gpr(Register::try_from(op - 0x26).unwrap());
+
+                self.add(self.acc(), self.data.read_indirect(addr), false);
+                PCState::ADVANCE
+            },
+            // 0x28..=0x2f
+            Instruction::ADD_A_R0
+            | Instruction::ADD_A_R1
+            | Instruction::ADD_A_R2
+            | Instruction::ADD_A_R3
+            | Instruction::ADD_A_R4
+            | Instruction::ADD_A_R5
+            | Instruction::ADD_A_R6
+            | Instruction::ADD_A_R7 => {
+                let rn = self.read_gpr(Register::try_from(op - 0x28).unwrap());
+
+                self.add(self.acc(), rn, false);
                 PCState::ADVANCE
             },
             // 0x30
             Instruction::JNB_BIT_CODE => {
-                self.pc += 3;
+                self.advance_pc(3);
 
-                let bit = self.data.get_bit(arg0).unwrap();
-
-                if bit == 0 {
-                    self.pc += arg1 as usize;
+                if let Some(false) = self.read_bit(arg0, insn) {
+                    self.jump_relative(arg1);
                 }
 
                 PCState::HANDLED
             }
+            // 0x32
+            Instruction::RETI => {
+                self.pop_return_address();
+                self.in_service.pop();
+                self.interrupt_hold = true;
+                PCState::HANDLED
+            },
+            // 0x33
+            Instruction::RLC_A => {
+                let acc = self.acc();
+
+                self.set_acc(acc << 1 | self.carry() as u8);
+                self.set_carry(acc & 0x80 != 0);
+                PCState::ADVANCE
+            },
+            // 0x34
+            Instruction::ADDC_A_CONST => {
+                self.add(self.acc(), arg0, true);
+                PCState::ADVANCE
+            },
+            // 0x35
+            Instruction::ADDC_A_DATA => {
+                let data = self.read_direct(arg0, insn);
+
+                self.add(self.acc(), data, true);
+                PCState::ADVANCE
+            },
+            // 0x36, 0x37
+            Instruction::ADDC_A_INDIRECT_R0 | Instruction::ADDC_A_INDIRECT_R1 => {
+                let addr = self.read_gpr(Register::try_from(op - 0x36).unwrap());
+
+                self.add(self.acc(), self.data.read_indirect(addr), true);
+                PCState::ADVANCE
+            },
             // 0x38..=0x3f
             Instruction::ADDC_A_R0
             | Instruction::ADDC_A_R1
@@ -257,52 +1925,215 @@ impl CPU {
             | Instruction::ADDC_A_R5
             | Instruction::ADDC_A_R6
             | Instruction::ADDC_A_R7 => {
-                let acc = self.data.get_sfr_reg(SFR::ACC);
-                let rn = self.data.get_gpr_reg(Register::try_from(op - 0x38).unwrap());
+                let acc = self.acc();
+                let rn = self.read_gpr(Register::try_from(op - 0x38).unwrap());
 
-                self.addc(acc, rn);
+                self.add(acc, rn, true);
 
                 PCState::ADVANCE
             },
+            // 0x42
+            Instruction::ORL_DATA_A => {
+                let data = self.read_direct(arg0, insn);
+                let acc = self.acc();
+
+                self.data.write(arg0, data | acc);
+                PCState::ADVANCE
+            },
             // 0x43
             Instruction::ORL_DATA_CONST => {
-                self.data.write(arg0, self.data.read(arg0) | arg1);
+                let data = self.read_direct(arg0, insn);
+
+                self.data.write(arg0, data | arg1);
                 PCState::ADVANCE
             },
-            // 0x4d
-            Instruction::ORL_A_R5 => {
-                self.data.set_sfr_reg(
-                    SFR::ACC,
-                    self.data.get_sfr_reg(SFR::ACC) | self.data.get_gpr_reg(Register::R5),
-                );
+            // 0x44
+            Instruction::ORL_A_CONST => {
+                self.set_acc(self.acc() | arg0);
+                PCState::ADVANCE
+            },
+            // 0x45
+            Instruction::ORL_A_DATA => {
+                let data = self.read_direct(arg0, insn);
+
+                self.set_acc(self.acc() | data);
+                PCState::ADVANCE
+            },
+            // 0x46, 0x47
+            Instruction::ORL_A_INDIRECT_R0 | Instruction::ORL_A_INDIRECT_R1 => {
+                let addr = self.read_gpr(Register::try_from(op - 0x46).unwrap());
+
+                self.set_acc(self.acc() | self.data.read_indirect(addr));
+                PCState::ADVANCE
+            },
+            // 0x48..=0x4f
+            Instruction::ORL_A_R0
+            | Instruction::ORL_A_R1
+            | Instruction::ORL_A_R2
+            | Instruction::ORL_A_R3
+            | Instruction::ORL_A_R4
+            | Instruction::ORL_A_R5
+            | Instruction::ORL_A_R6
+            | Instruction::ORL_A_R7 => {
+                let reg = self.read_gpr(Register::try_from(op - 0x48).unwrap());
+
+                self.set_acc(self.acc() | reg);
                 PCState::ADVANCE
             },
             // 0x50
             Instruction::JNC => {
-                self.pc += 2;
-                let psw = self.data.get_sfr_reg(SFR::PSW);
+                self.advance_pc(2);
 
-                // if carry is not set
-                if !psw.is_bit_set(7) {
-                    self.pc += arg0 as usize;
+                if !self.carry() {
+                    self.jump_relative(arg0);
                 }
 
                 PCState::HANDLED
             }
             // 0x52
             Instruction::ANL_DATA_A => {
-                let data = self.data.read(arg0);
-                let acc = self.data.get_sfr_reg(SFR::ACC);
+                let data = self.read_direct(arg0, insn);
+                let acc = self.acc();
 
                 self.data.write(arg0, data & acc);
                 PCState::ADVANCE
             },
-            // 0x5f
-            Instruction::ANL_A_R7 => {
-                self.data.set_sfr_reg(
-                    SFR::ACC,
-                    self.data.get_sfr_reg(SFR::ACC) & self.data.get_gpr_reg(Register::R7),
-                );
+            // 0x53
+            Instruction::ANL_DATA_CONST => {
+                let data = self.read_direct(arg0, insn);
+
+                self.data.write(arg0, data & arg1);
+                PCState::ADVANCE
+            },
+            // 0x54
+            Instruction::ANL_A_CONST => {
+                self.set_acc(self.acc() & arg0);
+                PCState::ADVANCE
+            },
+            // 0x55
+            Instruction::ANL_A_DATA => {
+                let data = self.read_direct(arg0, insn);
+
+                self.set_acc(self.acc() & data);
+                PCState::ADVANCE
+            },
+            // 0x56, 0x57
+            Instruction::ANL_A_INDIRECT_R0 | Instruction::ANL_A_INDIRECT_R1 => {
+                let addr = self.read_gpr(Register::try_from(op - 0x56).unwrap());
+
+                self.set_acc(self.acc() & self.data.read_indirect(addr));
+                PCState::ADVANCE
+            },
+            // 0x58..=0x5f
+            Instruction::ANL_A_R0
+            | Instruction::ANL_A_R1
+            | Instruction::ANL_A_R2
+            | Instruction::ANL_A_R3
+            | Instruction::ANL_A_R4
+            | Instruction::ANL_A_R5
+            | Instruction::ANL_A_R6
+            | Instruction::ANL_A_R7 => {
+                let reg = self.read_gpr(Register::try_from(op - 0x58).unwrap());
+
+                self.set_acc(self.acc() & reg);
+                PCState::ADVANCE
+            },
+            // 0x60
+            Instruction::JZ => {
+                self.advance_pc(2);
+
+                if self.acc() == 0 {
+                    self.jump_relative(arg0);
+                }
+
+                PCState::HANDLED
+            },
+            // 0x62
+            Instruction::XRL_DATA_A => {
+                let data = self.read_direct(arg0, insn);
+                let acc = self.acc();
+
+                self.data.write(arg0, data ^ acc);
+                PCState::ADVANCE
+            },
+            // 0x63
+            Instruction::XRL_DATA_CONST => {
+                let data = self.read_direct(arg0, insn);
+
+                self.data.write(arg0, data ^ arg1);
+                PCState::ADVANCE
+            },
+            // 0x64
+            Instruction::XRL_A_CONST => {
+                self.set_acc(self.acc() ^ arg0);
+                PCState::ADVANCE
+            },
+            // 0x65
+            Instruction::XRL_A_DATA => {
+                let data = self.read_direct(arg0, insn);
+
+                self.set_acc(self.acc() ^ data);
+                PCState::ADVANCE
+            },
+            // 0x66, 0x67
+            Instruction::XRL_A_INDIRECT_R0 | Instruction::XRL_A_INDIRECT_R1 => {
+                let addr = self.read_gpr(Register::try_from(op - 0x66).unwrap());
+
+                self.set_acc(self.acc() ^ self.data.read_indirect(addr));
+                PCState::ADVANCE
+            },
+            // 0x68..=0x6f
+            Instruction::XRL_A_R0
+            | Instruction::XRL_A_R1
+            | Instruction::XRL_A_R2
+            | Instruction::XRL_A_R3
+            | Instruction::XRL_A_R4
+            | Instruction::XRL_A_R5
+            | Instruction::XRL_A_R6
+            | Instruction::XRL_A_R7 => {
+                let reg = self.read_gpr(Register::try_from(op - 0x68).unwrap());
+
+                self.set_acc(self.acc() ^ reg);
+                PCState::ADVANCE
+            },
+            // 0x70
+            Instruction::JNZ => {
+                self.advance_pc(2);
+
+                if self.acc() != 0 {
+                    self.jump_relative(arg0);
+                }
+
+                PCState::HANDLED
+            },
+            // 0x72
+            Instruction::ORL_C_BIT => {
+                if let Some(bit) = self.read_bit(arg0, insn) {
+                    self.set_carry(self.carry() | bit);
+                }
+                PCState::ADVANCE
+            },
+            // 0x73
+            Instruction::JMP => {
+                self.pc = self.dptr().wrapping_add(self.acc() as u16) as usize;
+
+                PCState::HANDLED
+            },
+            // 0x74
+            Instruction::MOV_A_CONST => {
+                self.set_acc(arg0);
+                PCState::ADVANCE
+            },
+            // 0x75
+            Instruction::MOV_DATA_CONST => {
+                self.data.write(arg0, arg1);
+                PCState::ADVANCE
+            },
+            // 0x76, 0x77
+            Instruction::MOV_INDIRECT_R0_CONST | Instruction::MOV_INDIRECT_R1_CONST => {
+                let addr = self.read_gpr(Register::try_from(op - 0x76).unwrap());
+
+                self.data.write_indirect(addr, arg0);
                 PCState::ADVANCE
             },
             // 0x78..=0x7f
@@ -314,21 +2145,63 @@ impl CPU {
             | Instruction::MOV_R5_CONST
             | Instruction::MOV_R6_CONST
             | Instruction::MOV_R7_CONST => {
-                self.data.set_gpr_reg(Register::try_from(op - 0x78).unwrap(), arg0);
+                self.write_gpr(Register::try_from(op - 0x78).unwrap(), arg0);
                 PCState::ADVANCE
             }
             // 0x80
             Instruction::SJMP => {
-                self.pc += 2;
+                self.advance_pc(2);
 
-                if arg0 >= 128 {
-                    self.pc -= (arg0 - 127) as usize;
-                } else {
-                    self.pc += arg0 as usize;
-                }
+                self.jump_relative(arg0);
 
                 PCState::HANDLED
             }
+            // 0x82
+            Instruction::ANL_C_BIT => {
+                if let Some(bit) = self.read_bit(arg0, insn) {
+                    self.set_carry(self.carry() & bit);
+                }
+                PCState::ADVANCE
+            },
+            // 0x83
+            Instruction::MOVC_A_INDIRECT_A_PC => {
+                // relative to the instruction after the one-byte MOVC, not the MOVC itself
+                let addr = (self.pc as u16).wrapping_add(1).wrapping_add(self.acc() as u16);
+
+                self.set_acc(self.data.read_flash(addr));
+                PCState::ADVANCE
+            },
+            // 0x84
+            Instruction::DIV_AB => {
+                let (acc, b) = (self.acc(), self.b());
+
+                // ACC and B are undefined after a zero divisor, they keep their values here
+                if let (Some(quotient), Some(remainder)) = (acc.checked_div(b), acc.checked_rem(b))
+                {
+                    self.set_acc(quotient);
+                    self.data.set_sfr_reg(SFR::B, remainder);
+                }
+
+                self.set_carry(false);
+                self.set_overflow(b == 0);
+
+                PCState::ADVANCE
+            },
+            // 0x85
+            Instruction::MOV_DATA_DATA => {
+                // the source comes first in the opcode stream: 0x85 src dest
+                let data = self.read_direct(arg0, insn);
+
+                self.data.write(arg1, data);
+                PCState::ADVANCE
+            },
+            // 0x86, 0x87
+            Instruction::MOV_DATA_INDIRECT_R0 | Instruction::MOV_DATA_INDIRECT_R1 => {
+                let addr = self.read_gpr(Register::try_from(op - 0x86).unwrap());
+
+                self.data.write(arg0, self.data.read_indirect(addr));
+                PCState::ADVANCE
+            },
             // 0x88..=0x8f
             Instruction::MOV_DATA_R0
             | Instruction::MOV_DATA_R1
@@ -338,18 +2211,48 @@ impl CPU {
             | Instruction::MOV_DATA_R5
             | Instruction::MOV_DATA_R6
             | Instruction::MOV_DATA_R7 => {
-                self.data.write(arg0, self.data.get_gpr_reg(Register::try_from(op - 0x88).unwrap()));
+                self.data.write(arg0, self.read_gpr(Register::try_from(op - 0x88).unwrap()));
+                PCState::ADVANCE
+            },
+            // 0x90
+            Instruction::MOV_DPTR_CONST => {
+                self.data.set_sfr_reg(SFR::DPH, arg0);
+                self.data.set_sfr_reg(SFR::DPL, arg1);
+                PCState::ADVANCE
+            },
+            // 0x92
+            Instruction::MOV_BIT_C => {
+                self.write_bit(arg0, self.carry(), insn);
+                PCState::ADVANCE
+            },
+            // 0x93
+            Instruction::MOVC_A_INDIRECT_A_DPTR => {
+                let addr = self.dptr().wrapping_add(self.acc() as u16);
+
+                self.set_acc(self.data.read_flash(addr));
+                PCState::ADVANCE
+            },
+            // 0x94
+            Instruction::SUBB_A_CONST => {
+                self.subb(self.acc(), arg0);
                 PCState::ADVANCE
             },
             // 0x95
             Instruction::SUBB_A_DATA => {
-                let acc = self.data.get_sfr_reg(SFR::ACC);
-                let data = self.data.read(arg0);
+                let acc = self.acc();
+                let data = self.read_direct(arg0, insn);
 
                 self.subb(acc, data);
 
                 PCState::ADVANCE
             },
+            // 0x96, 0x97
+            Instruction::SUBB_A_INDIRECT_R0 | Instruction::SUBB_A_INDIRECT_R1 => {
+                let addr = self.read_gpr(Register::try_from(op - 0x96).unwrap());
+
+                self.subb(self.acc(), self.data.read_indirect(addr));
+                PCState::ADVANCE
+            },
             // 0x98..=0x9f
             Instruction::SUBB_A_R0
             | Instruction::SUBB_A_R1
@@ -359,30 +2262,64 @@ impl CPU {
             | Instruction::SUBB_A_R5
             | Instruction::SUBB_A_R6
             | Instruction::SUBB_A_R7 => {
-                let reg = self.data.get_gpr_reg(Register::try_from(op - 0x98).unwrap());
-                let acc = self.data.get_sfr_reg(SFR::ACC);
+                let reg = self.read_gpr(Register::try_from(op - 0x98).unwrap());
+                let acc = self.acc();
 
                 self.subb(acc, reg);
 
                 PCState::ADVANCE
             },
+            // 0xa0
+            Instruction::ORL_C_COMPLEMENT_BIT => {
+                if let Some(bit) = self.read_bit(arg0, insn) {
+                    self.set_carry(self.carry() | !bit);
+                }
+                PCState::ADVANCE
+            },
+            // 0xa2
+            Instruction::MOV_C_BIT => {
+                if let Some(bit) = self.read_bit(arg0, insn) {
+                    self.set_carry(bit);
+                }
+                PCState::ADVANCE
+            },
             // 0xa3
             Instruction::INC_DPTR => {
-                let mut dpl = self.data.get_sfr_reg(SFR::DPL);
-                let mut dph = self.data.get_sfr_reg(SFR::DPH);
+                // a 16-bit increment, DPL carries into DPH
+                let [dph, dpl] = self.dptr().wrapping_add(1).to_be_bytes();
 
-                let mut dptr = (dph as u16) << 8 | dpl as u16;
+                self.data.set_sfr_reg(SFR::DPL, dpl);
+                self.data.set_sfr_reg(SFR::DPH, dph);
 
-                dptr += 1;
+                PCState::ADVANCE
+            },
+            // 0xa4
+            Instruction::MUL_AB => {
+                let [high, low] = (self.acc() as u16 * self.b() as u16).to_be_bytes();
 
-                dpl = (dptr & 0xff) as u8;
-                dph = ((dptr & 0xff00) >> 8) as u8;
+                self.set_acc(low);
+                self.data.set_sfr_reg(SFR::B, high);
+                self.set_carry(false);
+                self.set_overflow(high != 0);
 
-                self.data.set_sfr_reg(SFR::DPL, dpl);
-                self.data.set_sfr_reg(SFR::DPH, dph);
+                PCState::ADVANCE
+            },
+            // 0xa5
+            Instruction::RESERVED_A5 => match self.unimplemented_policy {
+                UnimplementedPolicy::Halt => {
+                    self.halt(HaltReason::ReservedOpcode);
+                    PCState::HANDLED
+                },
+                _ => self.unimplemented(insn),
+            },
+            // 0xa6, 0xa7
+            Instruction::MOV_INDIRECT_R0_DATA | Instruction::MOV_INDIRECT_R1_DATA => {
+                let addr = self.read_gpr(Register::try_from(op - 0xa6).unwrap());
+                let data = self.read_direct(arg0, insn);
 
+                self.data.write_indirect(addr, data);
                 PCState::ADVANCE
-            }
+            },
             // 0xa8..=0xaf
             Instruction::MOV_R0_DATA
             | Instruction::MOV_R1_DATA
@@ -392,39 +2329,51 @@ impl CPU {
             | Instruction::MOV_R5_DATA
             | Instruction::MOV_R6_DATA
             | Instruction::MOV_R7_DATA => {
-                self.data.set_gpr_reg(Register::try_from(op - 0xa8).unwrap(), self.data.read(arg0));
+                let data = self.read_direct(arg0, insn);
 
+                self.write_gpr(Register::try_from(op - 0xa8).unwrap(), data);
+
+                PCState::ADVANCE
+            },
+            // 0xb0
+            Instruction::ANL_C_COMPLEMENT_BIT => {
+                if let Some(bit) = self.read_bit(arg0, insn) {
+                    self.set_carry(self.carry() & !bit);
+                }
+                PCState::ADVANCE
+            },
+            // 0xb2
+            Instruction::CPL_BIT => {
+                if let Some(bit) = self.read_bit(arg0, insn) {
+                    self.write_bit(arg0, !bit, insn);
+                }
+                PCState::ADVANCE
+            },
+            // 0xb3
+            Instruction::CPL_C => {
+                self.set_carry(!self.carry());
                 PCState::ADVANCE
             },
+            // 0xb4
+            Instruction::CJNE_A_CONST_CODE => {
+                self.cjne(self.acc(), arg0, arg1);
+                PCState::HANDLED
+            },
             // 0xb5
             Instruction::CJNE_A_DATA_CODE => {
-                self.pc += 3;
-                let acc = self.data.get_sfr_reg(SFR::ACC);
-
-                debug!(
-                    "CJNE_A_DATA_CODE: ACC={:#04x}; DATA={:#04x}, CODE={:#04x}",
-                    acc, arg0, arg1
-                );
-
-                if acc != arg0 {
-                    self.pc += arg1 as usize;
-                    debug!("CJNE_A_DATA_CODE: acc != arg0");
-                }
+                let data = self.read_direct(arg0, insn);
 
-                let mut psw = self.data.get_sfr_reg(SFR::PSW);
-                debug!("PSW: {:#010b}", psw);
-
-                if acc < arg0 {
-                    psw.set_bit(7);
-                } else {
-                    psw.clear_bit(7);
-                }
-                debug!("PSW: {:#010b}", psw);
+                self.cjne(self.acc(), data, arg1);
+                PCState::HANDLED
+            },
+            // 0xb6, 0xb7
+            Instruction::CJNE_INDIRECT_R0_CONST_CODE | Instruction::CJNE_INDIRECT_R1_CONST_CODE => {
+                let addr = self.read_gpr(Register::try_from(op - 0xb6).unwrap());
 
-                self.data.set_sfr_reg(SFR::PSW, psw);
+                self.cjne(self.data.read_indirect(addr), arg0, arg1);
                 PCState::HANDLED
             },
-            // 0xb8..= 0xbf
+            // 0xb8..=0xbf
             Instruction::CJNE_R0_CONST_CODE
             | Instruction::CJNE_R1_CONST_CODE
             | Instruction::CJNE_R2_CONST_CODE
@@ -433,56 +2382,139 @@ impl CPU {
             | Instruction::CJNE_R5_CONST_CODE
             | Instruction::CJNE_R6_CONST_CODE
             | Instruction::CJNE_R7_CONST_CODE => {
-                self.pc += 3;
-                let data = self.data.get_gpr_reg(Register::try_from(op - 0xb8).unwrap());
-                let mut psw = self.data.get_sfr_reg(SFR::PSW);
-
-                if data != arg0 {
-                    self.pc += arg1 as usize;
-                }
-
-                if data < arg0 {
-                    psw.set_bit(7);
-                } else {
-                    psw.clear_bit(7);
-                }
-
-                self.data.set_sfr_reg(SFR::PSW, psw);
+                let data = self.read_gpr(Register::try_from(op - 0xb8).unwrap());
 
+                self.cjne(data, arg0, arg1);
                 PCState::HANDLED
-            }
+            },
             // 0xc0
             Instruction::PUSH_DATA => {
-                let sp = self.data.get_sfr_reg(SFR::SP) + 1;
+                // SP is incremented before the source is read, PUSH SP stores the new SP
+                let sp = self.sp().wrapping_add(1);
 
                 self.data.set_sfr_reg(SFR::SP, sp);
-                self.data.write(sp, self.data.read(arg0));
+
+                let data = self.read_direct(arg0, insn);
+
+                self.data.write_indirect(sp, data);
+                PCState::ADVANCE
+            },
+            // 0xc2
+            Instruction::CLR_BIT => {
+                self.write_bit(arg0, false, insn);
+                PCState::ADVANCE
+            },
+            // 0xc3
+            Instruction::CLR_C => {
+                self.set_carry(false);
+                PCState::ADVANCE
+            },
+            // 0xc4
+            Instruction::SWAP_A => {
+                let acc = self.acc();
+
+                self.set_acc(acc.rotate_left(4));
+                PCState::ADVANCE
+            },
+            // 0xc5
+            Instruction::XCH_A_DATA => {
+                let data = self.read_direct(arg0, insn);
+
+                self.data.write(arg0, self.acc());
+                self.set_acc(data);
+                PCState::ADVANCE
+            },
+            // 0xc6, 0xc7
+            Instruction::XCH_A_INDIRECT_R0 | Instruction::XCH_A_INDIRECT_R1 => {
+                let addr = self.read_gpr(Register::try_from(op - 0xc6).unwrap());
+                let data = self.data.read_indirect(addr);
+
+                self.data.write_indirect(addr, self.acc());
+                self.set_acc(data);
+                PCState::ADVANCE
+            },
+            // 0xc8..=0xcf
+            Instruction::XCH_A_R0
+            | Instruction::XCH_A_R1
+            | Instruction::XCH_A_R2
+            | Instruction::XCH_A_R3
+            | Instruction::XCH_A_R4
+            | Instruction::XCH_A_R5
+            | Instruction::XCH_A_R6
+            | Instruction::XCH_A_R7 => {
+                let reg = Register::try_from(op - 0xc8).unwrap();
+                let data = self.read_gpr(reg);
+
+                self.write_gpr(reg, self.acc());
+                self.set_acc(data);
                 PCState::ADVANCE
             },
             // 0xd0
             Instruction::POP_DATA => {
-                let sp = self.data.get_sfr_reg(SFR::SP);
-                let data = self.data.read(sp);
+                // SP is decremented before the destination is written, POP SP ends up popped
+                let sp = self.sp();
+                let data = self.data.read_indirect(sp);
 
-                self.data.set_sfr_reg(SFR::SP, sp - 1);
+                self.data.set_sfr_reg(SFR::SP, sp.wrapping_sub(1));
                 self.data.write(arg0, data);
                 PCState::ADVANCE
             },
+            // 0xd2
+            Instruction::SETB_BIT => {
+                self.write_bit(arg0, true, insn);
+                PCState::ADVANCE
+            },
+            // 0xd3
+            Instruction::SETB_C => {
+                self.set_carry(true);
+                PCState::ADVANCE
+            },
+            // 0xd4
+            Instruction::DA_A => {
+                let mut res = self.acc() as u16;
+
+                if res & 0x0f > 0x09 || self.aux_carry() {
+                    res += 0x06;
+                }
+
+                if res >> 4 > 0x09 || self.carry() {
+                    res += 0x60;
+                }
+
+                self.set_acc(res as u8);
+
+                // DA only ever sets CY, a decimal carry from the ADD stays
+                if res > 0xff {
+                    self.set_carry(true);
+                }
+
+                PCState::ADVANCE
+            },
             // 0xd5
             Instruction::DJNZ_DATA_CODE => {
-                self.pc += 2;
-                let mut data = self.data.read(arg0);
+                self.advance_pc(3);
+
+                let data = self.read_direct(arg0, insn).wrapping_sub(1);
 
-                data -= 1;
                 self.data.write(arg0, data);
 
-                if data > 0 {
-                    self.pc += arg1 as usize;
+                if data != 0 {
+                    self.jump_relative(arg1);
                 }
 
                 PCState::HANDLED
             },
-            // 0xd8..=0xda
+            // 0xd6, 0xd7
+            Instruction::XCHD_A_INDIRECT_R0 | Instruction::XCHD_A_INDIRECT_R1 => {
+                let addr = self.read_gpr(Register::try_from(op - 0xd6).unwrap());
+                let (acc, data) = (self.acc(), self.data.read_indirect(addr));
+
+                // only the low nibbles trade places
+                self.data.write_indirect(addr, data & 0xf0 | acc & 0x0f);
+                self.set_acc(acc & 0xf0 | data & 0x0f);
+                PCState::ADVANCE
+            },
+            // 0xd8..=0xdf
             Instruction::DJNZ_R0_CODE
             | Instruction::DJNZ_R1_CODE
             | Instruction::DJNZ_R2_CODE
@@ -491,106 +2523,154 @@ impl CPU {
             | Instruction::DJNZ_R5_CODE
             | Instruction::DJNZ_R6_CODE
             | Instruction::DJNZ_R7_CODE => {
-                self.pc += 2;
+                self.advance_pc(2);
+
                 let reg = Register::try_from(op - 0xd8).unwrap();
-                let mut data = self.data.get_gpr_reg(reg);
+                let data = self.read_gpr(reg).wrapping_sub(1);
 
-                if data > 0 {
-                    data -= 1;
-                    self.data.set_gpr_reg(reg, data);
-                    self.pc += arg1 as usize;
+                self.write_gpr(reg, data);
+
+                if data != 0 {
+                    self.jump_relative(arg0);
                 }
+
                 PCState::HANDLED
             },
             // 0xe0
             Instruction::MOVX_A_INDIRECT_DPTR => {
-                let mut dptr = 0u16;
-                let dph = self.data.get_sfr_reg(SFR::DPH);
-                let dpl = self.data.get_sfr_reg(SFR::DPL);
-
-                debug!("DPH: {:#04x}, DPL: {:#04x}", dph, dpl);
-
-                dptr |= (dph as u16) << 8;
-                dptr |= dpl as u16;
+                let dptr = self.dptr();
 
                 debug!("DPTR: {:#06x}", dptr);
 
-                let val = self.data.read_flash(dptr);
-
-                debug!("Value: {:#04x}", val);
+                self.set_acc(self.read_xram(dptr));
+                PCState::ADVANCE
+            },
+            // 0xe2, 0xe3
+            Instruction::MOVX_A_INDIRECT_R0 | Instruction::MOVX_A_INDIRECT_R1 => {
+                let addr = self.paged_xram_addr(Register::try_from(op - 0xe2).unwrap());
 
-                self.data.set_sfr_reg(SFR::ACC, val);
+                self.set_acc(self.read_xram(addr));
                 PCState::ADVANCE
             },
-            _ => {
-                self.halt("Unimplemented Instruction", insn);
+            // 0xe4
+            Instruction::CLR_A => {
+                self.set_acc(0x00);
                 PCState::ADVANCE
             },
-        }
-    }
+            // 0xe5
+            Instruction::MOV_A_DATA => {
+                let data = self.read_direct(arg0, insn);
 
-    fn addc(&mut self, lhs: u8, rhs: u8) {
-        let mut psw = self.data.get_sfr_reg(SFR::PSW);
+                self.set_acc(data);
+                PCState::ADVANCE
+            },
+            // 0xe6, 0xe7
+            Instruction::MOV_A_INDIRECT_R0 | Instruction::MOV_A_INDIRECT_R1 => {
+                let addr = self.read_gpr(Register::try_from(op - 0xe6).unwrap());
 
-        let res = lhs.wrapping_add(rhs + psw.get_bit(7));
-        self.data.set_sfr_reg(SFR::ACC, res);
+                self.set_acc(self.data.read_indirect(addr));
+                PCState::ADVANCE
+            },
+            // 0xe8..=0xef
+            Instruction::MOV_A_R0
+            | Instruction::MOV_A_R1
+            | Instruction::MOV_A_R2
+            | Instruction::MOV_A_R3
+            | Instruction::MOV_A_R4
+            | Instruction::MOV_A_R5
+            | Instruction::MOV_A_R6
+            | Instruction::MOV_A_R7 => {
+                self.set_acc(self.read_gpr(Register::try_from(op - 0xe8).unwrap()));
+                PCState::ADVANCE
+            },
+            // 0xf0
+            Instruction::MOVX_INDIRECT_DPTR_A => {
+                self.write_xram(self.dptr(), self.acc());
+                PCState::ADVANCE
+            },
+            // 0xf2, 0xf3
+            Instruction::MOVX_INDIRECT_R0_A | Instruction::MOVX_INDIRECT_R1_A => {
+                let addr = self.paged_xram_addr(Register::try_from(op - 0xf2).unwrap());
 
-        let bit6overflow = rhs.is_bit_set(6) && lhs.is_bit_set(6);
-        let bit7overflow = rhs.is_bit_set(7) && lhs.is_bit_set(7);
+                self.write_xram(addr, self.acc());
+                PCState::ADVANCE
+            },
+            // 0xf4
+            Instruction::CPL_A => {
+                let acc = self.acc();
 
-        match (bit6overflow, bit7overflow) {
-            (true, false) => psw.set_bit(7),
-            (false, true) => psw.set_bit(2),
-            _ => (),
-        };
+                self.set_acc(!acc);
+                PCState::ADVANCE
+            },
+            // 0xf5
+            Instruction::MOV_DATA_A => {
+                self.data.write(arg0, self.acc());
+                PCState::ADVANCE
+            },
+            // 0xf6, 0xf7
+            Instruction::MOV_INDIRECT_R0_A | Instruction::MOV_INDIRECT_R1_A => {
+                let addr = self.read_gpr(Register::try_from(op - 0xf6).unwrap());
 
-        self.data.set_sfr_reg(SFR::PSW, psw);
+                self.data.write_indirect(addr, self.acc());
+                PCState::ADVANCE
+            },
+            // 0xf8..=0xff
+            Instruction::MOV_R0_A
+            | Instruction::MOV_R1_A
+            | Instruction::MOV_R2_A
+            | Instruction::MOV_R3_A
+            | Instruction::MOV_R4_A
+            | Instruction::MOV_R5_A
+            | Instruction::MOV_R6_A
+            | Instruction::MOV_R7_A => {
+                self.write_gpr(Register::try_from(op - 0xf8).unwrap(), self.acc());
+                PCState::ADVANCE
+            },
+            _ => self.unimplemented(insn),
+        }
     }
 
-    fn subb(&mut self, lhs: u8, rhs: u8) {
-        let mut psw = self.data.get_sfr_reg(SFR::PSW);
-
-        // lhs - (rhs + carry)
-        let mut res = i32::sub(lhs as i32, (rhs + psw.get_bit(7)) as i32);
-
-        if res < 0 {
-            res += 256;
-            psw.set_bit(7);
-        } else {
-            psw.clear_bit(7)
-        }
+    /// ACC = `lhs` + `rhs` (+ C if `carry_in`), setting CY, AC and OV.
+    fn add(&mut self, lhs: u8, rhs: u8, carry_in: bool) {
+        let (res, flags) = arithmetics::add(lhs, rhs, carry_in && self.carry());
 
-        self.data.set_sfr_reg(SFR::ACC, (res & 0xFF) as u8);
+        self.set_acc(res);
+        self.set_carry(flags.contains(ArithmeticOpFlags::C));
+        self.set_aux_carry(flags.contains(ArithmeticOpFlags::AC));
+        self.set_overflow(flags.contains(ArithmeticOpFlags::OVERFLOW));
+    }
 
-        let signed_res = lhs.to_signed() - rhs.to_signed();
+    /// Steps over a three-byte CJNE, branching by `rel` if `lhs` != `rhs`. CY is set if `lhs`
+    /// is the smaller one, unsigned.
+    fn cjne(&mut self, lhs: u8, rhs: u8, rel: u8) {
+        self.advance_pc(3);
 
-        if signed_res <= 127 && signed_res >= -128 {
-            psw.clear_bit(2);
-        } else {
-            psw.set_bit(2);
+        if lhs != rhs {
+            self.jump_relative(rel);
         }
 
-        let lhs_low_nibble = lhs & 0xf;
-        let rhs_low_nibble = rhs & 0xf;
+        self.set_carry(lhs < rhs);
+    }
 
-        if lhs_low_nibble < rhs_low_nibble {
-            psw.set_bit(6);
-        } else {
-            psw.clear_bit(6);
-        }
+    /// ACC = `lhs` - `rhs` - C, setting CY, AC and OV.
+    fn subb(&mut self, lhs: u8, rhs: u8) {
+        let (res, flags) = arithmetics::subb(lhs, rhs, self.carry());
 
-        self.data.set_sfr_reg(SFR::PSW, psw);
+        self.set_acc(res);
+        self.set_carry(flags.contains(ArithmeticOpFlags::C));
+        self.set_aux_carry(flags.contains(ArithmeticOpFlags::AC));
+        self.set_overflow(flags.contains(ArithmeticOpFlags::OVERFLOW));
     }
 
-    fn halt(&mut self, msg: &str, insn: Instruction) {
-        let halt_msg = format!("HALT: {} at Instruction::{:?} (PC: {:#06x})", msg, insn, self.pc);
+    fn halt(&mut self, reason: HaltReason) {
+        let halt_msg = format!("HALT: {} (PC: {:#06x})", reason, self.pc);
         let padded_lb = format!("{:=>width$}", "", width = halt_msg.len());
 
         error!("{}", padded_lb);
         error!("{}", halt_msg);
         error!("{}", padded_lb);
 
-        self.halt = true;
+        self.halt = Some(reason);
     }
 }
 
@@ -0,0 +1,169 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+use super::{instruction::Instruction, memory::registers::SFR};
+
+/// Disassembles the instruction at `addr` in `flash`, returning its text, e.g. `MOV A,#0x42`,
+/// and its length in bytes.
+///
+/// Direct addresses of SFRs are given by name (`ACC`), bit addresses as `byte.bit` (`P1.0`,
+/// `0x20.3`). Jump targets are resolved to absolute addresses. Operands read past the end of
+/// `flash` wrap around to its start, like the PC does in the code space.
+pub fn disassemble(flash: &[u8], addr: u16) -> (String, u8) {
+    let byte = |offset: u16| flash[addr.wrapping_add(offset) as usize % flash.len()];
+
+    disassemble_bytes([byte(0), byte(1), byte(2)], addr)
+}
+
+/// Disassembles the instruction made of `bytes`, located at `addr`, like [`disassemble`]. Any
+/// bytes past the instruction's length are ignored.
+pub fn disassemble_bytes(bytes: [u8; 3], addr: u16) -> (String, u8) {
+    let [op, arg0, arg1] = bytes;
+    // with RESERVED_A5, every opcode decodes
+    let insn = Instruction::try_from(op).unwrap();
+    let len = Instruction::get_num_bytes(&insn) as u8;
+
+    let next = addr.wrapping_add(len as u16);
+    let rel = |offset: u8| format!("{:#06x}", next.wrapping_add(offset as i8 as u16));
+    let imm = |data: u8| format!("#{:#04x}", data);
+    let rn = format!("R{}", op & 0x07);
+    let at_ri = format!("@R{}", op & 0x01);
+    let a = "A".to_string();
+    let c = "C".to_string();
+
+    use Instruction::*;
+
+    let operands = match insn {
+        NOP | RET | RETI => vec![],
+        RESERVED_A5 => return (format!("DB {:#04x}", op), len),
+        AJMP1 | AJMP2 | AJMP3 | AJMP4 | AJMP5 | AJMP6 | AJMP7 | AJMP8 | ACALL1 | ACALL2
+        | ACALL3 | ACALL4 | ACALL5 | ACALL6 | ACALL7 | ACALL8 => {
+            vec![format!("{:#06x}", next & 0xf800 | ((op & 0xe0) as u16) << 3 | arg0 as u16)]
+        },
+        LJMP | LCALL => vec![format!("{:#06x}", u16::from_be_bytes([arg0, arg1]))],
+        SJMP | JC | JNC | JZ | JNZ => vec![rel(arg0)],
+        JBC_BIT_ADDR | JB_BIT_CODE | JNB_BIT_CODE => vec![bit(arg0), rel(arg1)],
+        JMP => vec!["@A+DPTR".to_string()],
+
+        RR_A | RRC_A | RL_A | RLC_A | INC_A | DEC_A | SWAP_A | DA_A | CLR_A | CPL_A => vec![a],
+        MUL_AB | DIV_AB => vec!["AB".to_string()],
+        INC_DPTR => vec!["DPTR".to_string()],
+        INC_DATA | DEC_ADDR | PUSH_DATA | POP_DATA => vec![direct(arg0)],
+        INC_INDIRECT_R0 | INC_INDIRECT_R1 | DEC_INDIRECT_R0 | DEC_INDIRECT_R1 => vec![at_ri],
+        INC_R0 | INC_R1 | INC_R2 | INC_R3 | INC_R4 | INC_R5 | INC_R6 | INC_R7 | DEC_R0
+        | DEC_R1 | DEC_R2 | DEC_R3 | DEC_R4 | DEC_R5 | DEC_R6 | DEC_R7 => vec![rn],
+
+        ADD_A_CONST | ADDC_A_CONST | SUBB_A_CONST | ORL_A_CONST | ANL_A_CONST | XRL_A_CONST
+        | MOV_A_CONST => vec![a, imm(arg0)],
+        ADD_A_DATA | ADDC_A_DATA | SUBB_A_DATA | ORL_A_DATA | ANL_A_DATA | XRL_A_DATA
+        | MOV_A_DATA | XCH_A_DATA => vec![a, direct(arg0)],
+        ADD_A_INDIRECT_R0 | ADD_A_INDIRECT_R1 | ADDC_A_INDIRECT_R0 | ADDC_A_INDIRECT_R1
+        | SUBB_A_INDIRECT_R0 | SUBB_A_INDIRECT_R1 | ORL_A_INDIRECT_R0 | ORL_A_INDIRECT_R1
+        | ANL_A_INDIRECT_R0 | ANL_A_INDIRECT_R1 | XRL_A_INDIRECT_R0 | XRL_A_INDIRECT_R1
+        | MOV_A_INDIRECT_R0 | MOV_A_INDIRECT_R1 | XCH_A_INDIRECT_R0 | XCH_A_INDIRECT_R1
+        | XCHD_A_INDIRECT_R0 | XCHD_A_INDIRECT_R1 | MOVX_A_INDIRECT_R0 | MOVX_A_INDIRECT_R1 => {
+            vec![a, at_ri]
+        },
+        ADD_A_R0 | ADD_A_R1 | ADD_A_R2 | ADD_A_R3 | ADD_A_R4 | ADD_A_R5 | ADD_A_R6 | ADD_A_R7
+        | ADDC_A_R0 | ADDC_A_R1 | ADDC_A_R2 | ADDC_A_R3 | ADDC_A_R4 | ADDC_A_R5 | ADDC_A_R6
+        | ADDC_A_R7 | SUBB_A_R0 | SUBB_A_R1 | SUBB_A_R2 | SUBB_A_R3 | SUBB_A_R4 | SUBB_A_R5
+        | SUBB_A_R6 | SUBB_A_R7 | ORL_A_R0 | ORL_A_R1 | ORL_A_R2 | ORL_A_R3 | ORL_A_R4
+        | ORL_A_R5 | ORL_A_R6 | ORL_A_R7 | ANL_A_R0 | ANL_A_R1 | ANL_A_R2 | ANL_A_R3
+        | ANL_A_R4 | ANL_A_R5 | ANL_A_R6 | ANL_A_R7 | XRL_A_R0 | XRL_A_R1 | XRL_A_R2
+        | XRL_A_R3 | XRL_A_R4 | XRL_A_R5 | XRL_A_R6 | XRL_A_R7 | MOV_A_R0 | MOV_A_R1
+        | MOV_A_R2 | MOV_A_R3 | MOV_A_R4 | MOV_A_R5 | MOV_A_R6 | MOV_A_R7 | XCH_A_R0
+        | XCH_A_R1 | XCH_A_R2 | XCH_A_R3 | XCH_A_R4 | XCH_A_R5 | XCH_A_R6 | XCH_A_R7 => {
+            vec![a, rn]
+        },
+        ORL_DATA_A | ANL_DATA_A | XRL_DATA_A | MOV_DATA_A => vec![direct(arg0), a],
+        ORL_DATA_CONST | ANL_DATA_CONST | XRL_DATA_CONST | MOV_DATA_CONST => {
+            vec![direct(arg0), imm(arg1)]
+        },
+
+        ORL_C_BIT | ANL_C_BIT | MOV_C_BIT => vec![c, bit(arg0)],
+        ORL_C_COMPLEMENT_BIT | ANL_C_COMPLEMENT_BIT => vec![c, format!("/{}", bit(arg0))],
+        MOV_BIT_C => vec![bit(arg0), c],
+        CPL_BIT | CLR_BIT | SETB_BIT => vec![bit(arg0)],
+        CPL_C | CLR_C | SETB_C => vec![c],
+
+        MOV_INDIRECT_R0_CONST | MOV_INDIRECT_R1_CONST => vec![at_ri, imm(arg0)],
+        MOV_R0_CONST | MOV_R1_CONST | MOV_R2_CONST | MOV_R3_CONST | MOV_R4_CONST
+        | MOV_R5_CONST | MOV_R6_CONST | MOV_R7_CONST => vec![rn, imm(arg0)],
+        // the source comes first in the encoding
+        MOV_DATA_DATA => vec![direct(arg1), direct(arg0)],
+        MOV_DATA_INDIRECT_R0 | MOV_DATA_INDIRECT_R1 => vec![direct(arg0), at_ri],
+        MOV_DATA_R0 | MOV_DATA_R1 | MOV_DATA_R2 | MOV_DATA_R3 | MOV_DATA_R4 | MOV_DATA_R5
+        | MOV_DATA_R6 | MOV_DATA_R7 => vec![direct(arg0), rn],
+        MOV_INDIRECT_R0_DATA | MOV_INDIRECT_R1_DATA => vec![at_ri, direct(arg0)],
+        MOV_R0_DATA | MOV_R1_DATA | MOV_R2_DATA | MOV_R3_DATA | MOV_R4_DATA | MOV_R5_DATA
+        | MOV_R6_DATA | MOV_R7_DATA => vec![rn, direct(arg0)],
+        MOV_INDIRECT_R0_A | MOV_INDIRECT_R1_A | MOVX_INDIRECT_R0_A | MOVX_INDIRECT_R1_A => {
+            vec![at_ri, a]
+        },
+        MOV_R0_A | MOV_R1_A | MOV_R2_A | MOV_R3_A | MOV_R4_A | MOV_R5_A | MOV_R6_A
+        | MOV_R7_A => vec![rn, a],
+        MOV_DPTR_CONST => {
+            vec!["DPTR".to_string(), format!("#{:#06x}", u16::from_be_bytes([arg0, arg1]))]
+        },
+        MOVC_A_INDIRECT_A_PC => vec![a, "@A+PC".to_string()],
+        MOVC_A_INDIRECT_A_DPTR => vec![a, "@A+DPTR".to_string()],
+        MOVX_A_INDIRECT_DPTR => vec![a, "@DPTR".to_string()],
+        MOVX_INDIRECT_DPTR_A => vec!["@DPTR".to_string(), a],
+
+        CJNE_A_CONST_CODE => vec![a, imm(arg0), rel(arg1)],
+        CJNE_A_DATA_CODE => vec![a, direct(arg0), rel(arg1)],
+        CJNE_INDIRECT_R0_CONST_CODE | CJNE_INDIRECT_R1_CONST_CODE => {
+            vec![at_ri, imm(arg0), rel(arg1)]
+        },
+        CJNE_R0_CONST_CODE | CJNE_R1_CONST_CODE | CJNE_R2_CONST_CODE | CJNE_R3_CONST_CODE
+        | CJNE_R4_CONST_CODE | CJNE_R5_CONST_CODE | CJNE_R6_CONST_CODE | CJNE_R7_CONST_CODE => {
+            vec![rn, imm(arg0), rel(arg1)]
+        },
+        DJNZ_DATA_CODE => vec![direct(arg0), rel(arg1)],
+        DJNZ_R0_CODE | DJNZ_R1_CODE | DJNZ_R2_CODE | DJNZ_R3_CODE | DJNZ_R4_CODE
+        | DJNZ_R5_CODE | DJNZ_R6_CODE | DJNZ_R7_CODE => vec![rn, rel(arg0)],
+    };
+
+    let mnemonic = Instruction::get_mnemonic(&insn);
+
+    if operands.is_empty() {
+        (mnemonic, len)
+    } else {
+        (format!("{} {}", mnemonic, operands.join(",")), len)
+    }
+}
+
+/// Disassembles `count` instructions starting at `addr`, returning each one's address and text.
+pub fn disassemble_range(flash: &[u8], addr: u16, count: usize) -> Vec<(u16, String)> {
+    let mut addr = addr;
+
+    (0..count)
+        .map(|_| {
+            let (text, len) = disassemble(flash, addr);
+            let line = (addr, text);
+
+            addr = addr.wrapping_add(len as u16);
+            line
+        })
+        .collect()
+}
+
+/// A direct address, by name for SFRs.
+fn direct(addr: u8) -> String {
+    match SFR::try_from(addr) {
+        Ok(sfr) if addr >= 0x80 => format!("{:?}", sfr),
+        _ => format!("{:#04x}", addr),
+    }
+}
+
+/// A bit address as `byte.bit`.
+fn bit(bit_addr: u8) -> String {
+    match bit_addr {
+        0x00..=0x7f => format!("{:#04x}.{}", 0x20 + bit_addr / 8, bit_addr % 8),
+        _ => format!("{}.{}", direct(bit_addr & 0xf8), bit_addr % 8),
+    }
+}
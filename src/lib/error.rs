@@ -0,0 +1,57 @@
+use alloc::string::String;
+
+use core::fmt::{Display, Formatter};
+
+/// Errors raised while running the CPU.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorType {
+    /// An address outside of the memory it was used for.
+    InvalidAddr(u16),
+    /// A bit number outside of 0 to 7.
+    InvalidBit(u8),
+    /// A malformed line (1-based) in an input script.
+    InvalidScript(usize, String),
+    /// RET or RETI popped a return address outside the code space.
+    CorruptReturnAddress(u16),
+    /// A line of assembly that couldn't be assembled.
+    Assembly(String),
+    /// The PC landed on an operand byte of an instruction executed before.
+    MisalignedInstruction(u16),
+    /// A malformed record (1-based line) in an Intel HEX file.
+    InvalidHex(usize, String),
+    /// A file that couldn't be read, with the reason.
+    FileLoad(String, String),
+    /// A reader that failed, with the reason.
+    Io(String),
+    /// A saved state that couldn't be restored, with the reason.
+    InvalidState(String),
+    /// A malformed or unexpected [`PortMessage`](super::ws::PortMessage), with the reason.
+    InvalidMessage(String),
+}
+
+impl Display for ErrorType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ErrorType::InvalidAddr(addr) => write!(f, "invalid address {:#06x}", addr),
+            ErrorType::InvalidBit(bit) => write!(f, "invalid bit {}", bit),
+            ErrorType::InvalidScript(line, msg) => write!(f, "script line {}: {}", line, msg),
+            ErrorType::CorruptReturnAddress(addr) => {
+                write!(f, "corrupt return address {:#06x}", addr)
+            },
+            ErrorType::Assembly(msg) => write!(f, "assembly: {}", msg),
+            ErrorType::MisalignedInstruction(addr) => {
+                write!(f, "execution of operand byte {:#06x}", addr)
+            },
+            ErrorType::InvalidHex(line, msg) => write!(f, "HEX line {}: {}", line, msg),
+            ErrorType::FileLoad(path, msg) => write!(f, "failed to load {:?}: {}", path, msg),
+            ErrorType::Io(msg) => write!(f, "read failed: {}", msg),
+            ErrorType::InvalidState(msg) => write!(f, "invalid saved state: {}", msg),
+            ErrorType::InvalidMessage(msg) => write!(f, "invalid port message: {}", msg),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ErrorType {}
+
+pub type Result<T> = core::result::Result<T, ErrorType>;
@@ -0,0 +1,48 @@
+use std::sync::mpsc::{self, Sender};
+
+use super::{
+    cpu::{ExitReason, CPU},
+    error::Result,
+    uart::{SerialBackend, UartFrame},
+};
+
+/// What [`run_firmware`] observed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FirmwareResult {
+    pub exit: ExitReason,
+    /// Every byte transmitted on UART0, in order.
+    pub uart0: Vec<u8>,
+}
+
+/// Forwards transmitted frames into a channel, receives nothing.
+struct ChannelBackend {
+    tx: Sender<UartFrame>,
+}
+
+impl SerialBackend for ChannelBackend {
+    fn on_tx(&mut self, frame: UartFrame) {
+        // the receiver outlives the CPU in run_firmware
+        self.tx.send(frame).unwrap();
+    }
+
+    fn poll_rx(&mut self) -> Option<UartFrame> {
+        None
+    }
+}
+
+/// Loads the HEX file at `path` into a fresh CPU and runs it for up to `max_cycles` machine
+/// cycles, capturing what it transmits on UART0. For black-box firmware tests.
+pub fn run_firmware(path: &str, max_cycles: u64) -> Result<FirmwareResult> {
+    let (tx, rx) = mpsc::channel();
+    let mut cpu = CPU::init();
+
+    cpu.load_from_file(path)?;
+    cpu.set_serial_backend(0, Box::new(ChannelBackend { tx }));
+    cpu.break_after_cycles(max_cycles);
+
+    let exit = cpu.run();
+
+    drop(cpu);
+
+    Ok(FirmwareResult { exit, uart0: rx.iter().map(|frame| frame.data).collect() })
+}
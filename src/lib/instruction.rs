@@ -1,9 +1,16 @@
 #![allow(dead_code)]
 
+use alloc::{
+    format,
+    string::{String, ToString},
+};
+
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 
+use super::memory::registers::PSWFlags;
+
 #[allow(non_camel_case_types)]
-#[derive(Debug, Clone, Copy, TryFromPrimitive, IntoPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, IntoPrimitive)]
 #[repr(u8)]
 pub enum Instruction {
     NOP = 0x00,
@@ -172,7 +179,9 @@ pub enum Instruction {
     MOV_C_BIT = 0xa2,
     INC_DPTR = 0xa3,
     MUL_AB = 0xa4,
-    // 0xa5 => reserved
+    /// Reserved, not part of the MCS-51 instruction set. Executed as one byte, see
+    /// [`ExitReason::ReservedOpcode`](super::cpu::ExitReason::ReservedOpcode).
+    RESERVED_A5 = 0xa5,
     /// Asm: MOV @R0, data addr
     MOV_INDIRECT_R0_DATA = 0xa6,
     MOV_INDIRECT_R1_DATA = 0xa7,
@@ -267,7 +276,198 @@ pub enum Instruction {
     MOV_R7_A = 0xff,
 }
 
+/// Static metadata about an opcode, see [`Instruction::all`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstructionInfo {
+    pub opcode: u8,
+    /// `None` for opcodes that don't decode to an instruction.
+    pub instruction: Option<Instruction>,
+    pub mnemonic: String,
+    pub bytes: usize,
+    pub cycles: usize,
+    /// The PSW flags the instruction defines. P isn't listed, it follows ACC on every write.
+    pub flags: PSWFlags,
+}
+
 impl Instruction {
+    /// Yields metadata for every opcode, in opcode order.
+    pub fn all() -> impl Iterator<Item = InstructionInfo> {
+        (0..=0xffu8).map(|opcode| match Instruction::try_from(opcode) {
+            Ok(insn) => InstructionInfo {
+                opcode,
+                instruction: Some(insn),
+                mnemonic: Instruction::get_mnemonic(&insn),
+                bytes: Instruction::get_num_bytes(&insn),
+                cycles: Instruction::get_num_cycles(&insn),
+                flags: Instruction::get_affected_flags(&insn),
+            },
+            Err(_) => InstructionInfo {
+                opcode,
+                instruction: None,
+                mnemonic: "RESERVED".to_string(),
+                bytes: 1,
+                cycles: 1,
+                flags: PSWFlags::empty(),
+            },
+        })
+    }
+
+    /// The assembler mnemonic, e.g. `MOV` for [`Instruction::MOV_A_CONST`].
+    pub fn get_mnemonic(insn: &Instruction) -> String {
+        let name = format!("{:?}", insn);
+        let mnemonic = name.split('_').next().unwrap();
+
+        mnemonic.trim_end_matches(|c: char| c.is_ascii_digit()).to_string()
+    }
+
+    /// Machine cycles the instruction takes, as accumulated by
+    /// [`CPU::cycle_count`](super::cpu::CPU::cycle_count).
+    pub fn cycles(self) -> u64 {
+        Instruction::get_num_cycles(&self) as u64
+    }
+
+    /// Number of machine cycles (12 clocks on the original 8051) the instruction takes.
+    pub fn get_num_cycles(insn: &Instruction) -> usize {
+        match insn {
+            Instruction::MUL_AB | Instruction::DIV_AB => 4,
+            Instruction::AJMP1
+            | Instruction::AJMP2
+            | Instruction::AJMP3
+            | Instruction::AJMP4
+            | Instruction::AJMP5
+            | Instruction::AJMP6
+            | Instruction::AJMP7
+            | Instruction::AJMP8
+            | Instruction::ACALL1
+            | Instruction::ACALL2
+            | Instruction::ACALL3
+            | Instruction::ACALL4
+            | Instruction::ACALL5
+            | Instruction::ACALL6
+            | Instruction::ACALL7
+            | Instruction::ACALL8
+            | Instruction::LJMP
+            | Instruction::LCALL
+            | Instruction::SJMP
+            | Instruction::JMP
+            | Instruction::RET
+            | Instruction::RETI
+            | Instruction::JBC_BIT_ADDR
+            | Instruction::JB_BIT_CODE
+            | Instruction::JNB_BIT_CODE
+            | Instruction::JC
+            | Instruction::JNC
+            | Instruction::JZ
+            | Instruction::JNZ
+            | Instruction::CJNE_A_CONST_CODE
+            | Instruction::CJNE_A_DATA_CODE
+            | Instruction::CJNE_INDIRECT_R0_CONST_CODE
+            | Instruction::CJNE_INDIRECT_R1_CONST_CODE
+            | Instruction::CJNE_R0_CONST_CODE
+            | Instruction::CJNE_R1_CONST_CODE
+            | Instruction::CJNE_R2_CONST_CODE
+            | Instruction::CJNE_R3_CONST_CODE
+            | Instruction::CJNE_R4_CONST_CODE
+            | Instruction::CJNE_R5_CONST_CODE
+            | Instruction::CJNE_R6_CONST_CODE
+            | Instruction::CJNE_R7_CONST_CODE
+            | Instruction::DJNZ_DATA_CODE
+            | Instruction::DJNZ_R0_CODE
+            | Instruction::DJNZ_R1_CODE
+            | Instruction::DJNZ_R2_CODE
+            | Instruction::DJNZ_R3_CODE
+            | Instruction::DJNZ_R4_CODE
+            | Instruction::DJNZ_R5_CODE
+            | Instruction::DJNZ_R6_CODE
+            | Instruction::DJNZ_R7_CODE
+            | Instruction::MOVC_A_INDIRECT_A_PC
+            | Instruction::MOVC_A_INDIRECT_A_DPTR
+            | Instruction::MOVX_A_INDIRECT_DPTR
+            | Instruction::MOVX_A_INDIRECT_R0
+            | Instruction::MOVX_A_INDIRECT_R1
+            | Instruction::MOVX_INDIRECT_DPTR_A
+            | Instruction::MOVX_INDIRECT_R0_A
+            | Instruction::MOVX_INDIRECT_R1_A
+            | Instruction::INC_DPTR
+            | Instruction::MOV_DPTR_CONST
+            | Instruction::PUSH_DATA
+            | Instruction::POP_DATA
+            | Instruction::ORL_C_BIT
+            | Instruction::ORL_C_COMPLEMENT_BIT
+            | Instruction::ANL_C_BIT
+            | Instruction::ANL_C_COMPLEMENT_BIT
+            | Instruction::MOV_BIT_C
+            | Instruction::ORL_DATA_CONST
+            | Instruction::ANL_DATA_CONST
+            | Instruction::XRL_DATA_CONST
+            | Instruction::MOV_DATA_CONST
+            | Instruction::MOV_DATA_DATA
+            | Instruction::MOV_DATA_INDIRECT_R0
+            | Instruction::MOV_DATA_INDIRECT_R1
+            | Instruction::MOV_DATA_R0
+            | Instruction::MOV_DATA_R1
+            | Instruction::MOV_DATA_R2
+            | Instruction::MOV_DATA_R3
+            | Instruction::MOV_DATA_R4
+            | Instruction::MOV_DATA_R5
+            | Instruction::MOV_DATA_R6
+            | Instruction::MOV_DATA_R7
+            | Instruction::MOV_INDIRECT_R0_DATA
+            | Instruction::MOV_INDIRECT_R1_DATA
+            | Instruction::MOV_R0_DATA
+            | Instruction::MOV_R1_DATA
+            | Instruction::MOV_R2_DATA
+            | Instruction::MOV_R3_DATA
+            | Instruction::MOV_R4_DATA
+            | Instruction::MOV_R5_DATA
+            | Instruction::MOV_R6_DATA
+            | Instruction::MOV_R7_DATA => 2,
+            _ => 1,
+        }
+    }
+
+    /// Whether the instruction reads its direct operand, modifies it and writes it back. On
+    /// port SFRs these read the output latch instead of the pins.
+    pub fn is_read_modify_write(insn: &Instruction) -> bool {
+        matches!(
+            insn,
+            Instruction::INC_DATA
+                | Instruction::DEC_ADDR
+                | Instruction::ANL_DATA_A
+                | Instruction::ANL_DATA_CONST
+                | Instruction::ORL_DATA_A
+                | Instruction::ORL_DATA_CONST
+                | Instruction::XRL_DATA_A
+                | Instruction::XRL_DATA_CONST
+                | Instruction::DJNZ_DATA_CODE
+                | Instruction::JBC_BIT_ADDR
+                | Instruction::CPL_BIT
+                | Instruction::CLR_BIT
+                | Instruction::SETB_BIT
+                | Instruction::MOV_BIT_C
+        )
+    }
+
+    /// The PSW flags the instruction defines, see [`InstructionInfo::flags`].
+    pub fn get_affected_flags(insn: &Instruction) -> PSWFlags {
+        match Instruction::get_mnemonic(insn).as_str() {
+            "ADD" | "ADDC" | "SUBB" => PSWFlags::CY | PSWFlags::AC | PSWFlags::OV,
+            "MUL" | "DIV" => PSWFlags::CY | PSWFlags::OV,
+            "DA" | "RRC" | "RLC" | "CJNE" => PSWFlags::CY,
+            _ => match insn {
+                Instruction::SETB_C
+                | Instruction::CLR_C
+                | Instruction::CPL_C
+                | Instruction::ANL_C_BIT
+                | Instruction::ANL_C_COMPLEMENT_BIT
+                | Instruction::ORL_C_BIT
+                | Instruction::ORL_C_COMPLEMENT_BIT
+                | Instruction::MOV_C_BIT => PSWFlags::CY,
+                _ => PSWFlags::empty(),
+            },
+        }
+    }
+
     pub fn get_num_bytes(insn: &Instruction) -> usize {
         match insn {
             Instruction::NOP => 1,
@@ -372,8 +572,8 @@ impl Instruction {
             Instruction::XRL_DATA_CONST => 3,
             Instruction::XRL_A_CONST => 2,
             Instruction::XRL_A_DATA => 2,
-            Instruction::XRL_A_INDIRECT_R0 => 2,
-            Instruction::XRL_A_INDIRECT_R1 => 2,
+            Instruction::XRL_A_INDIRECT_R0 => 1,
+            Instruction::XRL_A_INDIRECT_R1 => 1,
             Instruction::XRL_A_R0 => 1,
             Instruction::XRL_A_R1 => 1,
             Instruction::XRL_A_R2 => 1,
@@ -435,6 +635,7 @@ impl Instruction {
             Instruction::MOV_C_BIT => 2,
             Instruction::INC_DPTR => 1,
             Instruction::MUL_AB => 1,
+            Instruction::RESERVED_A5 => 1,
             Instruction::MOV_INDIRECT_R0_DATA => 2,
             Instruction::MOV_INDIRECT_R1_DATA => 2,
             Instruction::MOV_R0_DATA => 2,
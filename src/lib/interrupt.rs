@@ -0,0 +1,137 @@
+use super::memory::registers::SFR;
+
+/// Interrupt sources of the C8051F34x, in order of their natural priority.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InterruptSource {
+    Int0,
+    Timer0,
+    Int1,
+    Timer1,
+    Uart0,
+    Timer2,
+    Spi0,
+    Smbus0,
+    Usb0,
+    Adc0Window,
+    Adc0Conversion,
+    Pca0,
+    Comparator0,
+    Comparator1,
+    Timer3,
+    VbusLevel,
+    Uart1,
+}
+
+impl InterruptSource {
+    /// Every source, in order of natural priority.
+    pub const ALL: [InterruptSource; 17] = [
+        InterruptSource::Int0,
+        InterruptSource::Timer0,
+        InterruptSource::Int1,
+        InterruptSource::Timer1,
+        InterruptSource::Uart0,
+        InterruptSource::Timer2,
+        InterruptSource::Spi0,
+        InterruptSource::Smbus0,
+        InterruptSource::Usb0,
+        InterruptSource::Adc0Window,
+        InterruptSource::Adc0Conversion,
+        InterruptSource::Pca0,
+        InterruptSource::Comparator0,
+        InterruptSource::Comparator1,
+        InterruptSource::Timer3,
+        InterruptSource::VbusLevel,
+        InterruptSource::Uart1,
+    ];
+
+    /// The address control is transferred to when the interrupt is serviced.
+    pub fn vector(self) -> u16 {
+        let idx = InterruptSource::ALL.iter().position(|source| *source == self).unwrap() as u16;
+
+        0x0003 + idx * 8
+    }
+
+    /// The SFR bit that flags the interrupt as pending, if it is visible to firmware. This is
+    /// the bit [`CPU::request_interrupt`](super::cpu::CPU::request_interrupt) sets, see
+    /// [`InterruptSource::flags`] for all of them.
+    pub fn flag(self) -> Option<(SFR, u8)> {
+        match self {
+            InterruptSource::Int0 => Some((SFR::TCON, 1)),
+            InterruptSource::Timer0 => Some((SFR::TCON, 5)),
+            InterruptSource::Int1 => Some((SFR::TCON, 3)),
+            InterruptSource::Timer1 => Some((SFR::TCON, 7)),
+            // RI0, TI0 is the second flag
+            InterruptSource::Uart0 => Some((SFR::SCON0, 0)),
+            // TF2H
+            InterruptSource::Timer2 => Some((SFR::TMR2CN, 7)),
+            // SPIF
+            InterruptSource::Spi0 => Some((SFR::SPI0CN, 7)),
+            // SI
+            InterruptSource::Smbus0 => Some((SFR::SMB0CN, 0)),
+            // flagged through the indirect USB0 registers
+            InterruptSource::Usb0 => None,
+            // AD0WINT
+            InterruptSource::Adc0Window => Some((SFR::ADC0CN, 3)),
+            // AD0INT
+            InterruptSource::Adc0Conversion => Some((SFR::ADC0CN, 5)),
+            // CF
+            InterruptSource::Pca0 => Some((SFR::PCA0CN, 7)),
+            // CP0FIF
+            InterruptSource::Comparator0 => Some((SFR::CPT0CN, 4)),
+            // CP1FIF
+            InterruptSource::Comparator1 => Some((SFR::CPT1CN, 4)),
+            // TF3H
+            InterruptSource::Timer3 => Some((SFR::TMR3CN, 7)),
+            // level sensitive, no flag
+            InterruptSource::VbusLevel => None,
+            // RI1, TI1 is the second flag
+            InterruptSource::Uart1 => Some((SFR::SCON1, 0)),
+        }
+    }
+
+    /// Every SFR bit that flags the interrupt as pending. The UART interrupts are requested by
+    /// either RI or TI, the other sources only have [`InterruptSource::flag`].
+    pub fn flags(self) -> impl Iterator<Item = (SFR, u8)> {
+        let ti = match self {
+            InterruptSource::Uart0 => Some((SFR::SCON0, 1)),
+            InterruptSource::Uart1 => Some((SFR::SCON1, 1)),
+            _ => None,
+        };
+
+        self.flag().into_iter().chain(ti)
+    }
+
+    /// The SFR bit that enables the interrupt, in IE, EIE1 or EIE2.
+    pub fn enable(self) -> (SFR, u8) {
+        let idx = InterruptSource::ALL.iter().position(|source| *source == self).unwrap() as u8;
+
+        // the enable and priority registers follow the natural order, seven sources in IE
+        // (bit 7 being EA), eight in EIE1 and the rest in EIE2
+        match idx {
+            0..=6 => (SFR::IE, idx),
+            7..=14 => (SFR::EIE1, idx - 7),
+            _ => (SFR::EIE2, idx - 15),
+        }
+    }
+
+    /// The SFR bit that gives the interrupt high priority, in IP, EIP1 or EIP2.
+    pub fn priority(self) -> (SFR, u8) {
+        match self.enable() {
+            (SFR::IE, bit) => (SFR::IP, bit),
+            (SFR::EIE1, bit) => (SFR::EIP1, bit),
+            (_, bit) => (SFR::EIP2, bit),
+        }
+    }
+
+    /// Whether vectoring to the interrupt clears its flag. Timer 0/1 overflows always do,
+    /// external interrupts only when edge triggered (IT0/IT1 in TCON); the other flags have to
+    /// be cleared by the handler.
+    pub fn cleared_on_vector(self, tcon: u8) -> bool {
+        match self {
+            InterruptSource::Timer0 | InterruptSource::Timer1 => true,
+            InterruptSource::Int0 => tcon & 0x01 != 0,
+            InterruptSource::Int1 => tcon & 0x04 != 0,
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,62 @@
+/// A byte of one of the memories, as targeted by a [`Fault`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultSite {
+    Flash(u16),
+    /// The direct address space, RAM and SFRs.
+    Ram(u8),
+    /// The indirect address space of `@Ri` and the stack. Above 0x80 this is the upper RAM,
+    /// below it the same bytes as [`FaultSite::Ram`].
+    IndirectRam(u8),
+    Xram(u16),
+}
+
+impl FaultSite {
+    /// The site with the lower half of the indirect RAM named as direct RAM, so both names of
+    /// a byte compare equal.
+    pub fn canonical(self) -> Self {
+        match self {
+            FaultSite::IndirectRam(addr) if addr < 0x80 => FaultSite::Ram(addr),
+            site => site,
+        }
+    }
+}
+
+/// A defective bit, applied to every read of its byte, see
+/// [`CPU::inject_fault`](super::super::cpu::CPU::inject_fault).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// `bit` reads as 1 whatever was written.
+    StuckHigh { at: FaultSite, bit: u8 },
+    /// `bit` reads as 0 whatever was written.
+    StuckLow { at: FaultSite, bit: u8 },
+    /// `bit` reads inverted.
+    FlipOnRead { at: FaultSite, bit: u8 },
+}
+
+impl Fault {
+    pub fn site(self) -> FaultSite {
+        match self {
+            Fault::StuckHigh { at, .. }
+            | Fault::StuckLow { at, .. }
+            | Fault::FlipOnRead { at, .. } => at,
+        }
+    }
+
+    /// The faulty bit, only 0 to 7 are valid.
+    pub fn bit(self) -> u8 {
+        match self {
+            Fault::StuckHigh { bit, .. }
+            | Fault::StuckLow { bit, .. }
+            | Fault::FlipOnRead { bit, .. } => bit,
+        }
+    }
+
+    /// `val` as read through the fault.
+    pub fn apply(self, val: u8) -> u8 {
+        match self {
+            Fault::StuckHigh { bit, .. } => val | 1 << bit,
+            Fault::StuckLow { bit, .. } => val & !(1 << bit),
+            Fault::FlipOnRead { bit, .. } => val ^ 1 << bit,
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use alloc::{string::ToString, vec::Vec};
+
+use super::super::error::{ErrorType, Result};
+
+/// The payload of one data record of an Intel HEX file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    /// 1-based line of the record.
+    pub line: usize,
+    /// Absolute address of the first byte, extended address records applied.
+    pub addr: u32,
+    pub data: Vec<u8>,
+}
+
+/// Decodes an Intel HEX file into its data records, in file order.
+///
+/// Handles data (00), end of file (01) and extended segment and linear address records (02,
+/// 04). Start address records (03, 05) don't matter to an emulator and are skipped, anything
+/// after the end of file record is ignored. Every record's checksum is verified.
+pub fn decode(src: &str) -> Result<Vec<Chunk>> {
+    let mut chunks = Vec::new();
+    let mut base = 0u32;
+
+    for (idx, line) in src.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let invalid = |msg: &str| ErrorType::InvalidHex(idx + 1, msg.to_string());
+
+        let record = line.strip_prefix(':').ok_or_else(|| invalid("missing ':'"))?;
+        let record = hex::decode(record).map_err(|_| invalid("invalid hex digits"))?;
+
+        // byte count, address (big endian), record type, data, checksum
+        if record.len() < 5 || record.len() != record[0] as usize + 5 {
+            return Err(invalid("wrong byte count"));
+        }
+
+        if record.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0 {
+            return Err(invalid("checksum mismatch"));
+        }
+
+        let addr = u16::from_be_bytes([record[1], record[2]]) as u32;
+        let data = &record[4..record.len() - 1];
+
+        match (record[3], data) {
+            (0x00, _) => {
+                chunks.push(Chunk { line: idx + 1, addr: base + addr, data: data.to_vec() })
+            },
+            (0x01, _) => return Ok(chunks),
+            (0x02, &[high, low]) => base = (u16::from_be_bytes([high, low]) as u32) << 4,
+            (0x04, &[high, low]) => base = (u16::from_be_bytes([high, low]) as u32) << 16,
+            (0x02 | 0x04, _) => return Err(invalid("wrong address record length")),
+            (0x03 | 0x05, _) => (),
+            _ => return Err(invalid("unknown record type")),
+        }
+    }
+
+    Err(ErrorType::InvalidHex(src.lines().count(), "missing end of file record".to_string()))
+}
+
+#[cfg(test)]
+mod hex_tests {
+    use alloc::{string::ToString, vec};
+
+    use super::{decode, Chunk};
+    use crate::lib::error::ErrorType;
+
+    #[test]
+    fn segments() {
+        let chunks = decode(
+            ":03000000020100FA\n\
+             :020000040001F9\n\
+             :02001000E422E8\n\
+             :020000021000EC\n\
+             :01000200A558\n\
+             :0400000500000000F7\n\
+             :00000001FF\n\
+             garbage after the end\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            chunks,
+            vec![
+                Chunk { line: 1, addr: 0x0_0000, data: vec![0x02, 0x01, 0x00] },
+                Chunk { line: 3, addr: 0x1_0010, data: vec![0xe4, 0x22] },
+                Chunk { line: 5, addr: 0x1_0002, data: vec![0xa5] },
+            ]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let invalid = |line, msg: &str| Err(ErrorType::InvalidHex(line, msg.to_string()));
+
+        assert_eq!(decode("03000000020100FA\n"), invalid(1, "missing ':'"));
+        assert_eq!(decode(":0300000002010G00FA\n"), invalid(1, "invalid hex digits"));
+        assert_eq!(decode("\n:04000000020100FA\n"), invalid(2, "wrong byte count"));
+        assert_eq!(decode(":03000000020100FB\n"), invalid(1, "checksum mismatch"));
+        assert_eq!(decode(":0100000401FA\n"), invalid(1, "wrong address record length"));
+        assert_eq!(decode(":00000006FA\n"), invalid(1, "unknown record type"));
+        assert_eq!(decode(":03000000020100FA\n"), invalid(1, "missing end of file record"));
+    }
+}
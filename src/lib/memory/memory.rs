@@ -1,53 +1,153 @@
-use std::{
-    fs::File,
-    io::{BufReader, Read},
-};
+use alloc::{vec, vec::Vec};
+#[cfg(feature = "serde")]
+use alloc::string::ToString;
 
 use super::{
-    super::ops::arithmetics::BitOps,
+    super::{
+        error::{ErrorType, Result},
+        ops::arithmetics::BitOps,
+    },
+    fault::{Fault, FaultSite},
+    hex,
     registers::{Register, SFR},
 };
 
 use log::debug;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+pub const MEMORY_FLASH_SIZE: usize = 0x10000;
+/// On-chip XRAM, aliased across the whole 64K external data space.
+pub const MEMORY_XRAM_SIZE: usize = 0x1000;
+/// Code at and above this address is banked, see [`Memory::set_code_banks`].
+pub const CODE_BANK_START: usize = 0x8000;
+
+/// Copies of the SFRs the core reads on nearly every instruction.
+///
+/// Kept in sync by every write into the direct address space, so reading a field always
+/// matches reading the SFR itself.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CoreRegisters {
+    pub acc: u8,
+    pub b: u8,
+    pub psw: u8,
+    pub sp: u8,
+    pub dph: u8,
+    pub dpl: u8,
+}
+
+/// The contents of every memory, see [`Memory::image`].
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryImage {
+    flash: Vec<u8>,
+    code_banks: Vec<Vec<u8>>,
+    bank_select: u8,
+    /// The direct address space, RAM and SFRs.
+    direct: Vec<u8>,
+    upper_ram: Vec<u8>,
+    xram: Vec<u8>,
+}
 
 pub struct Memory {
-    flash: [u8; 0xffff],
-    mem: [u8; 0xff],
+    flash: [u8; MEMORY_FLASH_SIZE],
+    mem: [u8; 0x100],
+    /// The upper 128 bytes of internal RAM, only reachable indirectly, see
+    /// [`Memory::read_indirect`].
+    upper_ram: [u8; 0x80],
+    xram: [u8; MEMORY_XRAM_SIZE],
+    core: CoreRegisters,
+    /// Banks 1 and up of the banked code area, bank 0 is the upper half of `flash`.
+    code_banks: Vec<Vec<u8>>,
+    /// Direct address of the bank select SFR.
+    bank_select: u8,
+    faults: Vec<Fault>,
+    /// Addresses written since the last call to [`Memory::take_writes`].
+    writes: Vec<u8>,
 }
 
 impl Memory {
     pub fn init() -> Self {
-        let mut data_memory = Memory { flash: [0; 0xffff], mem: [0; 0xff] };
-
-        data_memory.set_sfr_reg(SFR::ACC, 0x00);
-        data_memory.set_sfr_reg(SFR::SP, 0x07);
+        let mut data_memory = Memory {
+            flash: [0; MEMORY_FLASH_SIZE],
+            mem: [0; 0x100],
+            upper_ram: [0; 0x80],
+            xram: [0; MEMORY_XRAM_SIZE],
+            core: CoreRegisters::default(),
+            code_banks: Vec::new(),
+            bank_select: 0,
+            faults: Vec::new(),
+            writes: Vec::new(),
+        };
+
+        data_memory.reset_sfrs();
+        data_memory.writes.clear();
 
         data_memory
     }
 
-    pub fn load_from_file(&mut self, file_name: &str) -> bool {
-        let mut reader = BufReader::new(
-            File::open(file_name).expect(format!("Failed to open: {:?}", file_name).as_str()),
-        );
+    /// Puts the SFRs into their reset state. RAM contents are retained.
+    pub fn reset_sfrs(&mut self) {
+        for addr in 0x80..self.mem.len() {
+            self.mem[addr] = 0;
+        }
+        self.core = CoreRegisters::default();
 
-        let mut string: String = String::new();
+        self.set_sfr_reg(SFR::ACC, 0x00);
+        self.set_sfr_reg(SFR::SP, 0x07);
+        // the internal oscillator is enabled out of reset
+        self.set_sfr_reg(SFR::OSCICN, 0x80);
+    }
 
-        reader.read_to_string(&mut string).unwrap();
+    /// Returns the addresses written since the last call, in write order.
+    pub fn take_writes(&mut self) -> Vec<u8> {
+        core::mem::take(&mut self.writes)
+    }
 
-        debug!("HEX FILE CONTENTS:\n{}", string);
+    /// Loads an Intel HEX file into flash, see [`hex::decode`]. Nothing is written if the file
+    /// is malformed or places data outside the code space.
+    ///
+    /// Data at 0x10000 and above goes to the code banks, see [`Memory::set_code_banks`]: bank
+    /// `n` is the part of the 64K page at `n * 0x10000` above [`CODE_BANK_START`], the layout
+    /// banking linkers emit.
+    pub fn load_hex(&mut self, src: &str) -> Result<()> {
+        let chunks = hex::decode(src)?;
+
+        for chunk in &chunks {
+            if self.hex_target(chunk.addr, chunk.data.len()).is_none() {
+                return Err(ErrorType::InvalidHex(chunk.line, "data beyond the code space".into()));
+            }
+        }
 
-        let mut pc_offset = 0;
-        let mut lines = string.lines();
+        for chunk in chunks {
+            let (bank, start) = self.hex_target(chunk.addr, chunk.data.len()).unwrap();
+            let code = match bank {
+                Some(bank) => &mut self.code_banks[bank][..],
+                None => &mut self.flash[..],
+            };
 
-        while let Some(line) = lines.next() {
-            let decoded_hex = hex::decode(&line.replace(":", "")).unwrap();
-            for u8 in decoded_hex {
-                self.flash[pc_offset] = u8;
-                pc_offset += 1;
-            }
+            code[start..start + chunk.data.len()].copy_from_slice(&chunk.data);
         }
 
-        true
+        Ok(())
+    }
+
+    /// Where `len` bytes of HEX data at `addr` are stored: the index into `code_banks`, `None`
+    /// for flash, and the offset. `None` if they don't fit.
+    fn hex_target(&self, addr: u32, len: usize) -> Option<(Option<usize>, usize)> {
+        let (page, offset) = ((addr >> 16) as usize, (addr & 0xffff) as usize);
+
+        if offset + len > MEMORY_FLASH_SIZE {
+            return None;
+        }
+
+        match page {
+            0 => Some((None, offset)),
+            _ if page <= self.code_banks.len() && offset >= CODE_BANK_START => {
+                Some((Some(page - 1), offset - CODE_BANK_START))
+            },
+            _ => None,
+        }
     }
 
     fn get_bit_internal(&self, addr: u8, bit: u8) -> Option<u8> {
@@ -71,30 +171,159 @@ impl Memory {
     }
 
     pub fn cur_reg_bank(&self) -> u8 {
-        let psw = self.get_sfr_reg(SFR::PSW);
+        let psw = self.core.psw;
         let rs0 = psw.get_bit(3);
         let rs1 = psw.get_bit(4);
 
         (rs1 << 1) | rs0
     }
 
+    /// Splits the code space into a common area below [`CODE_BANK_START`] and `banks` banks
+    /// above it, selected by the SFR at direct address `select`. Bank numbers wrap around the
+    /// bank count, the select register resets to bank 0. One bank or less disables banking.
+    ///
+    /// Every code read and write, from fetches to MOVC, goes through the selected bank.
+    pub fn set_code_banks(&mut self, banks: usize, select: u8) {
+        self.code_banks = vec![vec![0; 0x10000 - CODE_BANK_START]; banks.saturating_sub(1)];
+        self.bank_select = select;
+    }
+
+    /// Copies the contents of every memory, code banks included. Injected faults aren't part of
+    /// the contents.
+    #[cfg(feature = "serde")]
+    pub fn image(&self) -> MemoryImage {
+        MemoryImage {
+            flash: self.flash.to_vec(),
+            code_banks: self.code_banks.clone(),
+            bank_select: self.bank_select,
+            direct: self.mem.to_vec(),
+            upper_ram: self.upper_ram.to_vec(),
+            xram: self.xram.to_vec(),
+        }
+    }
+
+    /// Replaces the contents of every memory with `image`, leaving them alone if its sizes don't
+    /// match this memory's.
+    #[cfg(feature = "serde")]
+    pub fn load_image(&mut self, image: MemoryImage) -> Result<()> {
+        let bank_size = 0x10000 - CODE_BANK_START;
+
+        if image.flash.len() != self.flash.len()
+            || image.direct.len() != self.mem.len()
+            || image.upper_ram.len() != self.upper_ram.len()
+            || image.xram.len() != self.xram.len()
+            || image.code_banks.iter().any(|bank| bank.len() != bank_size)
+        {
+            return Err(ErrorType::InvalidState("memory sizes don't match".to_string()));
+        }
+
+        self.flash.copy_from_slice(&image.flash);
+        self.upper_ram.copy_from_slice(&image.upper_ram);
+        self.xram.copy_from_slice(&image.xram);
+        self.code_banks = image.code_banks;
+        self.bank_select = image.bank_select;
+
+        // through store, to refresh the cached core registers
+        for (addr, val) in image.direct.into_iter().enumerate() {
+            self.store(addr as u8, val);
+        }
+
+        Ok(())
+    }
+
+    /// Makes every later read of the faulty byte go through `fault`. Several faults on one byte
+    /// apply in the order they were injected. Faults on the SFRs cached in [`CoreRegisters`]
+    /// only show through reads of the direct address, not through [`Memory::core`].
+    ///
+    /// Fails with [`ErrorType::InvalidBit`] for a bit outside of 0 to 7.
+    pub fn inject_fault(&mut self, fault: Fault) -> Result<()> {
+        if fault.bit() > 7 {
+            return Err(ErrorType::InvalidBit(fault.bit()));
+        }
+
+        self.faults.push(fault);
+
+        Ok(())
+    }
+
+    pub fn clear_faults(&mut self) {
+        self.faults.clear();
+    }
+
+    fn faulty(&self, site: FaultSite, val: u8) -> u8 {
+        self.faults
+            .iter()
+            .filter(|fault| fault.site().canonical() == site)
+            .fold(val, |val, fault| fault.apply(val))
+    }
+
+    /// The selected code bank, `None` for bank 0.
+    fn selected_bank(&self) -> Option<usize> {
+        if self.code_banks.is_empty() {
+            return None;
+        }
+
+        match self.mem[self.bank_select as usize] as usize % (self.code_banks.len() + 1) {
+            0 => None,
+            bank => Some(bank - 1),
+        }
+    }
+
     pub fn read_flash(&self, addr: u16) -> u8 {
-        let val = self.flash[addr as usize];
+        let val = match self.selected_bank() {
+            Some(bank) if addr as usize >= CODE_BANK_START => {
+                self.code_banks[bank][addr as usize - CODE_BANK_START]
+            },
+            _ => self.flash[addr as usize],
+        };
+        let val = self.faulty(FaultSite::Flash(addr), val);
 
         debug!("[FLASH READ: {:#06x}]: {:#04x}", addr, val);
 
         val
     }
 
-    // leaving this if needed
-    // pub fn write_flash(&mut self, addr: u16, val: u8) {
-    //     debug!("[FLASH WRITE: {:#06x}]: {:#04x}", addr, val);
+    /// Reads up to `len` bytes of flash starting at `start` with [`Memory::read_flash`], stopping
+    /// at the end of flash.
+    pub fn flash_range(&self, start: u16, len: usize) -> Vec<u8> {
+        let end = (start as usize + len).min(self.flash.len());
 
-    //     self.mem[addr as usize] = val;
-    // }
+        (start as usize..end).map(|addr| self.read_flash(addr as u16)).collect()
+    }
+
+    pub fn write_flash(&mut self, addr: u16, val: u8) {
+        debug!("[FLASH WRITE: {:#06x}]: {:#04x}", addr, val);
+
+        match self.selected_bank() {
+            Some(bank) if addr as usize >= CODE_BANK_START => {
+                self.code_banks[bank][addr as usize - CODE_BANK_START] = val
+            },
+            _ => self.flash[addr as usize] = val,
+        }
+    }
+
+    pub fn read_xram(&self, addr: u16) -> u8 {
+        let addr = addr % MEMORY_XRAM_SIZE as u16;
+        let val = self.faulty(FaultSite::Xram(addr), self.xram[addr as usize]);
+
+        debug!("[XRAM READ: {:#06x}]: {:#04x}", addr, val);
+
+        val
+    }
+
+    pub fn write_xram(&mut self, addr: u16, val: u8) {
+        debug!("[XRAM WRITE: {:#06x}]: {:#04x}", addr, val);
+
+        self.xram[addr as usize % MEMORY_XRAM_SIZE] = val;
+    }
+
+    /// The direct address space, RAM followed by the SFRs.
+    pub fn direct_memory(&self) -> &[u8] {
+        &self.mem
+    }
 
     pub fn read(&self, addr: u8) -> u8 {
-        let val = self.mem[addr as usize];
+        let val = self.faulty(FaultSite::Ram(addr), self.mem[addr as usize]);
 
         debug!("[READ: {:#04x}]: {:#04x}", addr, val);
 
@@ -104,7 +333,55 @@ impl Memory {
     pub fn write(&mut self, addr: u8, val: u8) {
         debug!("[WRITE: {:#04x}]: {:#04x}", addr, val);
 
+        self.store(addr, val);
+        self.writes.push(addr);
+    }
+
+    /// Reads `addr` in the indirect address space, as `@Ri` and the stack do. Below 0x80 this
+    /// is the RAM of [`Memory::read`], above it the upper 128 bytes of RAM, which share their
+    /// addresses with the SFRs but not their contents.
+    pub fn read_indirect(&self, addr: u8) -> u8 {
+        if addr < 0x80 {
+            return self.read(addr);
+        }
+
+        let val = self.faulty(FaultSite::IndirectRam(addr), self.upper_ram[addr as usize - 0x80]);
+
+        debug!("[INDIRECT READ: {:#04x}]: {:#04x}", addr, val);
+
+        val
+    }
+
+    /// Writes `addr` in the indirect address space, see [`Memory::read_indirect`].
+    pub fn write_indirect(&mut self, addr: u8, val: u8) {
+        if addr < 0x80 {
+            return self.write(addr, val);
+        }
+
+        debug!("[INDIRECT WRITE: {:#04x}]: {:#04x}", addr, val);
+
+        self.upper_ram[addr as usize - 0x80] = val;
+    }
+
+    /// The cached core registers.
+    pub fn core(&self) -> &CoreRegisters {
+        &self.core
+    }
+
+    fn store(&mut self, addr: u8, val: u8) {
         self.mem[addr as usize] = val;
+
+        let cached = match SFR::try_from(addr) {
+            Ok(SFR::ACC) => &mut self.core.acc,
+            Ok(SFR::B) => &mut self.core.b,
+            Ok(SFR::PSW) => &mut self.core.psw,
+            Ok(SFR::SP) => &mut self.core.sp,
+            Ok(SFR::DPH) => &mut self.core.dph,
+            Ok(SFR::DPL) => &mut self.core.dpl,
+            _ => return,
+        };
+
+        *cached = val;
     }
 
     pub fn get_sfr_reg(&self, sfr: SFR) -> u8 {
@@ -117,15 +394,25 @@ impl Memory {
         self.write(sfr.into(), val);
     }
 
+    /// Writes `sfr` without recording the write, for peripherals updating their own registers.
+    pub fn set_sfr_reg_internal(&mut self, sfr: SFR, val: u8) {
+        debug!("[SFR WRITE (internal)]: {:?}", sfr);
+        self.store(sfr.into(), val);
+    }
+
+    /// Address of `reg` in the register bank currently selected by PSW RS1/RS0.
+    pub fn gpr_addr(&self, reg: Register) -> u8 {
+        let reg_addr: u8 = reg.into();
+        self.cur_reg_bank() * 8 + reg_addr
+    }
+
     pub fn get_gpr_reg(&self, reg: Register) -> u8 {
         debug!("[GPR READ]: {:?} at REG BANK {}", reg, self.cur_reg_bank());
-        let reg_addr: u8 = reg.into();
-        self.read(reg_addr * self.cur_reg_bank())
+        self.read(self.gpr_addr(reg))
     }
 
     pub fn set_gpr_reg(&mut self, reg: Register, val: u8) {
         debug!("[GPR WRITE]: {:?} at REG BANK {}", reg, self.cur_reg_bank());
-        let reg_addr: u8 = reg.into();
-        self.write(reg_addr * self.cur_reg_bank(), val);
+        self.write(self.gpr_addr(reg), val);
     }
 }
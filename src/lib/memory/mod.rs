@@ -1,2 +1,4 @@
+pub mod fault;
+pub mod hex;
 pub mod memory;
 pub mod registers;
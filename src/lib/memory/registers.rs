@@ -1,6 +1,7 @@
+use bitflags::bitflags;
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 
-#[derive(Debug, Clone, Copy, TryFromPrimitive, IntoPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, IntoPrimitive)]
 #[repr(u8)]
 pub enum SFR {
     ACC = 0xE0,      // Accumulator
@@ -128,6 +129,31 @@ pub enum SFR {
     XBR2 = 0xE3,     // Port I/O Crossbar Control 2
 }
 
+impl SFR {
+    /// Whether the SFR's bits are individually addressable (bit addresses 0x80-0xFF).
+    pub fn is_bit_addressable(self) -> bool {
+        matches!(
+            self,
+            SFR::P0
+                | SFR::TCON
+                | SFR::P1
+                | SFR::SCON0
+                | SFR::P2
+                | SFR::IE
+                | SFR::P3
+                | SFR::IP
+                | SFR::SMB0CN
+                | SFR::TMR2CN
+                | SFR::PSW
+                | SFR::PCA0CN
+                | SFR::ACC
+                | SFR::ADC0CN
+                | SFR::B
+                | SFR::SPI0CN
+        )
+    }
+}
+
 #[derive(Debug, Clone, Copy, TryFromPrimitive, IntoPrimitive)]
 #[repr(u8)]
 pub enum Register {
@@ -140,3 +166,25 @@ pub enum Register {
     R6 = 0x06,
     R7 = 0x07,
 }
+
+bitflags! {
+    /// The bits of [`SFR::PSW`].
+    pub struct PSWFlags: u8 {
+        /// Parity of the accumulator.
+        const P = 1 << 0;
+        /// User flag 1.
+        const F1 = 1 << 1;
+        /// Overflow.
+        const OV = 1 << 2;
+        /// Register bank select, low bit.
+        const RS0 = 1 << 3;
+        /// Register bank select, high bit.
+        const RS1 = 1 << 4;
+        /// User flag 0.
+        const F0 = 1 << 5;
+        /// Auxiliary carry (carry out of bit 3).
+        const AC = 1 << 6;
+        /// Carry.
+        const CY = 1 << 7;
+    }
+}
@@ -1,4 +1,18 @@
+pub mod assembler;
+pub mod bus;
 pub mod cpu;
+pub mod disasm;
+pub mod error;
+#[cfg(feature = "std")]
+pub mod firmware;
 pub mod instruction;
+pub mod interrupt;
 pub mod memory;
 pub mod ops;
+pub mod ports;
+pub mod script;
+pub mod state;
+pub mod timers;
+pub mod trace;
+pub mod uart;
+pub mod ws;
@@ -1,7 +1,9 @@
-use std::ops::{Range, RangeInclusive};
+use core::ops::{Range, RangeInclusive};
 
 use log::debug;
 
+use super::{super::memory::registers::SFR, bytes::ArithmeticOpFlags};
+
 pub trait BitOps {
     fn get_bit(self, bit: u8) -> u8;
     fn set_bit(&mut self, bit: u8);
@@ -56,7 +58,7 @@ impl BitOps for u8 {
 
     fn is_bit_addressable(self) -> bool {
         debug!("BitOps::is_bit_addressable({:#04x})", self);
-        (self >= 32 && self <= 47) || (self >= 128 && self % 8 == 0)
+        (self >= 32 && self <= 47) || SFR::try_from(self).is_ok_and(SFR::is_bit_addressable)
     }
 
     fn is_bit_set(self, bit: u8) -> bool {
@@ -74,6 +76,215 @@ impl BitOps for u8 {
     }
 }
 
+#[cfg(not(feature = "fast-alu"))]
+pub use serial::{add, subb};
+
+#[cfg(feature = "fast-alu")]
+pub use native::{add, subb};
+
+/// The ALU spelled out one bit at a time, the way the hardware's ripple-carry adder works.
+///
+/// Both functions return the result along with C (carry or borrow out of bit 7), AC (out of bit
+/// 3) and OVERFLOW (the carry or borrow out of bit 6 differing from the one out of bit 7).
+#[cfg_attr(feature = "fast-alu", allow(dead_code))]
+pub mod serial {
+    use super::ArithmeticOpFlags;
+
+    fn set_flags(flags: &mut ArithmeticOpFlags, bit: u8, carry: bool, carry6: &mut bool) {
+        match bit {
+            3 => flags.set(ArithmeticOpFlags::AC, carry),
+            6 => *carry6 = carry,
+            7 => {
+                flags.set(ArithmeticOpFlags::C, carry);
+                flags.set(ArithmeticOpFlags::OVERFLOW, carry != *carry6);
+            },
+            _ => (),
+        }
+    }
+
+    /// `lhs` + `rhs` + `carry`.
+    pub fn add(lhs: u8, rhs: u8, carry: bool) -> (u8, ArithmeticOpFlags) {
+        let mut res = 0u8;
+        let mut flags = ArithmeticOpFlags::empty();
+        let mut carry = carry;
+        let mut carry6 = false;
+
+        for bit in 0..8 {
+            let a = (lhs >> bit) & 1 == 1;
+            let b = (rhs >> bit) & 1 == 1;
+
+            if a ^ b ^ carry {
+                res |= 1 << bit;
+            }
+
+            carry = (a && b) || (carry && (a ^ b));
+            set_flags(&mut flags, bit, carry, &mut carry6);
+        }
+
+        (res, flags)
+    }
+
+    /// `lhs` - `rhs` - `borrow`.
+    pub fn subb(lhs: u8, rhs: u8, borrow: bool) -> (u8, ArithmeticOpFlags) {
+        let mut res = 0u8;
+        let mut flags = ArithmeticOpFlags::empty();
+        let mut borrow = borrow;
+        let mut borrow6 = false;
+
+        for bit in 0..8 {
+            let a = (lhs >> bit) & 1 == 1;
+            let b = (rhs >> bit) & 1 == 1;
+
+            if a ^ b ^ borrow {
+                res |= 1 << bit;
+            }
+
+            borrow = (!a && b) || (borrow && !(a ^ b));
+            set_flags(&mut flags, bit, borrow, &mut borrow6);
+        }
+
+        (res, flags)
+    }
+}
+
+/// The ALU in native integer math, behaving exactly like [`serial`].
+#[cfg(feature = "fast-alu")]
+pub mod native {
+    use super::ArithmeticOpFlags;
+
+    fn flags(carry: bool, aux_carry: bool, signed: i16) -> ArithmeticOpFlags {
+        let mut flags = ArithmeticOpFlags::empty();
+
+        flags.set(ArithmeticOpFlags::C, carry);
+        flags.set(ArithmeticOpFlags::AC, aux_carry);
+        flags.set(ArithmeticOpFlags::OVERFLOW, !(-128..=127).contains(&signed));
+
+        flags
+    }
+
+    /// `lhs` + `rhs` + `carry`.
+    pub fn add(lhs: u8, rhs: u8, carry: bool) -> (u8, ArithmeticOpFlags) {
+        let sum = lhs as u16 + rhs as u16 + carry as u16;
+        let aux_carry = (lhs & 0xf) + (rhs & 0xf) + carry as u8 > 0xf;
+        let signed = lhs as i8 as i16 + rhs as i8 as i16 + carry as i16;
+
+        (sum as u8, flags(sum > 0xff, aux_carry, signed))
+    }
+
+    /// `lhs` - `rhs` - `borrow`.
+    pub fn subb(lhs: u8, rhs: u8, borrow: bool) -> (u8, ArithmeticOpFlags) {
+        let diff = lhs as i16 - rhs as i16 - borrow as i16;
+        let aux_carry = (lhs & 0xf) < (rhs & 0xf) + borrow as u8;
+        let signed = lhs as i8 as i16 - rhs as i8 as i16 - borrow as i16;
+
+        (diff as u8, flags(diff < 0, aux_carry, signed))
+    }
+}
+
+#[cfg(test)]
+mod alu_tests {
+    use super::{add, subb, ArithmeticOpFlags};
+
+    const NONE: ArithmeticOpFlags = ArithmeticOpFlags::empty();
+    const C: ArithmeticOpFlags = ArithmeticOpFlags::C;
+    const AC: ArithmeticOpFlags = ArithmeticOpFlags::AC;
+    const OV: ArithmeticOpFlags = ArithmeticOpFlags::OVERFLOW;
+
+    #[test]
+    fn all_ones_plus_one() {
+        assert_eq!(add(0xff, 0x01, false), (0x00, C.union(AC)));
+        assert_eq!(add(0x01, 0xff, false), (0x00, C.union(AC)));
+        assert_eq!(add(0xff, 0x00, true), (0x00, C.union(AC)));
+        assert_eq!(add(0x0f, 0x01, false), (0x10, AC));
+        assert_eq!(add(0xff, 0xff, true), (0xff, C.union(AC)));
+    }
+
+    #[test]
+    fn sign_boundaries() {
+        assert_eq!(add(0x7f, 0x01, false), (0x80, AC.union(OV)));
+        assert_eq!(add(0x7f, 0x00, true), (0x80, AC.union(OV)));
+        assert_eq!(add(0x80, 0x80, false), (0x00, C.union(OV)));
+        assert_eq!(add(0x80, 0xff, false), (0x7f, C.union(OV)));
+        assert_eq!(add(0x40, 0x40, false), (0x80, OV));
+        assert_eq!(add(0xc0, 0x40, false), (0x00, C));
+    }
+
+    #[test]
+    fn nibble_boundary() {
+        // AC is the carry out of bit 3, whichever bits below it produced it
+        assert_eq!(add(0x08, 0x08, false), (0x10, AC));
+        assert_eq!(add(0x0f, 0x01, false), (0x10, AC));
+        assert_eq!(add(0x18, 0x08, false), (0x20, AC));
+        assert_eq!(add(0x09, 0x07, false), (0x10, AC));
+        assert_eq!(add(0x0f, 0x00, true), (0x10, AC));
+        assert_eq!(add(0x88, 0x88, false), (0x10, C.union(AC).union(OV)));
+        assert_eq!(add(0x07, 0x08, false), (0x0f, NONE));
+        assert_eq!(add(0x0e, 0x01, false), (0x0f, NONE));
+        assert_eq!(add(0x07, 0x07, true), (0x0f, NONE));
+        assert_eq!(add(0xf0, 0x0f, false), (0xff, NONE));
+    }
+
+    #[test]
+    fn plain_sums() {
+        assert_eq!(add(0x00, 0x00, false), (0x00, NONE));
+        assert_eq!(add(0x12, 0x34, false), (0x46, NONE));
+        assert_eq!(add(0xc3, 0xaa, true), (0x6e, C.union(OV)));
+    }
+
+    #[test]
+    fn subb_edge_cases() {
+        assert_eq!(subb(0x00, 0x01, false), (0xff, C.union(AC)));
+        assert_eq!(subb(0x00, 0x00, true), (0xff, C.union(AC)));
+        assert_eq!(subb(0x80, 0x01, false), (0x7f, AC.union(OV)));
+        assert_eq!(subb(0x7f, 0xff, false), (0x80, C.union(OV)));
+        assert_eq!(subb(0xc9, 0x54, true), (0x74, OV));
+        assert_eq!(subb(0x10, 0x01, false), (0x0f, AC));
+        assert_eq!(subb(0x42, 0x42, false), (0x00, NONE));
+    }
+
+    #[test]
+    fn matches_native_arithmetic() {
+        for lhs in 0..=0xffu8 {
+            for rhs in 0..=0xffu8 {
+                for carry in [false, true] {
+                    let (res, flags) = add(lhs, rhs, carry);
+                    let sum = lhs as u16 + rhs as u16 + carry as u16;
+                    let signed = lhs as i8 as i16 + rhs as i8 as i16 + carry as i16;
+
+                    assert_eq!(res, sum as u8);
+                    assert_eq!(flags.contains(C), sum > 0xff);
+                    assert_eq!(flags.contains(AC), (lhs & 0xf) + (rhs & 0xf) + carry as u8 > 0xf);
+                    assert_eq!(flags.contains(OV), !(-128..=127).contains(&signed));
+
+                    let (res, flags) = subb(lhs, rhs, carry);
+                    let diff = lhs as i16 - rhs as i16 - carry as i16;
+                    let signed = lhs as i8 as i16 - rhs as i8 as i16 - carry as i16;
+
+                    assert_eq!(res, diff as u8);
+                    assert_eq!(flags.contains(C), diff < 0);
+                    assert_eq!(flags.contains(AC), (lhs & 0xf) < (rhs & 0xf) + carry as u8);
+                    assert_eq!(flags.contains(OV), !(-128..=127).contains(&signed));
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "fast-alu")]
+    #[test]
+    fn serial_and_native_agree() {
+        use super::{native, serial};
+
+        for lhs in (0..=0xffu8).step_by(3) {
+            for rhs in (0..=0xffu8).step_by(5) {
+                for carry in [false, true] {
+                    assert_eq!(serial::add(lhs, rhs, carry), native::add(lhs, rhs, carry));
+                    assert_eq!(serial::subb(lhs, rhs, carry), native::subb(lhs, rhs, carry));
+                }
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod bitops_tests {
     use super::BitOps;
@@ -87,6 +298,20 @@ mod bitops_tests {
         assert_eq!(byte.get_bit(7), 1);
     }
 
+    #[test]
+    fn is_bit_addressable() {
+        // bit-addressable RAM
+        assert!(0x20u8.is_bit_addressable());
+        assert!(0x2fu8.is_bit_addressable());
+        assert!(!0x30u8.is_bit_addressable());
+        // TCON
+        assert!(0x88u8.is_bit_addressable());
+        // TMOD
+        assert!(!0x89u8.is_bit_addressable());
+        // DPL
+        assert!(!0x82u8.is_bit_addressable());
+    }
+
     #[test]
     fn set_bit() {
         let mut byte = 0u8;
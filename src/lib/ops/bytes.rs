@@ -1,5 +1,5 @@
 use bitflags::bitflags;
-use std::{
+use core::{
     fmt::{Debug, Display, LowerHex},
     ops::{Add, AddAssign, BitAnd},
 };
@@ -48,20 +48,20 @@ pub struct Byte {
 }
 
 impl Debug for Byte {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{:?}", self.value)
         // f.debug_struct("Byte").field("inner_value", &self.inner_value).field("signed", &self.signed).field("flags", &self.flags).finish()
     }
 }
 
 impl Display for Byte {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{:?}", self.value)
     }
 }
 
 impl LowerHex for Byte {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         LowerHex::fmt(&self.value, f)
     }
 }
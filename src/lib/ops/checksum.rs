@@ -0,0 +1,47 @@
+/// Checksum algorithms commonly used by self-test firmware.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumAlgo {
+    /// Sum of all bytes, truncated to 8 bits.
+    Sum8,
+    /// CRC-16-CCITT (polynomial 0x1021, initial value 0xFFFF, no reflection).
+    Crc16Ccitt,
+}
+
+pub fn checksum(data: &[u8], algo: ChecksumAlgo) -> u16 {
+    match algo {
+        ChecksumAlgo::Sum8 => data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) as u16,
+        ChecksumAlgo::Crc16Ccitt => data.iter().fold(0xFFFF, |crc, byte| crc16_ccitt(crc, *byte)),
+    }
+}
+
+fn crc16_ccitt(mut crc: u16, byte: u8) -> u16 {
+    crc ^= (byte as u16) << 8;
+
+    for _ in 0..8 {
+        if crc & 0x8000 != 0 {
+            crc = (crc << 1) ^ 0x1021;
+        } else {
+            crc <<= 1;
+        }
+    }
+
+    crc
+}
+
+#[cfg(test)]
+mod checksum_tests {
+    use super::{checksum, ChecksumAlgo};
+
+    #[test]
+    fn sum8() {
+        assert_eq!(checksum(&[], ChecksumAlgo::Sum8), 0x00);
+        assert_eq!(checksum(&[0x01, 0x02, 0x03], ChecksumAlgo::Sum8), 0x06);
+        assert_eq!(checksum(&[0xff, 0x02], ChecksumAlgo::Sum8), 0x01);
+    }
+
+    #[test]
+    fn crc16_ccitt() {
+        assert_eq!(checksum(&[], ChecksumAlgo::Crc16Ccitt), 0xffff);
+        assert_eq!(checksum(b"123456789", ChecksumAlgo::Crc16Ccitt), 0x29b1);
+    }
+}
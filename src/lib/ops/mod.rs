@@ -1,2 +1,3 @@
 pub mod arithmetics;
-pub mod bytes;
\ No newline at end of file
+pub mod bytes;
+pub mod checksum;
\ No newline at end of file
@@ -0,0 +1,89 @@
+use super::{
+    memory::{memory::Memory, registers::SFR},
+    ops::arithmetics::BitOps,
+};
+
+/// Number of ports, P0-P4.
+pub const PORT_COUNT: usize = 5;
+
+/// The port pins P0-P4. Each port has an output latch, its `Pn` SFR, and a pin level sampled
+/// from the outside.
+///
+/// A pin reads low if either its latch or the outside pulls it low, the ports being
+/// quasi-bidirectional: a high latch only drives high in push-pull mode, see `PnMDOUT`. Read
+/// instructions sample the pins, read-modify-write ones and bit writes use the latch.
+pub struct Ports {
+    /// Levels external devices drive onto P0-P4, high where the pin is left alone.
+    inputs: [u8; PORT_COUNT],
+}
+
+impl Default for Ports {
+    fn default() -> Self {
+        Ports { inputs: [0xff; PORT_COUNT] }
+    }
+}
+
+impl Ports {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The latch SFR of `Pport`.
+    pub fn sfr(port: u8) -> SFR {
+        [SFR::P0, SFR::P1, SFR::P2, SFR::P3, SFR::P4][port as usize]
+    }
+
+    /// The port whose latch lives at the direct address `addr`, if any.
+    pub fn index(addr: u8) -> Option<u8> {
+        match SFR::try_from(addr) {
+            Ok(SFR::P0) => Some(0),
+            Ok(SFR::P1) => Some(1),
+            Ok(SFR::P2) => Some(2),
+            Ok(SFR::P3) => Some(3),
+            Ok(SFR::P4) => Some(4),
+            _ => None,
+        }
+    }
+
+    /// The latch of `Pport`, what firmware last wrote to it.
+    pub fn latch(mem: &Memory, port: u8) -> u8 {
+        mem.get_sfr_reg(Self::sfr(port))
+    }
+
+    /// Level of the pins of `port`, its latch pulled low wherever the outside drives low.
+    pub fn pins(&self, mem: &Memory, port: u8) -> u8 {
+        Self::latch(mem, port) & self.inputs[port as usize]
+    }
+
+    /// What `Pport.bit` drives onto its pin, `None` if it isn't driven strongly. A low latch
+    /// always drives low, a high latch only drives high in push-pull mode.
+    pub fn output(mem: &Memory, port: u8, bit: u8) -> Option<bool> {
+        let mdout = [SFR::P0MDOUT, SFR::P1MDOUT, SFR::P2MDOUT, SFR::P3MDOUT, SFR::P4MDOUT];
+        let push_pull = mem.get_sfr_reg(mdout[port as usize]).is_bit_set(bit);
+
+        match Self::latch(mem, port).is_bit_set(bit) {
+            false => Some(false),
+            true => push_pull.then_some(true),
+        }
+    }
+
+    /// Drives `Pport.bit` to `level` from the outside. Driving high releases the pin.
+    pub fn drive(&mut self, port: u8, bit: u8, level: bool) {
+        let input = &mut self.inputs[port as usize];
+
+        if level {
+            input.set_bit(bit);
+        } else {
+            input.clear_bit(bit);
+        }
+    }
+
+    /// The levels driven from the outside, see [`Ports::drive`].
+    pub fn inputs(&self) -> [u8; PORT_COUNT] {
+        self.inputs
+    }
+
+    pub fn set_inputs(&mut self, inputs: [u8; PORT_COUNT]) {
+        self.inputs = inputs;
+    }
+}
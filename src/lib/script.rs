@@ -0,0 +1,176 @@
+use alloc::{collections::VecDeque, string::ToString, vec::Vec};
+
+use super::{
+    error::{ErrorType, Result},
+    interrupt::InterruptSource,
+};
+
+/// An external event applied to the CPU while a [`Script`] runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptEvent {
+    /// Drives `Pport.bit` to `level` from the outside (`pin P1.0 low`), see
+    /// [`CPU::drive_pin`](super::cpu::CPU::drive_pin).
+    Pin { port: u8, bit: u8, level: bool },
+    /// Receives a byte on UART0 (`uart0 0x41`).
+    Uart0Rx(u8),
+    /// Flags an interrupt request (`irq int0`).
+    Interrupt(InterruptSource),
+}
+
+/// A list of [`ScriptEvent`]s ordered by the time they are due.
+///
+/// Scripts are line based, each line has the form `<time>: <event>`. Empty lines and lines
+/// starting with `#` are ignored. Times are counted in machine cycles, see
+/// [`CPU::cycle_count`](super::cpu::CPU::cycle_count).
+///
+/// ```text
+/// # wait for the firmware to initialize the UART
+/// 100: uart0 0x41
+/// 150: pin P1.0 low
+/// 200: irq int0
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Script {
+    events: VecDeque<(u64, ScriptEvent)>,
+}
+
+impl Script {
+    pub fn parse(script: &str) -> Result<Self> {
+        let mut events = Vec::new();
+
+        for (idx, line) in script.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = |msg: &str| ErrorType::InvalidScript(idx + 1, msg.to_string());
+
+            let (time, event) = line.split_once(':').ok_or_else(|| invalid("missing ':'"))?;
+            let time = parse_number(time.trim()).ok_or_else(|| invalid("invalid time"))?;
+            let mut words = event.split_whitespace();
+
+            let event = match (words.next(), words.next(), words.next(), words.next()) {
+                (Some("pin"), Some(pin), Some(level), None) => {
+                    let (port, bit) = parse_pin(pin).ok_or_else(|| invalid("invalid pin"))?;
+                    let level = match level {
+                        "high" | "1" => true,
+                        "low" | "0" => false,
+                        _ => return Err(invalid("invalid pin level")),
+                    };
+
+                    ScriptEvent::Pin { port, bit, level }
+                },
+                (Some("uart0"), Some(byte), None, None) => {
+                    let byte = parse_number(byte)
+                        .and_then(|byte| u8::try_from(byte).ok())
+                        .ok_or_else(|| invalid("invalid byte"))?;
+
+                    ScriptEvent::Uart0Rx(byte)
+                },
+                (Some("irq"), Some(source), None, None) => ScriptEvent::Interrupt(
+                    parse_interrupt(source).ok_or_else(|| invalid("unknown interrupt"))?,
+                ),
+                _ => return Err(invalid("unknown event")),
+            };
+
+            events.push((time, event));
+        }
+
+        // stable, so events due at the same time keep their script order
+        events.sort_by_key(|(time, _)| *time);
+
+        Ok(Script { events: events.into() })
+    }
+
+    /// Removes and returns the next event due at or before `now`.
+    pub fn next_due(&mut self, now: u64) -> Option<ScriptEvent> {
+        match self.events.front() {
+            Some((time, _)) if *time <= now => self.events.pop_front().map(|(_, event)| event),
+            _ => None,
+        }
+    }
+
+    /// The time the next event is due at.
+    pub fn next_time(&self) -> Option<u64> {
+        self.events.front().map(|(time, _)| *time)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+fn parse_number(number: &str) -> Option<u64> {
+    match number.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => number.parse().ok(),
+    }
+}
+
+/// Parses `Px.y` into its port and bit.
+fn parse_pin(pin: &str) -> Option<(u8, u8)> {
+    let (port, bit) = pin.strip_prefix('P')?.split_once('.')?;
+    let (port, bit) = (port.parse().ok()?, bit.parse().ok()?);
+
+    (port <= 4 && bit <= 7).then_some((port, bit))
+}
+
+fn parse_interrupt(source: &str) -> Option<InterruptSource> {
+    match source {
+        "int0" => Some(InterruptSource::Int0),
+        "timer0" => Some(InterruptSource::Timer0),
+        "int1" => Some(InterruptSource::Int1),
+        "timer1" => Some(InterruptSource::Timer1),
+        "uart0" => Some(InterruptSource::Uart0),
+        "timer2" => Some(InterruptSource::Timer2),
+        "spi0" => Some(InterruptSource::Spi0),
+        "smbus0" => Some(InterruptSource::Smbus0),
+        "adc0_window" => Some(InterruptSource::Adc0Window),
+        "adc0" => Some(InterruptSource::Adc0Conversion),
+        "pca0" => Some(InterruptSource::Pca0),
+        "comparator0" => Some(InterruptSource::Comparator0),
+        "comparator1" => Some(InterruptSource::Comparator1),
+        "timer3" => Some(InterruptSource::Timer3),
+        "uart1" => Some(InterruptSource::Uart1),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod script_tests {
+    use alloc::string::ToString;
+
+    use super::{Script, ScriptEvent};
+    use crate::lib::{error::ErrorType, interrupt::InterruptSource};
+
+    #[test]
+    fn parse() {
+        let mut script =
+            Script::parse("# comment\n\n20: irq int0\n10: pin P1.3 low\n0x10: uart0 0x41\n")
+                .unwrap();
+
+        assert_eq!(script.next_due(9), None);
+        assert_eq!(script.next_due(10), Some(ScriptEvent::Pin { port: 1, bit: 3, level: false }));
+        assert_eq!(script.next_due(30), Some(ScriptEvent::Uart0Rx(0x41)));
+        assert_eq!(script.next_due(30), Some(ScriptEvent::Interrupt(InterruptSource::Int0)));
+        assert!(script.is_empty());
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            Script::parse("10: pin P9.0 low"),
+            Err(ErrorType::InvalidScript(1, "invalid pin".to_string()))
+        );
+        assert_eq!(
+            Script::parse("\n10 uart0 0x41"),
+            Err(ErrorType::InvalidScript(2, "missing ':'".to_string()))
+        );
+        assert_eq!(
+            Script::parse("10: uart0 0x141"),
+            Err(ErrorType::InvalidScript(1, "invalid byte".to_string()))
+        );
+    }
+}
@@ -0,0 +1,81 @@
+use alloc::vec::Vec;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "serde")]
+use super::{memory::memory::MemoryImage, ports::PORT_COUNT};
+use super::memory::registers::SFR;
+
+/// A copy of the CPU state visible to firmware.
+///
+/// Taken by [`CPU::state`](super::cpu::CPU::state).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpuState {
+    pub pc: usize,
+    /// The direct address space, RAM below 0x80 and the SFRs above.
+    pub memory: Vec<u8>,
+}
+
+/// A single difference between two [`CpuState`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateDiff {
+    Pc { before: usize, after: usize },
+    Sfr { sfr: SFR, before: u8, after: u8 },
+    /// A RAM byte, or an address in the SFR range that isn't mapped to a register.
+    Ram { addr: u8, before: u8, after: u8 },
+}
+
+impl CpuState {
+    /// Lists what changed going from `self` to `other`, the PC first, then memory in address
+    /// order.
+    pub fn diff(&self, other: &CpuState) -> Vec<StateDiff> {
+        let mut diffs = Vec::new();
+
+        if self.pc != other.pc {
+            diffs.push(StateDiff::Pc { before: self.pc, after: other.pc });
+        }
+
+        for (addr, (&before, &after)) in self.memory.iter().zip(&other.memory).enumerate() {
+            if before == after {
+                continue;
+            }
+
+            let addr = addr as u8;
+
+            diffs.push(match SFR::try_from(addr) {
+                Ok(sfr) if addr >= 0x80 => StateDiff::Sfr { sfr, before, after },
+                _ => StateDiff::Ram { addr, before, after },
+            });
+        }
+
+        diffs
+    }
+}
+
+/// A saved emulator session, see [`CPU::snapshot`](super::cpu::CPU::snapshot).
+///
+/// Holds the memories, the core's own state and that of the peripherals, down to a UART frame
+/// still being transmitted or a latched timer byte. Host-side setup (the serial backends, a
+/// loaded script, the trace, break conditions) isn't saved.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedState {
+    pub pc: usize,
+    pub retired: u64,
+    pub cycles: u64,
+    pub timers_ticked: u64,
+    pub reset_source: u8,
+    pub mcd_enabled: bool,
+    pub in_service: Vec<bool>,
+    pub interrupt_hold: bool,
+    pub port_inputs: [u8; PORT_COUNT],
+    pub ext_osc_countdown: Option<u64>,
+    pub ext_osc_locked: bool,
+    pub uart_rx_buffers: [u8; 2],
+    pub uart_tx_remaining: [Option<u64>; 2],
+    /// The partner byte latched by an atomic timer read: its address, value and the retired
+    /// instruction count at the read.
+    pub timer_latch: Option<(u8, u8, u64)>,
+    pub memory: MemoryImage,
+}
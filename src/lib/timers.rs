@@ -0,0 +1,152 @@
+use super::{
+    memory::{memory::Memory, registers::SFR},
+    ops::arithmetics::BitOps,
+};
+
+/// Timer 0 or Timer 1, counting machine cycles while its TRn bit in [`SFR::TCON`] is set.
+///
+/// Counts in all four modes of [`SFR::TMOD`]: 13-bit (0), 16-bit (1), 8-bit auto-reload (2) and
+/// split (3). GATE and C/T aren't modelled, the timers always count machine cycles. An overflow
+/// sets TFn, which requests the timer's interrupt.
+pub struct Timer {
+    index: u8,
+}
+
+impl Timer {
+    pub fn new(index: u8) -> Self {
+        Timer { index }
+    }
+
+    /// The low and high byte of the counter.
+    pub fn registers(&self) -> (SFR, SFR) {
+        match self.index {
+            0 => (SFR::TL0, SFR::TH0),
+            _ => (SFR::TL1, SFR::TH1),
+        }
+    }
+
+    pub fn running(&self, mem: &Memory) -> bool {
+        mem.get_sfr_reg(SFR::TCON).is_bit_set(4 + 2 * self.index)
+    }
+
+    /// The M1:M0 bits of the timer's half of [`SFR::TMOD`].
+    pub fn mode(&self, mem: &Memory) -> u8 {
+        (mem.get_sfr_reg(SFR::TMOD) >> (4 * self.index)) & 0b11
+    }
+
+    /// Advances the timer by `cycles` machine cycles, setting TFn on overflow.
+    ///
+    /// In mode 3 Timer 0 splits into TL0, run by TR0 and overflowing into TF0, and TH0, run by
+    /// TR1 and overflowing into TF1. Timer 1 stops in mode 3, and while Timer 0 is split it
+    /// runs without TR1 and never sets TF1.
+    pub fn tick(&self, mem: &mut Memory, cycles: u64) {
+        let (tl, th) = self.registers();
+        let split = Timer::new(0).mode(mem) == 3;
+
+        match (self.index, self.mode(mem)) {
+            (0, 3) => {
+                if self.running(mem) && Self::count8(mem, SFR::TL0, cycles) {
+                    Self::set_overflow_flag(mem, 0);
+                }
+
+                if Timer::new(1).running(mem) && Self::count8(mem, SFR::TH0, cycles) {
+                    Self::set_overflow_flag(mem, 1);
+                }
+            },
+            (_, 3) => (),
+            (_, mode) => {
+                let timer1_free = self.index == 1 && split;
+
+                if !timer1_free && !self.running(mem) {
+                    return;
+                }
+
+                let overflowed = match mode {
+                    0 => Self::count13(mem, tl, th, cycles),
+                    1 => Self::count16(mem, tl, th, cycles),
+                    _ => Self::reload8(mem, tl, th, cycles),
+                };
+
+                if overflowed && !timer1_free {
+                    Self::set_overflow_flag(mem, self.index);
+                }
+            },
+        }
+    }
+
+    /// Machine cycles until [`Timer::tick`] next sets an overflow flag, `None` if the timer
+    /// doesn't count towards one.
+    pub fn cycles_to_overflow(&self, mem: &Memory) -> Option<u64> {
+        let (tl, th) = self.registers();
+        let low = mem.get_sfr_reg(tl) as u64;
+        let high = mem.get_sfr_reg(th) as u64;
+        let split = Timer::new(0).mode(mem) == 3;
+
+        match (self.index, self.mode(mem)) {
+            (0, 3) => {
+                let tl0 = Some(0x100 - low).filter(|_| self.running(mem));
+                let th0 = Some(0x100 - high).filter(|_| Timer::new(1).running(mem));
+
+                tl0.into_iter().chain(th0).min()
+            },
+            (_, 3) => None,
+            _ if !self.running(mem) || self.index == 1 && split => None,
+            (_, 0) => Some(0x2000 - (high << 5 | low & 0x1f)),
+            (_, 1) => Some(0x10000 - (high << 8 | low)),
+            _ => Some(0x100 - low),
+        }
+    }
+
+    /// Counts the 8-bit register `reg`, returns whether it overflowed.
+    fn count8(mem: &mut Memory, reg: SFR, cycles: u64) -> bool {
+        let count = mem.get_sfr_reg(reg) as u64 + cycles;
+
+        mem.set_sfr_reg_internal(reg, count as u8);
+        count > 0xff
+    }
+
+    /// Counts THn:TLn as a 13-bit counter: the 8 bits of THn above the low 5 bits of TLn. The
+    /// upper 3 bits of TLn are left alone.
+    fn count13(mem: &mut Memory, tl: SFR, th: SFR, cycles: u64) -> bool {
+        let low = mem.get_sfr_reg(tl);
+        let count = ((mem.get_sfr_reg(th) as u64) << 5 | (low & 0x1f) as u64) + cycles;
+
+        mem.set_sfr_reg_internal(tl, low & 0xe0 | (count & 0x1f) as u8);
+        mem.set_sfr_reg_internal(th, (count >> 5) as u8);
+        count > 0x1fff
+    }
+
+    fn count16(mem: &mut Memory, tl: SFR, th: SFR, cycles: u64) -> bool {
+        let count = u16::from_be_bytes([mem.get_sfr_reg(th), mem.get_sfr_reg(tl)]) as u64 + cycles;
+        let [high, low] = (count as u16).to_be_bytes();
+
+        mem.set_sfr_reg_internal(tl, low);
+        mem.set_sfr_reg_internal(th, high);
+        count > 0xffff
+    }
+
+    /// Counts TLn, reloading it from THn on every overflow.
+    fn reload8(mem: &mut Memory, tl: SFR, th: SFR, cycles: u64) -> bool {
+        let reload = mem.get_sfr_reg(th) as u64;
+        let count = mem.get_sfr_reg(tl) as u64 + cycles;
+
+        if count <= 0xff {
+            mem.set_sfr_reg_internal(tl, count as u8);
+            return false;
+        }
+
+        // after the first overflow, TLn runs from the reload value up every 256 - THn cycles
+        let since_reload = (count - 0x100) % (0x100 - reload);
+
+        mem.set_sfr_reg_internal(tl, (reload + since_reload) as u8);
+        true
+    }
+
+    /// Sets TF0 or TF1 in [`SFR::TCON`].
+    fn set_overflow_flag(mem: &mut Memory, index: u8) {
+        let mut tcon = mem.get_sfr_reg(SFR::TCON);
+
+        tcon.set_bit(5 + 2 * index);
+        mem.set_sfr_reg_internal(SFR::TCON, tcon);
+    }
+}
@@ -0,0 +1,43 @@
+use alloc::vec::Vec;
+
+use super::instruction::Instruction;
+
+/// An executed instruction, see [`CPU::enable_trace`](super::cpu::CPU::enable_trace).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub pc: u16,
+    pub opcode: u8,
+    pub instruction: Instruction,
+    /// ACC after the instruction.
+    pub acc: u8,
+    /// PSW after the instruction.
+    pub psw: u8,
+}
+
+/// Keeps the last `capacity` [`TraceEntry`]s.
+///
+/// Entries are appended to a buffer of up to twice the capacity, which drops its older half
+/// when full. That keeps pushing cheap and the retained entries contiguous.
+pub struct Trace {
+    capacity: usize,
+    entries: Vec<TraceEntry>,
+}
+
+impl Trace {
+    pub fn new(capacity: usize) -> Self {
+        Trace { capacity, entries: Vec::with_capacity(2 * capacity) }
+    }
+
+    pub fn push(&mut self, entry: TraceEntry) {
+        if self.entries.len() == 2 * self.capacity {
+            self.entries.drain(..self.capacity);
+        }
+
+        self.entries.push(entry);
+    }
+
+    /// The retained entries, oldest first.
+    pub fn entries(&self) -> &[TraceEntry] {
+        &self.entries[self.entries.len().saturating_sub(self.capacity)..]
+    }
+}
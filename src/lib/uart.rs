@@ -0,0 +1,291 @@
+use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
+
+use log::debug;
+
+use super::{
+    memory::{memory::Memory, registers::SFR},
+    ops::arithmetics::BitOps,
+};
+
+/// UART0 framing, selected by SM0/SM1 in [`SFR::SCON0`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UartMode {
+    /// Mode 0, 8 data bits shifted out synchronously.
+    ShiftRegister,
+    /// Mode 1, 8 data bits at a variable baud rate.
+    EightBit,
+    /// Mode 2, 8 data bits plus a 9th bit at a fixed baud rate.
+    NineBitFixed,
+    /// Mode 3, 8 data bits plus a 9th bit at a variable baud rate.
+    NineBitVariable,
+}
+
+impl UartMode {
+    pub fn from_scon(scon: u8) -> Self {
+        match scon >> 6 {
+            0b00 => UartMode::ShiftRegister,
+            0b01 => UartMode::EightBit,
+            0b10 => UartMode::NineBitFixed,
+            _ => UartMode::NineBitVariable,
+        }
+    }
+
+    pub fn has_ninth_bit(self) -> bool {
+        matches!(self, UartMode::NineBitFixed | UartMode::NineBitVariable)
+    }
+}
+
+/// A character on the serial line. `ninth` is the 9th data bit (TB8/RB8) in framings that have
+/// one, and `None` otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UartFrame {
+    pub data: u8,
+    pub ninth: Option<bool>,
+}
+
+/// The other end of a UART's serial line, see
+/// [`CPU::set_serial_backend`](super::cpu::CPU::set_serial_backend).
+pub trait SerialBackend {
+    /// Called with every frame the firmware transmits.
+    fn on_tx(&mut self, frame: UartFrame);
+    /// Polled for the next frame to receive once per cycle, as long as RI is clear.
+    fn poll_rx(&mut self) -> Option<UartFrame>;
+}
+
+// SCONn bits, named after their UART0 function
+const SCON_RI: u8 = 0;
+const SCON_TI: u8 = 1;
+const SCON_RB8: u8 = 2;
+const SCON_TB8: u8 = 3;
+const SCON_SM2: u8 = 5;
+
+// SMOD1 bits
+const SMOD1_XBE1: u8 = 1;
+const SMOD1_MCE1: u8 = 7;
+
+/// PCON bit doubling the UART0 baud rate.
+const PCON_SMOD: u8 = 7;
+/// Oscillator clocks per machine cycle.
+const CLOCKS_PER_CYCLE: u64 = 12;
+
+/// One of the two UARTs.
+///
+/// UART0 is framed by its SCON0 mode bits, UART1 by [`SFR::SMOD1`], which selects 5 to 8 data
+/// bits and an optional extra bit. The extra bit is sent and received through the same SCON bits
+/// as the 9th bit of UART0.
+///
+/// Transmitted frames go to the [`SerialBackend`] if there is one, and are queued for
+/// [`Uart::take_tx`] otherwise. TI follows once the frame would be out on the line, see
+/// [`Uart::tick`].
+pub struct Uart {
+    index: u8,
+    /// The byte firmware reads from SBUF, writes go to the transmitter.
+    rx_buffer: u8,
+    /// Machine cycles until the frame being transmitted is out and TI is flagged.
+    tx_remaining: Option<u64>,
+    tx: VecDeque<UartFrame>,
+    backend: Option<Box<dyn SerialBackend>>,
+}
+
+impl Uart {
+    pub fn new(index: u8) -> Self {
+        assert!(index < 2, "there is no UART{}", index);
+
+        Uart { index, rx_buffer: 0, tx_remaining: None, tx: VecDeque::new(), backend: None }
+    }
+
+    pub fn set_backend(&mut self, backend: Box<dyn SerialBackend>) {
+        self.backend = Some(backend);
+    }
+
+    /// Clears the receive buffer along with the SFRs, and drops the TI of a frame still being
+    /// transmitted. Frames waiting in the transmit queue have already left the chip and are kept.
+    pub fn reset(&mut self) {
+        self.rx_buffer = 0;
+        self.tx_remaining = None;
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn rx_buffer(&self) -> u8 {
+        self.rx_buffer
+    }
+
+    /// Puts back a receive buffer and transmission saved with [`Uart::rx_buffer`] and
+    /// [`Uart::tx_remaining`].
+    #[cfg(feature = "serde")]
+    pub fn restore(&mut self, rx_buffer: u8, tx_remaining: Option<u64>) {
+        self.rx_buffer = rx_buffer;
+        self.tx_remaining = tx_remaining;
+    }
+
+    pub fn scon(&self) -> SFR {
+        [SFR::SCON0, SFR::SCON1][self.index as usize]
+    }
+
+    pub fn sbuf(&self) -> SFR {
+        [SFR::SBUF0, SFR::SBUF1][self.index as usize]
+    }
+
+    fn data_bits(&self, mem: &Memory) -> u8 {
+        match self.index {
+            0 => 8,
+            _ => 5 + ((mem.get_sfr_reg(SFR::SMOD1) >> 2) & 0b11),
+        }
+    }
+
+    fn has_ninth_bit(&self, mem: &Memory) -> bool {
+        match self.index {
+            0 => UartMode::from_scon(mem.get_sfr_reg(SFR::SCON0)).has_ninth_bit(),
+            _ => mem.get_sfr_reg(SFR::SMOD1).is_bit_set(SMOD1_XBE1),
+        }
+    }
+
+    /// Whether multiprocessor communication is on, SM2 for UART0 and MCE1 for UART1.
+    fn multiprocessor(&self, mem: &Memory) -> bool {
+        match self.index {
+            0 => mem.get_sfr_reg(SFR::SCON0).is_bit_set(SCON_SM2),
+            _ => mem.get_sfr_reg(SFR::SMOD1).is_bit_set(SMOD1_MCE1),
+        }
+    }
+
+    fn data_mask(&self, mem: &Memory) -> u8 {
+        (0xffu16 >> (8 - self.data_bits(mem))) as u8
+    }
+
+    /// Length of a bit on the line in oscillator clocks, for UART0.
+    ///
+    /// Mode 0 shifts a bit per machine cycle and mode 2 takes 64 clocks. Modes 1 and 3 take 32
+    /// Timer 1 overflows, which requires Timer 1 in its 8-bit auto-reload mode, otherwise this
+    /// is `None`. SMOD in [`SFR::PCON`] halves the bit time of modes 1 to 3. UART1 has its own
+    /// baud rate generator, which isn't modelled.
+    pub fn bit_clocks(&self, mem: &Memory) -> Option<u64> {
+        if self.index != 0 {
+            return None;
+        }
+
+        let smod = mem.get_sfr_reg(SFR::PCON).is_bit_set(PCON_SMOD) as u32;
+        let timer1_overflow = || {
+            let auto_reload = (mem.get_sfr_reg(SFR::TMOD) >> 4) & 0b11 == 2;
+
+            auto_reload.then(|| (0x100 - mem.get_sfr_reg(SFR::TH1) as u64) * CLOCKS_PER_CYCLE)
+        };
+
+        match UartMode::from_scon(mem.get_sfr_reg(SFR::SCON0)) {
+            UartMode::ShiftRegister => Some(CLOCKS_PER_CYCLE),
+            UartMode::NineBitFixed => Some(64 >> smod),
+            UartMode::EightBit | UartMode::NineBitVariable => {
+                timer1_overflow().map(|overflow| (32 * overflow) >> smod)
+            },
+        }
+    }
+
+    /// Length of a whole frame in oscillator clocks, start and stop bits included, see
+    /// [`Uart::bit_clocks`].
+    pub fn frame_clocks(&self, mem: &Memory) -> Option<u64> {
+        let bits = match UartMode::from_scon(mem.get_sfr_reg(SFR::SCON0)) {
+            UartMode::ShiftRegister => 8,
+            UartMode::EightBit => 10,
+            UartMode::NineBitFixed | UartMode::NineBitVariable => 11,
+        };
+
+        self.bit_clocks(mem).map(|clocks| bits * clocks)
+    }
+
+    /// Handles a firmware write to SBUF: queues the frame and puts the receive buffer back in
+    /// place. TI is flagged after [`Uart::frame_clocks`], right away if the baud rate isn't
+    /// modelled.
+    pub fn transmit(&mut self, mem: &mut Memory) {
+        let scon = mem.get_sfr_reg(self.scon());
+        let frame = UartFrame {
+            data: mem.get_sfr_reg(self.sbuf()) & self.data_mask(mem),
+            ninth: self.has_ninth_bit(mem).then(|| scon.is_bit_set(SCON_TB8)),
+        };
+
+        debug!("UART{} TX: {:?}", self.index, frame);
+
+        match &mut self.backend {
+            Some(backend) => backend.on_tx(frame),
+            None => self.tx.push_back(frame),
+        }
+        mem.set_sfr_reg_internal(self.sbuf(), self.rx_buffer);
+
+        match self.frame_clocks(mem) {
+            Some(clocks) => self.tx_remaining = Some(clocks.div_ceil(CLOCKS_PER_CYCLE)),
+            None => self.flag_tx_done(mem),
+        }
+    }
+
+    /// Advances the transmitter by `cycles` machine cycles, flagging TI once the frame being
+    /// transmitted is out.
+    pub fn tick(&mut self, mem: &mut Memory, cycles: u64) {
+        match self.tx_remaining {
+            Some(remaining) if remaining > cycles => self.tx_remaining = Some(remaining - cycles),
+            Some(_) => self.flag_tx_done(mem),
+            None => (),
+        }
+    }
+
+    /// Machine cycles until the frame being transmitted is done and flags TI.
+    pub fn tx_remaining(&self) -> Option<u64> {
+        self.tx_remaining
+    }
+
+    fn flag_tx_done(&mut self, mem: &mut Memory) {
+        let scon = mem.get_sfr_reg(self.scon());
+
+        self.tx_remaining = None;
+        mem.set_sfr_reg_internal(self.scon(), scon | 1 << SCON_TI);
+    }
+
+    /// Receives `frame` into SBUF and flags RI.
+    ///
+    /// In 9-bit framings RB8 takes the 9th bit, a missing one counts as 0. With multiprocessor
+    /// communication on, frames with a 9th bit of 0 are dropped without flagging RI. In UART0
+    /// mode 1 RB8 takes the stop bit instead, mode 0 leaves it alone.
+    pub fn receive(&mut self, mem: &mut Memory, frame: UartFrame) {
+        debug!("UART{} RX: {:?}", self.index, frame);
+
+        let mut scon = mem.get_sfr_reg(self.scon());
+        let rb8 = if self.has_ninth_bit(mem) {
+            let ninth = frame.ninth.unwrap_or(false);
+
+            if !ninth && self.multiprocessor(mem) {
+                debug!("UART{} RX: not addressed, dropped", self.index);
+                return;
+            }
+
+            Some(ninth)
+        } else if self.index == 0 && UartMode::from_scon(scon) == UartMode::EightBit {
+            Some(true)
+        } else {
+            None
+        };
+
+        match rb8 {
+            Some(true) => scon.set_bit(SCON_RB8),
+            Some(false) => scon.clear_bit(SCON_RB8),
+            None => (),
+        }
+        scon.set_bit(SCON_RI);
+
+        self.rx_buffer = frame.data & self.data_mask(mem);
+        mem.set_sfr_reg_internal(self.sbuf(), self.rx_buffer);
+        mem.set_sfr_reg_internal(self.scon(), scon);
+    }
+
+    /// Receives the next frame from the backend, unless RI is still set.
+    pub fn poll(&mut self, mem: &mut Memory) {
+        if mem.get_sfr_reg(self.scon()).is_bit_set(SCON_RI) {
+            return;
+        }
+
+        if let Some(frame) = self.backend.as_mut().and_then(|backend| backend.poll_rx()) {
+            self.receive(mem, frame);
+        }
+    }
+
+    /// Returns the frames transmitted since the last call.
+    pub fn take_tx(&mut self) -> Vec<UartFrame> {
+        self.tx.drain(..).collect()
+    }
+}
@@ -0,0 +1,206 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use super::{
+    cpu::CPU,
+    error::{ErrorType, Result},
+};
+
+/// Version sent in [`PortMessage::Hello`], peers speaking another version are refused.
+pub const PROTOCOL_VERSION: u32 = 1;
+/// Ports mirrored over a link, P0-P3.
+pub const LINKED_PORTS: u8 = 4;
+
+/// A message of the controller link protocol, which mirrors the P0-P3 latches of two CPUs to
+/// each other, e.g. over a websocket.
+///
+/// Each message travels as one flat JSON object (one websocket text frame), tagged by `type`:
+///
+/// ```text
+/// {"type":"hello","version":1}
+/// {"type":"pin_write","port":1,"value":90}
+/// {"type":"pin_read","port":1}
+/// {"type":"sync","cycle":1200}
+/// ```
+///
+/// Handshake: once the client has connected, both sides send `hello` as their first message,
+/// without waiting for the other. Anything received before the peer's `hello`, or a `hello`
+/// with another version, is an error after which the connection should be closed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortMessage {
+    Hello { version: u32 },
+    /// The sender's `Pport` latch now reads `value`.
+    PinWrite { port: u8, value: u8 },
+    /// Asks for the receiver's `Pport` latch, answered with a `PinWrite`.
+    PinRead { port: u8 },
+    /// The sender's machine cycle count, for keeping the two CPUs in step.
+    Sync { cycle: u64 },
+}
+
+impl PortMessage {
+    pub fn to_json(self) -> String {
+        match self {
+            PortMessage::Hello { version } => {
+                format!(r#"{{"type":"hello","version":{}}}"#, version)
+            },
+            PortMessage::PinWrite { port, value } => {
+                format!(r#"{{"type":"pin_write","port":{},"value":{}}}"#, port, value)
+            },
+            PortMessage::PinRead { port } => format!(r#"{{"type":"pin_read","port":{}}}"#, port),
+            PortMessage::Sync { cycle } => format!(r#"{{"type":"sync","cycle":{}}}"#, cycle),
+        }
+    }
+
+    /// Parses a message as sent by [`PortMessage::to_json`]. Whitespace and the order of the
+    /// fields don't matter, unknown fields are ignored.
+    ///
+    /// Only flat objects are accepted: every value has to be a number, `true`, `false`, `null`
+    /// or a string without commas, quotes or backslashes. Anything else, e.g. a nested object,
+    /// is an [`ErrorType::InvalidMessage`], even in an unknown field.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let invalid = |msg: &str| ErrorType::InvalidMessage(msg.to_string());
+
+        let body = json
+            .trim()
+            .strip_prefix('{')
+            .and_then(|json| json.strip_suffix('}'))
+            .ok_or_else(|| invalid("not a JSON object"))?;
+
+        let mut fields = Vec::new();
+
+        for field in body.split(',').filter(|field| !field.trim().is_empty()) {
+            let (key, value) = field.split_once(':').ok_or_else(|| invalid("missing ':'"))?;
+
+            if !is_scalar(value.trim()) {
+                return Err(invalid("unsupported value"));
+            }
+
+            fields.push((unquote(key).ok_or_else(|| invalid("invalid key"))?, value.trim()));
+        }
+
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| *value)
+                .ok_or_else(|| invalid(&format!("missing \"{}\"", name)))
+        };
+        let number = |name: &str| {
+            field(name)?.parse::<u64>().map_err(|_| invalid(&format!("invalid \"{}\"", name)))
+        };
+        let port = || match number("port")? {
+            port if port < LINKED_PORTS as u64 => Ok(port as u8),
+            _ => Err(invalid("no such port")),
+        };
+
+        let message = match unquote(field("type")?) {
+            Some("hello") => PortMessage::Hello {
+                version: u32::try_from(number("version")?).map_err(|_| invalid("invalid version"))?,
+            },
+            Some("pin_write") => PortMessage::PinWrite {
+                port: port()?,
+                value: u8::try_from(number("value")?).map_err(|_| invalid("invalid \"value\""))?,
+            },
+            Some("pin_read") => PortMessage::PinRead { port: port()? },
+            Some("sync") => PortMessage::Sync { cycle: number("cycle")? },
+            _ => return Err(invalid("unknown type")),
+        };
+
+        Ok(message)
+    }
+}
+
+/// Whether `val` is one of the values [`PortMessage::from_json`] accepts.
+fn is_scalar(val: &str) -> bool {
+    let number = |val: &str| {
+        val.starts_with(|c: char| c == '-' || c.is_ascii_digit())
+            && val.chars().all(|c| c.is_ascii_digit() || "+-.eE".contains(c))
+    };
+
+    matches!(val, "true" | "false" | "null") || number(val) || unquote(val).is_some()
+}
+
+/// A JSON string without escapes, without its quotes.
+fn unquote(val: &str) -> Option<&str> {
+    val.trim().strip_prefix('"')?.strip_suffix('"').filter(|val| !val.contains(['"', '\\']))
+}
+
+/// One CPU's end of a controller link, turning its port writes into [`PortMessage`]s and
+/// applying the peer's messages to it. Moving the messages is up to the caller.
+#[derive(Debug, Default)]
+pub struct PortLink {
+    /// Whether the peer's `hello` has been received.
+    connected: bool,
+    /// The latches as last sent or received, `None` before the first exchange.
+    mirrored: [Option<u8>; LINKED_PORTS as usize],
+    peer_cycle: u64,
+}
+
+impl PortLink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The first message to send.
+    pub fn hello(&self) -> PortMessage {
+        PortMessage::Hello { version: PROTOCOL_VERSION }
+    }
+
+    pub fn connected(&self) -> bool {
+        self.connected
+    }
+
+    /// The machine cycle count the peer last reported with a [`PortMessage::Sync`].
+    pub fn peer_cycle(&self) -> u64 {
+        self.peer_cycle
+    }
+
+    /// A [`PortMessage::PinWrite`] for every latch of `cpu` that changed since the last call,
+    /// all of them on the first call. Latches set by the peer aren't echoed back.
+    pub fn outgoing(&mut self, cpu: &CPU) -> Vec<PortMessage> {
+        let mut messages = Vec::new();
+
+        for port in 0..LINKED_PORTS {
+            let value = cpu.port_latch(port);
+
+            if self.mirrored[port as usize] != Some(value) {
+                self.mirrored[port as usize] = Some(value);
+                messages.push(PortMessage::PinWrite { port, value });
+            }
+        }
+
+        messages
+    }
+
+    /// A [`PortMessage::Sync`] with `cpu`'s machine cycle count.
+    pub fn sync(&self, cpu: &CPU) -> PortMessage {
+        PortMessage::Sync { cycle: cpu.cycle_count() }
+    }
+
+    /// Applies `message` from the peer to `cpu`, returning the reply to send, if any.
+    ///
+    /// A `PinWrite` sets the latch of the port, as if `cpu` had written it.
+    pub fn apply(&mut self, cpu: &mut CPU, message: PortMessage) -> Result<Option<PortMessage>> {
+        let invalid = |msg: &str| Err(ErrorType::InvalidMessage(msg.to_string()));
+
+        match (self.connected, message) {
+            (false, PortMessage::Hello { version: PROTOCOL_VERSION }) => self.connected = true,
+            (false, PortMessage::Hello { .. }) => return invalid("unsupported protocol version"),
+            (false, _) => return invalid("message before hello"),
+            (true, PortMessage::Hello { .. }) => return invalid("repeated hello"),
+            (true, PortMessage::PinWrite { port, value }) => {
+                cpu.set_port_latch(port, value);
+                self.mirrored[port as usize] = Some(value);
+            },
+            (true, PortMessage::PinRead { port }) => {
+                return Ok(Some(PortMessage::PinWrite { port, value: cpu.port_latch(port) }));
+            },
+            (true, PortMessage::Sync { cycle }) => self.peer_cycle = cycle,
+        }
+
+        Ok(None)
+    }
+}
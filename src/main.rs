@@ -1,22 +1,68 @@
 #![feature(bigint_helper_methods)]
 
+extern crate alloc;
+
 mod lib;
 
 #[cfg(test)]
 mod tests;
 
 use lib::cpu::CPU;
-use log::LevelFilter;
-use pretty_env_logger::env_logger::WriteStyle;
 
+#[cfg(feature = "std")]
 fn main() {
+    use lib::{error::ErrorType, script::Script};
+    use log::{error, LevelFilter};
+    use pretty_env_logger::env_logger::WriteStyle;
+
     pretty_env_logger::formatted_timed_builder()
         .write_style(WriteStyle::Auto)
         .filter(None, LevelFilter::Debug)
         .init();
 
+    let mut hex_file = String::from("MONKAW.HEX");
+    let mut script_file = None;
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--script" => match args.next() {
+                Some(file) => script_file = Some(file),
+                None => {
+                    error!("--script needs a file");
+                    std::process::exit(1);
+                },
+            },
+            _ => hex_file = arg,
+        }
+    }
+
     let mut cpu: CPU = CPU::init();
 
-    cpu.load_from_file("MONKAW.HEX");
+    if let Err(err) = cpu.load_from_file(&hex_file) {
+        error!("{}", err);
+        std::process::exit(1);
+    }
+
+    if let Some(script_file) = script_file {
+        let script = std::fs::read_to_string(&script_file)
+            .map_err(|err| ErrorType::FileLoad(script_file, err.to_string()))
+            .and_then(|script| Script::parse(&script));
+
+        match script {
+            Ok(script) => cpu.load_script(script),
+            Err(err) => {
+                error!("{}", err);
+                std::process::exit(1);
+            },
+        }
+    }
+
     cpu.run();
 }
+
+/// Without `std` there are no files to load, this only checks that the core still builds.
+#[cfg(not(feature = "std"))]
+fn main() {
+    CPU::init();
+}
@@ -0,0 +1,138 @@
+use crate::lib::{disasm, error::ErrorType, instruction::Instruction};
+
+fn assert_assembles(cases: &[(&str, &[u8])]) {
+    for (line, expected) in cases {
+        let bytes = Instruction::assemble(line).unwrap();
+
+        assert_eq!(&bytes, expected, "{}", line);
+
+        let insn = Instruction::try_from(bytes[0]).unwrap();
+
+        assert_eq!(Instruction::get_num_bytes(&insn), bytes.len(), "{}", line);
+    }
+}
+
+#[test]
+fn implied_and_registers() {
+    assert_assembles(&[
+        ("NOP", &[0x00]),
+        ("RET", &[0x22]),
+        ("rr a", &[0x03]),
+        ("MUL AB", &[0xa4]),
+        ("INC DPTR", &[0xa3]),
+        ("INC R7", &[0x0f]),
+        ("DEC @R1", &[0x17]),
+        ("MOV A, R2", &[0xea]),
+        ("MOV @R0, A", &[0xf6]),
+        ("XCHD A, @R1", &[0xd7]),
+        ("XRL A, @R0", &[0x66]),
+        ("MOVX A, @DPTR", &[0xe0]),
+        ("MOVC A, @A+PC", &[0x83]),
+        ("JMP @A+DPTR", &[0x73]),
+    ]);
+}
+
+#[test]
+fn immediates() {
+    assert_assembles(&[
+        ("MOV A, #0x42", &[0x74, 0x42]),
+        ("MOV R3, #42h", &[0x7b, 0x42]),
+        ("ADD A, #66", &[0x24, 0x42]),
+        ("SUBB A, #-1", &[0x94, 0xff]),
+        ("MOV DPTR, #0x1234", &[0x90, 0x12, 0x34]),
+        ("ORL P1, #0x0F", &[0x43, 0x90, 0x0f]),
+    ]);
+}
+
+#[test]
+fn direct_addresses() {
+    assert_assembles(&[
+        ("MOV A, 0x30", &[0xe5, 0x30]),
+        ("MOV ACC, A", &[0xf5, 0xe0]),
+        ("PUSH DPL", &[0xc0, 0x82]),
+        ("MOV 0x30, 0x31", &[0x85, 0x31, 0x30]),
+        ("MOV SP, #0x60", &[0x75, 0x81, 0x60]),
+        ("ANL PSW, A", &[0x52, 0xd0]),
+        ("XCH A, B", &[0xc5, 0xf0]),
+    ]);
+}
+
+#[test]
+fn bit_addresses() {
+    assert_assembles(&[
+        ("SETB P1.0", &[0xd2, 0x90]),
+        ("CLR ACC.7", &[0xc2, 0xe7]),
+        ("CPL 0x20.3", &[0xb2, 0x03]),
+        ("MOV C, 0x7F", &[0xa2, 0x7f]),
+        ("ANL C, /PSW.2", &[0xb0, 0xd2]),
+        ("ORL C, P3.1", &[0x72, 0xb1]),
+    ]);
+}
+
+#[test]
+fn code_addresses_and_displacements() {
+    assert_assembles(&[
+        ("AJMP 0x345", &[0x61, 0x45]),
+        ("ACALL 0x7FF", &[0xf1, 0xff]),
+        ("LJMP 0x1234", &[0x02, 0x12, 0x34]),
+        ("SJMP -2", &[0x80, 0xfe]),
+        ("JNZ 5", &[0x70, 0x05]),
+        ("JB P1.1, -3", &[0x20, 0x91, 0xfd]),
+        ("CJNE A, #0x10, 4", &[0xb4, 0x10, 0x04]),
+        ("CJNE R5, #1, -128", &[0xbd, 0x01, 0x80]),
+        ("DJNZ R0, -2 ; loop", &[0xd8, 0xfe]),
+        ("DJNZ 0x40, 0x10", &[0xd5, 0x40, 0x10]),
+    ]);
+}
+
+#[test]
+fn absolute_targets() {
+    let cases: &[(&str, u16, &[u8])] = &[
+        ("SJMP 0x0000", 0x0000, &[0x80, 0xfe]),
+        ("SJMP 0x0f82", 0x1000, &[0x80, 0x80]),
+        ("JB P1.1, 0x0100", 0x0100, &[0x20, 0x91, 0xfd]),
+        ("DJNZ R0, 0xfffe", 0x0000, &[0xd8, 0xfc]),
+        ("AJMP 0x0fff", 0x0800, &[0xe1, 0xff]),
+        ("ACALL 0x1000", 0x0ffe, &[0x11, 0x00]),
+    ];
+
+    for (line, addr, expected) in cases {
+        assert_eq!(&Instruction::assemble_at(line, *addr).unwrap(), expected, "{}", line);
+    }
+
+    let lines = [("SJMP 0x0000", 0x0100), ("JNZ 0x10000", 0x0000), ("AJMP 0x0800", 0x07fd)];
+
+    for (line, addr) in lines {
+        let result = Instruction::assemble_at(line, addr);
+
+        assert!(matches!(result, Err(ErrorType::Assembly(_))), "{}", line);
+    }
+}
+
+#[test]
+fn disassembly_round_trips() {
+    let operands = [[0x00, 0x00], [0x7f, 0x80], [0x90, 0xfe], [0xe0, 0x42], [0x35, 0xb7]];
+
+    for opcode in 0x00..=0xff {
+        for [first, second] in operands {
+            for addr in [0x0000, 0x07fe, 0x1234, 0xfffd] {
+                let bytes = [opcode, first, second];
+                let (text, len) = disasm::disassemble_bytes(bytes, addr);
+                let assembled = Instruction::assemble_at(&text, addr);
+
+                let expected = &bytes[..len as usize];
+
+                assert_eq!(assembled.as_deref(), Ok(expected), "{:#06x}: {}", addr, text);
+            }
+        }
+    }
+}
+
+#[test]
+fn invalid_lines() {
+    let lines = ["FOO A", "MOV A", "MOV A, #256", "SJMP 128", "SETB P1.8", "SETB TH0.1", "MOV R8, A"];
+
+    for line in lines {
+        assert!(matches!(Instruction::assemble(line), Err(ErrorType::Assembly(_))), "{}", line);
+    }
+}
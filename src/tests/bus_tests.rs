@@ -0,0 +1,149 @@
+use crate::lib::{
+    bus::{Bus, Pin, PinEvent, Sink},
+    cpu::CPU,
+    error::ErrorType,
+    instruction::Instruction,
+    interrupt::InterruptSource,
+    memory::registers::SFR,
+    ops::arithmetics::BitOps,
+};
+
+#[test]
+fn pin_triggers_interrupt() {
+    let mut bus = Bus::new();
+
+    let a = bus.add_cpu(CPU::init());
+    let b = bus.add_cpu(CPU::init());
+
+    // ANL P1, A
+    bus.cpu_mut(a).get_memory().write_flash(0x0000, Instruction::ANL_DATA_A.into());
+    bus.cpu_mut(a).get_memory().write_flash(0x0001, SFR::P1.into());
+    bus.cpu_mut(a).get_memory().set_sfr_reg(SFR::P1, 0xff);
+    bus.cpu_mut(a).get_memory().set_sfr_reg(SFR::ACC, 0xfe);
+    // a high latch releases the pin, which then follows what a drives
+    bus.cpu_mut(b).get_memory().set_sfr_reg(SFR::P2, 0xff);
+
+    bus.connect(Pin { cpu: a, port: 1, bit: 0 }, Sink::Interrupt {
+        cpu: b,
+        source: InterruptSource::Int0,
+    });
+    bus.connect(Pin { cpu: a, port: 1, bit: 1 }, Sink::Pin(Pin { cpu: b, port: 2, bit: 0 }));
+
+    bus.step_all().unwrap();
+
+    assert!(!bus.cpu_mut(a).port_bit(1, 0));
+    assert!(bus.cpu_mut(b).get_memory().get_sfr_reg(SFR::TCON).is_bit_set(1));
+    assert!(bus.cpu(b).port_bit(2, 0));
+
+    // no further edge, no further request
+    bus.cpu_mut(b).get_memory().set_sfr_reg(SFR::TCON, 0x00);
+    bus.step_all().unwrap();
+
+    assert!(!bus.cpu_mut(b).get_memory().get_sfr_reg(SFR::TCON).is_bit_set(1));
+}
+
+#[test]
+fn contention() {
+    let mut bus = Bus::new();
+
+    let a = bus.add_cpu(CPU::init());
+    let b = bus.add_cpu(CPU::init());
+
+    // a drives P1.0 high push-pull, b drives P2.0 low
+    bus.cpu_mut(a).get_memory().set_sfr_reg(SFR::P1MDOUT, 0x01);
+    bus.cpu_mut(a).get_memory().set_sfr_reg(SFR::P1, 0x01);
+    bus.cpu_mut(b).get_memory().set_sfr_reg(SFR::P2MDOUT, 0x01);
+    bus.cpu_mut(b).get_memory().set_sfr_reg(SFR::P2, 0x00);
+
+    let high = Pin { cpu: a, port: 1, bit: 0 };
+    let low = Pin { cpu: b, port: 2, bit: 0 };
+
+    bus.connect(high, Sink::Pin(low));
+    bus.step_all().unwrap();
+
+    assert_eq!(bus.take_events(), vec![PinEvent::Contention { low, high }]);
+
+    // both keep driving, the short goes on without being reported again
+    bus.step_all().unwrap();
+
+    assert_eq!(bus.take_events(), vec![]);
+    assert_eq!(bus.contentions(), [PinEvent::Contention { low, high }]);
+    assert_eq!(bus.cpu(b).port_latch(2), 0x00);
+
+    // an open-drain high is only pulled up, pulling it low is fine
+    bus.cpu_mut(a).get_memory().set_sfr_reg(SFR::P1MDOUT, 0x00);
+    bus.step_all().unwrap();
+
+    assert_eq!(bus.take_events(), vec![]);
+    assert_eq!(bus.contentions(), []);
+}
+
+#[test]
+fn wire_drives_pin() {
+    let mut bus = Bus::new();
+
+    let a = bus.add_cpu(CPU::init());
+    let b = bus.add_cpu(CPU::init());
+
+    bus.connect(Pin { cpu: a, port: 1, bit: 0 }, Sink::Pin(Pin { cpu: b, port: 1, bit: 0 }));
+    bus.cpu_mut(a).set_port_bit(1, 0, false);
+    bus.cpu_mut(b).set_port_latch(1, 0x5a);
+    bus.step_all().unwrap();
+
+    // b's firmware keeps its latch, the pin reads what a drives
+    assert_eq!(bus.cpu(b).port_latch(1), 0x5a);
+    assert!(!bus.cpu(b).port_bit(1, 0));
+    assert!(bus.cpu(b).port_bit(1, 1));
+
+    // released by a, the pin follows b's latch again
+    bus.cpu_mut(a).set_port_bit(1, 0, true);
+    bus.cpu_mut(b).set_port_latch(1, 0x5b);
+    bus.step_all().unwrap();
+
+    assert!(bus.cpu(b).port_bit(1, 0));
+}
+
+#[test]
+fn lockstep() {
+    let mut bus = Bus::new();
+
+    let a = bus.add_cpu(CPU::init());
+    let b = bus.add_cpu(CPU::init());
+
+    // a runs MUL AB, 4 cycles each, b a NOP sled
+    for addr in 0..8 {
+        bus.cpu_mut(a).get_memory().write_flash(addr, Instruction::MUL_AB.into());
+    }
+
+    for _ in 0..10 {
+        bus.step_all().unwrap();
+
+        let cycles = bus.cycles();
+
+        assert_eq!(bus.cpu(b).cycle_count(), cycles);
+        assert!((cycles..cycles + 4).contains(&bus.cpu(a).cycle_count()));
+    }
+
+    assert_eq!(bus.cpu(a).instructions_retired(), 3);
+    assert_eq!(bus.cpu(b).instructions_retired(), 10);
+}
+
+#[test]
+fn failing_cpu() {
+    let mut bus = Bus::new();
+
+    let a = bus.add_cpu(CPU::init());
+    let b = bus.add_cpu(CPU::init());
+    let c = bus.add_cpu(CPU::init());
+
+    // b has no code space to run from, a and c run NOPs
+    bus.cpu_mut(b).set_code_size(0);
+
+    for cycles in 1..=3 {
+        assert_eq!(bus.step_all(), Err(ErrorType::InvalidAddr(0x0000)));
+        assert_eq!(bus.cycles(), cycles);
+        assert_eq!(bus.cpu(a).cycle_count(), cycles);
+        assert_eq!(bus.cpu(b).cycle_count(), 0);
+        assert_eq!(bus.cpu(c).cycle_count(), cycles);
+    }
+}
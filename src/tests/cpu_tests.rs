@@ -1,8 +1,22 @@
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
 use crate::lib::{
-    cpu::CPU,
+    cpu::{
+        CpuConfig, ExitReason, HaltReason, RegisterMismatch, ResetSource, StepOutcome,
+        UnimplementedPolicy, CPU,
+    },
+    error::ErrorType,
     instruction::Instruction,
-    memory::registers::{Register, SFR},
-    ops::arithmetics::BitOps,
+    interrupt::InterruptSource,
+    memory::{
+        fault::{Fault, FaultSite},
+        registers::{Register, SFR},
+    },
+    ops::{arithmetics::BitOps, checksum::ChecksumAlgo},
+    script::Script,
+    state::StateDiff,
+    trace::TraceEntry,
+    uart::{SerialBackend, UartFrame},
 };
 
 fn init_cpu(pc: usize) -> CPU {
@@ -13,6 +27,12 @@ fn init_cpu(pc: usize) -> CPU {
     cpu
 }
 
+fn load_program(cpu: &mut CPU, addr: u16, program: &[u8]) {
+    for (offset, byte) in program.iter().enumerate() {
+        cpu.get_memory().write_flash(addr + offset as u16, *byte);
+    }
+}
+
 #[test]
 fn ajmp() {
     let mut cpu = init_cpu(0x0345);
@@ -91,121 +111,3156 @@ fn ret() {
     assert_eq!(cpu.get_pc(), 0x0123);
 }
 
+#[test]
+fn reti() {
+    let mut cpu = init_cpu(0x0000);
+
+    // the return address an ACALL at 0x0120 pushes, low byte first
+    load_program(&mut cpu, 0x0120, &[Instruction::ACALL1.into(), 0x80]);
+    load_program(&mut cpu, 0x0080, &[Instruction::RETI.into()]);
+    cpu.set_pc(0x0120);
+
+    cpu.cycle().unwrap();
+
+    assert_eq!(cpu.get_pc(), 0x0080);
+    assert_eq!(cpu.get_memory().read(0x08), 0x22);
+    assert_eq!(cpu.get_memory().read(0x09), 0x01);
+
+    cpu.cycle().unwrap();
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SP), 0x07);
+    assert_eq!(cpu.get_pc(), 0x0122);
+}
+
+#[test]
+fn lcall() {
+    let mut cpu = init_cpu(0x0123);
+
+    cpu.run_instruction_test(Instruction::LCALL, 0x02, 0x00);
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SP), 0x09);
+    assert_eq!(cpu.get_memory().read(0x08), 0x26);
+    assert_eq!(cpu.get_memory().read(0x09), 0x01);
+    assert_eq!(cpu.get_pc(), 0x0200);
+}
+
+#[test]
+fn call_ret_round_trip() {
+    let mut cpu = init_cpu(0x0123);
+
+    load_program(&mut cpu, 0x0123, &[Instruction::LCALL.into(), 0x02, 0x00]);
+    load_program(&mut cpu, 0x0126, &[Instruction::ACALL4.into(), 0x45]);
+    load_program(&mut cpu, 0x0200, &[Instruction::RET.into()]);
+    load_program(&mut cpu, 0x0345, &[Instruction::RET.into()]);
+
+    cpu.cycle().unwrap();
+    cpu.cycle().unwrap();
+
+    assert_eq!(cpu.get_pc(), 0x0126);
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SP), 0x07);
+
+    cpu.cycle().unwrap();
+    cpu.cycle().unwrap();
+
+    assert_eq!(cpu.get_pc(), 0x0128);
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SP), 0x07);
+}
+
+#[test]
+fn call_ret_wraps_stack() {
+    let mut cpu = init_cpu(0x0123);
+
+    load_program(&mut cpu, 0x0123, &[Instruction::LCALL.into(), 0x02, 0x00]);
+    load_program(&mut cpu, 0x0200, &[Instruction::RET.into()]);
+    cpu.set_registers(&[(SFR::SP, 0xff)]);
+
+    // the return address lands on R0 and R1 of bank 0
+    cpu.cycle().unwrap();
+
+    assert_eq!(cpu.sp(), 0x01);
+    assert_eq!(cpu.read_gpr(Register::R0), 0x26);
+    assert_eq!(cpu.read_gpr(Register::R1), 0x01);
+
+    cpu.cycle().unwrap();
+
+    assert_eq!(cpu.get_pc(), 0x0126);
+    assert_eq!(cpu.sp(), 0xff);
+}
+
 #[test]
 fn addc_a_r2() {
     let mut cpu = init_cpu(0x0000);
 
-    cpu.get_memory().set_sfr_reg(SFR::ACC, 0xc3);
-    cpu.get_memory().set_gpr_reg(Register::R2, 0xaa);
-    cpu.get_memory().set_sfr_reg(SFR::PSW, 0x80);
+    cpu.set_registers(&[(SFR::ACC, 0xc3), (SFR::PSW, 0x80)]);
+    cpu.set_ram(&[(0x02, 0xaa)]);
 
     cpu.run_instruction_test(Instruction::ADDC_A_R2, 0, 0);
 
-    let psw = cpu.get_memory().get_sfr_reg(SFR::PSW);
+    assert_eq!(cpu.assert_registers(&[(SFR::ACC, 0x6e)]), vec![]);
+    assert!(cpu.overflow());
+    assert!(cpu.carry());
+    assert!(!cpu.aux_carry());
+}
+
+#[test]
+fn addc_carry_in() {
+    let mut cpu = init_cpu(0x0000);
+
+    // 0xff + 0x00 + C
+    cpu.set_registers(&[(SFR::ACC, 0xff), (SFR::PSW, 0x80)]);
+    cpu.run_instruction_test(Instruction::ADDC_A_CONST, 0x00, 0);
+
+    assert_eq!(cpu.acc(), 0x00);
+    assert!(cpu.carry() && cpu.aux_carry() && !cpu.overflow());
+
+    // without a carry in, ADDC is ADD
+    cpu.set_registers(&[(SFR::ACC, 0xff), (SFR::PSW, 0x00)]);
+    cpu.run_instruction_test(Instruction::ADDC_A_CONST, 0x00, 0);
+
+    assert_eq!(cpu.acc(), 0xff);
+    assert!(!cpu.carry() && !cpu.aux_carry() && !cpu.overflow());
 
-    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::ACC), 0x6e);
-    assert_eq!(psw.is_bit_set(2), true);
-    assert_eq!(psw.is_bit_set(7), true);
-    assert_eq!(psw.is_bit_set(6), false);
+    // a 16-bit 0x12ff + 0x0001 through @R0 and direct
+    cpu.set_ram(&[(0x00, 0x30), (0x30, 0x01), (0x31, 0x00)]);
+    cpu.set_registers(&[(SFR::ACC, 0xff), (SFR::PSW, 0x00)]);
+    cpu.run_instruction_test(Instruction::ADDC_A_INDIRECT_R0, 0, 0);
+
+    assert_eq!(cpu.acc(), 0x00);
+    assert!(cpu.carry());
+
+    cpu.set_registers(&[(SFR::ACC, 0x12)]);
+    cpu.run_instruction_test(Instruction::ADDC_A_DATA, 0x31, 0);
+
+    assert_eq!(cpu.acc(), 0x13);
+    assert!(!cpu.carry());
 }
 
 #[test]
-pub fn orl_data_const() {
+fn add_edge_cases() {
     let mut cpu = init_cpu(0x0000);
 
-    cpu.get_memory().write(0x4f, 0xdb);
+    cpu.set_registers(&[(SFR::ACC, 0xff), (SFR::PSW, 0x80)]);
+    // ADD ignores the incoming carry
+    cpu.run_instruction_test(Instruction::ADD_A_CONST, 0x01, 0);
 
-    cpu.run_instruction_test(Instruction::ORL_DATA_CONST, 0x4f, 0xf2);
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::ACC), 0x00);
+    assert!(cpu.carry() && cpu.aux_carry() && !cpu.overflow());
 
-    assert_eq!(cpu.get_memory().read(0x4f), 0xdb | 0xf2);
+    cpu.set_registers(&[(SFR::ACC, 0x7f)]);
+    cpu.set_ram(&[(0x05, 0x01)]);
+    cpu.run_instruction_test(Instruction::ADD_A_R5, 0, 0);
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::ACC), 0x80);
+    assert!(!cpu.carry() && cpu.aux_carry() && cpu.overflow());
+
+    // ADDC of 0xff with the carry set
+    cpu.set_registers(&[(SFR::ACC, 0x01), (SFR::PSW, 0x80)]);
+    cpu.set_ram(&[(0x02, 0xff)]);
+    cpu.run_instruction_test(Instruction::ADDC_A_R2, 0, 0);
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::ACC), 0x01);
+    assert!(cpu.carry() && cpu.aux_carry() && !cpu.overflow());
 }
 
 #[test]
-fn subb() {
+fn add_aux_carry() {
     let mut cpu = init_cpu(0x0000);
 
-    cpu.get_memory().set_sfr_reg(SFR::ACC, 0xc9);
-    cpu.get_memory().set_gpr_reg(Register::R2, 0x54);
-    cpu.get_memory().set_sfr_reg(SFR::PSW, 0x80);
+    // ACC, operand, AC
+    let cases = [(0x08, 0x08, true), (0x0f, 0x01, true), (0x18, 0x08, true), (0x07, 0x08, false)];
 
-    cpu.run_instruction_test(Instruction::SUBB_A_R2, 0, 0);
+    for (acc, operand, aux_carry) in cases {
+        cpu.set_registers(&[(SFR::ACC, acc), (SFR::PSW, 0x00)]);
+        cpu.run_instruction_test(Instruction::ADD_A_CONST, operand, 0);
+
+        assert_eq!(cpu.aux_carry(), aux_carry, "{:#04x} + {:#04x}", acc, operand);
+    }
+}
 
-    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::ACC), 0x74);
+#[test]
+fn mul_ab() {
+    let mut cpu = init_cpu(0x0000);
 
-    let psw = cpu.get_memory().get_sfr_reg(SFR::PSW);
+    // ACC, B, product, OV
+    let cases =
+        [(0xff, 0xff, 0xfe01, true), (0x0c, 0x10, 0x00c0, false), (0x5a, 0x00, 0x0000, false)];
 
-    assert_eq!(psw.is_bit_set(2), true);
-    assert_eq!(psw.is_bit_set(6), false);
-    assert_eq!(psw.is_bit_set(7), false);
+    for (acc, b, product, overflow) in cases {
+        cpu.set_registers(&[(SFR::ACC, acc), (SFR::B, b), (SFR::PSW, 0x80)]);
+        cpu.run_instruction_test(Instruction::MUL_AB, 0, 0);
+
+        assert_eq!(cpu.acc(), (product & 0xff) as u8, "{:#04x} * {:#04x}", acc, b);
+        assert_eq!(cpu.b(), (product >> 8) as u8, "{:#04x} * {:#04x}", acc, b);
+        assert!(!cpu.carry());
+        assert_eq!(cpu.overflow(), overflow, "{:#04x} * {:#04x}", acc, b);
+    }
 }
 
 #[test]
-fn sjmp() {
-    let mut cpu = init_cpu(0x0100);
+fn div_ab() {
+    let mut cpu = init_cpu(0x0000);
 
-    cpu.run_instruction_test(Instruction::SJMP, 2, 0);
+    // ACC, B, quotient, remainder, OV
+    let cases = [(0x50, 0x07, 0x0b, 0x03, false), (0x90, 0x0c, 0x0c, 0x00, false)];
 
-    assert_eq!(cpu.get_pc(), 0x0104);
+    for (acc, b, quotient, remainder, overflow) in cases {
+        cpu.set_registers(&[(SFR::ACC, acc), (SFR::B, b), (SFR::PSW, 0x84)]);
+        cpu.run_instruction_test(Instruction::DIV_AB, 0, 0);
+
+        assert_eq!(cpu.acc(), quotient, "{:#04x} / {:#04x}", acc, b);
+        assert_eq!(cpu.b(), remainder, "{:#04x} % {:#04x}", acc, b);
+        assert!(!cpu.carry());
+        assert_eq!(cpu.overflow(), overflow, "{:#04x} / {:#04x}", acc, b);
+    }
+
+    cpu.set_registers(&[(SFR::ACC, 0x2e), (SFR::B, 0x00), (SFR::PSW, 0x80)]);
+    cpu.run_instruction_test(Instruction::DIV_AB, 0, 0);
+
+    assert_eq!((cpu.acc(), cpu.b()), (0x2e, 0x00));
+    assert!(!cpu.carry());
+    assert!(cpu.overflow());
 }
 
 #[test]
-fn push_data() {
+fn da_a() {
     let mut cpu = init_cpu(0x0000);
 
-    cpu.get_memory().set_sfr_reg(SFR::SP, 0x09);
-    cpu.get_memory().set_sfr_reg(SFR::DPH, 0x01);
-    cpu.get_memory().set_sfr_reg(SFR::DPL, 0x23);
+    // 56 + 67 = 123 in BCD
+    cpu.set_registers(&[(SFR::ACC, 0x56), (SFR::PSW, 0x00)]);
+    cpu.run_instruction_test(Instruction::ADD_A_CONST, 0x67, 0);
 
-    cpu.run_instruction_test(Instruction::PUSH_DATA, SFR::DPL.into(), 0);
+    assert_eq!(cpu.acc(), 0xbd);
 
-    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SP), 0x0a);
-    assert_eq!(cpu.get_memory().read(0x0a), 0x23);
+    cpu.run_instruction_test(Instruction::DA_A, 0, 0);
 
-    cpu.run_instruction_test(Instruction::PUSH_DATA, SFR::DPH.into(), 0);
+    assert_eq!(cpu.acc(), 0x23);
+    assert!(cpu.carry());
 
-    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SP), 0x0b);
-    assert_eq!(cpu.get_memory().read(0x0b), 0x01);
+    // already valid BCD with CY set keeps CY and only adjusts the high nibble
+    cpu.set_registers(&[(SFR::ACC, 0x12), (SFR::PSW, 0x80)]);
+    cpu.run_instruction_test(Instruction::DA_A, 0, 0);
+
+    assert_eq!(cpu.acc(), 0x72);
+    assert!(cpu.carry());
+
+    cpu.set_registers(&[(SFR::ACC, 0x12), (SFR::PSW, 0x00)]);
+    cpu.run_instruction_test(Instruction::DA_A, 0, 0);
+
+    assert_eq!(cpu.acc(), 0x12);
+    assert!(!cpu.carry());
 }
 
 #[test]
-fn pop_data() {
+fn swap_a() {
     let mut cpu = init_cpu(0x0000);
 
-    cpu.get_memory().write(0x30, 0x20);
-    cpu.get_memory().write(0x31, 0x23);
-    cpu.get_memory().write(0x32, 0x01);
+    cpu.set_registers(&[(SFR::ACC, 0x3c), (SFR::PSW, 0xc4)]);
+    cpu.run_instruction_test(Instruction::SWAP_A, 0, 0);
 
-    cpu.get_memory().set_sfr_reg(SFR::SP, 0x32);
+    assert_eq!(cpu.acc(), 0xc3);
+    assert_eq!(cpu.psw(), 0xc4);
+}
 
-    cpu.run_instruction_test(Instruction::POP_DATA, SFR::DPH.into(), 0);
+#[test]
+fn cpl_clr_a() {
+    let mut cpu = init_cpu(0x0000);
 
-    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SP), 0x31);
-    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::DPH), 0x01);
+    cpu.set_registers(&[(SFR::ACC, 0xaa), (SFR::PSW, 0xc4)]);
+    cpu.run_instruction_test(Instruction::CPL_A, 0, 0);
 
-    cpu.run_instruction_test(Instruction::POP_DATA, SFR::DPL.into(), 0);
+    assert_eq!(cpu.acc(), 0x55);
+    assert_eq!(cpu.psw(), 0xc4);
 
-    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SP), 0x30);
-    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::DPL), 0x23);
+    for acc in [0x00, 0x01, 0x80, 0xff] {
+        cpu.set_registers(&[(SFR::ACC, acc), (SFR::PSW, 0xc4)]);
+        cpu.run_instruction_test(Instruction::CLR_A, 0, 0);
 
-    cpu.run_instruction_test(Instruction::POP_DATA, SFR::SP.into(), 0);
+        assert_eq!(cpu.acc(), 0x00);
+        assert_eq!(cpu.psw(), 0xc4);
+    }
+}
 
-    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SP), 0x20);
+#[test]
+fn rotate() {
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.set_registers(&[(SFR::ACC, 0x80), (SFR::PSW, 0x00)]);
+    cpu.run_instruction_test(Instruction::RL_A, 0, 0);
+
+    assert_eq!(cpu.acc(), 0x01);
+    assert!(!cpu.carry());
+
+    cpu.run_instruction_test(Instruction::RR_A, 0, 0);
+    cpu.run_instruction_test(Instruction::RR_A, 0, 0);
+
+    assert_eq!(cpu.acc(), 0x40);
+    assert!(!cpu.carry());
+
+    cpu.set_registers(&[(SFR::ACC, 0x01)]);
+    cpu.run_instruction_test(Instruction::RR_A, 0, 0);
+
+    assert_eq!(cpu.acc(), 0x80);
 }
 
 #[test]
-fn djnz() {
+fn rotate_through_carry() {
     let mut cpu = init_cpu(0x0000);
 
-    cpu.get_memory().write(0x40, 0x01);
-    cpu.get_memory().write(0x50, 0x70);
-    cpu.get_memory().write(0x60, 0x15);
+    cpu.set_registers(&[(SFR::ACC, 0x80), (SFR::PSW, 0x00)]);
+    cpu.run_instruction_test(Instruction::RLC_A, 0, 0);
 
-    cpu.run_instruction_test(Instruction::DJNZ_DATA_CODE, 0x40, 1);
-    assert_eq!(cpu.get_pc(), 2);
-    cpu.run_instruction_test(Instruction::DJNZ_DATA_CODE, 0x50, 1);
-    assert_eq!(cpu.get_pc(), 5);
+    assert_eq!(cpu.acc(), 0x00);
+    assert!(cpu.carry());
 
-    assert_eq!(cpu.get_memory().read(0x40), 0x00);
-    assert_eq!(cpu.get_memory().read(0x50), 0x6f);
-    assert_eq!(cpu.get_memory().read(0x60), 0x15);
+    // the carry comes back in at bit 0
+    cpu.run_instruction_test(Instruction::RLC_A, 0, 0);
+
+    assert_eq!(cpu.acc(), 0x01);
+    assert!(!cpu.carry());
+
+    cpu.run_instruction_test(Instruction::RRC_A, 0, 0);
+
+    assert_eq!(cpu.acc(), 0x00);
+    assert!(cpu.carry());
+
+    cpu.run_instruction_test(Instruction::RRC_A, 0, 0);
+
+    assert_eq!(cpu.acc(), 0x80);
+    assert!(!cpu.carry());
+}
+
+#[test]
+fn mov_a() {
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.run_instruction_test(Instruction::MOV_A_CONST, 0x5e, 0);
+
+    assert_eq!(cpu.acc(), 0x5e);
+    assert!(cpu.parity());
+
+    cpu.set_ram(&[(0x30, 0x11)]);
+    cpu.run_instruction_test(Instruction::MOV_A_DATA, 0x30, 0);
+
+    assert_eq!(cpu.acc(), 0x11);
+
+    // R0 and R1 hold the address
+    cpu.set_ram(&[(0x00, 0x40), (0x01, 0x41), (0x40, 0x22), (0x41, 0x33)]);
+    cpu.run_instruction_test(Instruction::MOV_A_INDIRECT_R0, 0, 0);
+
+    assert_eq!(cpu.acc(), 0x22);
+
+    cpu.run_instruction_test(Instruction::MOV_A_INDIRECT_R1, 0, 0);
+
+    assert_eq!(cpu.acc(), 0x33);
+
+    for reg in 0..8u8 {
+        cpu.get_memory().set_gpr_reg(Register::try_from(reg).unwrap(), 0xa0 + reg);
+        cpu.run_instruction_test(Instruction::try_from(0xe8 + reg).unwrap(), 0, 0);
+
+        assert_eq!(cpu.acc(), 0xa0 + reg);
+    }
+}
+
+#[test]
+fn mov_data() {
+    let mut cpu = init_cpu(0x0000);
+
+    // MOV 0x30, 0x40 assembles to 85 40 30
+    cpu.set_ram(&[(0x30, 0x00), (0x40, 0x99)]);
+    load_program(&mut cpu, 0x0000, &[0x85, 0x40, 0x30]);
+    cpu.break_after_instructions(1);
+    cpu.run();
+
+    assert_eq!(cpu.get_memory().read(0x30), 0x99);
+    assert_eq!(cpu.get_memory().read(0x40), 0x99);
+
+    cpu.run_instruction_test(Instruction::MOV_DATA_CONST, 0x31, 0x5a);
+
+    assert_eq!(cpu.get_memory().read(0x31), 0x5a);
+
+    cpu.set_ram(&[(0x01, 0x31)]);
+    cpu.run_instruction_test(Instruction::MOV_DATA_INDIRECT_R1, 0x32, 0);
+
+    assert_eq!(cpu.get_memory().read(0x32), 0x5a);
+
+    // @R0 = #data, @R0 = direct
+    cpu.set_ram(&[(0x00, 0x33)]);
+    cpu.run_instruction_test(Instruction::MOV_INDIRECT_R0_CONST, 0x7e, 0);
+
+    assert_eq!(cpu.get_memory().read(0x33), 0x7e);
+
+    cpu.run_instruction_test(Instruction::MOV_INDIRECT_R0_DATA, 0x31, 0);
+
+    assert_eq!(cpu.get_memory().read(0x33), 0x5a);
+
+    // stores from ACC
+    cpu.set_registers(&[(SFR::ACC, 0xc7)]);
+    cpu.run_instruction_test(Instruction::MOV_DATA_A, 0x34, 0);
+    cpu.run_instruction_test(Instruction::MOV_INDIRECT_R1_A, 0, 0);
+    cpu.run_instruction_test(Instruction::MOV_R5_A, 0, 0);
+
+    assert_eq!(cpu.get_memory().read(0x34), 0xc7);
+    assert_eq!(cpu.get_memory().read(0x31), 0xc7);
+    assert_eq!(cpu.get_memory().get_gpr_reg(Register::R5), 0xc7);
+}
+
+#[test]
+fn movx_dptr() {
+    let mut cpu = init_cpu(0x0000);
+
+    // flash holds something else at the same address
+    cpu.get_memory().write_flash(0x0123, 0xee);
+    cpu.set_registers(&[(SFR::DPH, 0x01), (SFR::DPL, 0x23), (SFR::ACC, 0x6b)]);
+    cpu.run_instruction_test(Instruction::MOVX_INDIRECT_DPTR_A, 0, 0);
+
+    assert_eq!(cpu.read_xram(0x0123), 0x6b);
+
+    cpu.run_instruction_test(Instruction::CLR_A, 0, 0);
+    cpu.run_instruction_test(Instruction::MOVX_A_INDIRECT_DPTR, 0, 0);
+
+    assert_eq!(cpu.acc(), 0x6b);
+}
+
+#[test]
+fn movx_paged() {
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.write_xram(0x0042, 0x11);
+    cpu.write_xram(0x0342, 0x33);
+    cpu.set_ram(&[(0x00, 0x42), (0x01, 0x43)]);
+
+    cpu.set_registers(&[(SFR::EMI0CN, 0x00)]);
+    cpu.run_instruction_test(Instruction::MOVX_A_INDIRECT_R0, 0, 0);
+
+    assert_eq!(cpu.acc(), 0x11);
+
+    cpu.set_registers(&[(SFR::EMI0CN, 0x03)]);
+    cpu.run_instruction_test(Instruction::MOVX_A_INDIRECT_R0, 0, 0);
+
+    assert_eq!(cpu.acc(), 0x33);
+
+    cpu.run_instruction_test(Instruction::MOVX_INDIRECT_R1_A, 0, 0);
+
+    assert_eq!(cpu.read_xram(0x0343), 0x33);
+    assert_eq!(cpu.read_xram(0x0043), 0x00);
+}
+
+#[test]
+fn movc() {
+    let mut cpu = init_cpu(0x0000);
+
+    load_program(&mut cpu, 0x0400, &[0x10, 0x20, 0x30, 0x40]);
+
+    cpu.set_registers(&[(SFR::DPH, 0x04), (SFR::DPL, 0x00), (SFR::ACC, 0x02)]);
+    cpu.run_instruction_test(Instruction::MOVC_A_INDIRECT_A_DPTR, 0, 0);
+
+    assert_eq!(cpu.acc(), 0x30);
+
+    // MOVC A, @A+PC at 0x03fe reads from 0x03ff + A
+    cpu.set_pc(0x03fe);
+    cpu.set_registers(&[(SFR::ACC, 0x01)]);
+    cpu.run_instruction_test(Instruction::MOVC_A_INDIRECT_A_PC, 0, 0);
+
+    assert_eq!(cpu.acc(), 0x10);
+
+    cpu.set_pc(0x03fe);
+    cpu.set_registers(&[(SFR::ACC, 0x04)]);
+    cpu.run_instruction_test(Instruction::MOVC_A_INDIRECT_A_PC, 0, 0);
+
+    assert_eq!(cpu.acc(), 0x40);
+}
+
+#[test]
+fn jmp_indirect() {
+    let mut cpu = init_cpu(0x0000);
+
+    // MOV DPTR, #0x0100; MOV A, #0x01; RL A; JMP @A+DPTR
+    load_program(&mut cpu, 0x0000, &[0x90, 0x01, 0x00, 0x74, 0x01, 0x23, 0x73]);
+    // AJMP 0x0020; AJMP 0x0030; AJMP 0x0040
+    load_program(&mut cpu, 0x0100, &[0x01, 0x20, 0x01, 0x30, 0x01, 0x40]);
+    cpu.break_after_instructions(5);
+
+    assert_eq!(cpu.run(), ExitReason::InstructionCount);
+    assert_eq!(cpu.get_pc(), 0x0030);
+
+    // DPTR + A wraps around the code space
+    cpu.set_registers(&[(SFR::DPH, 0xff), (SFR::DPL, 0xff), (SFR::ACC, 0x02)]);
+    cpu.run_instruction_test(Instruction::JMP, 0, 0);
+
+    assert_eq!(cpu.get_pc(), 0x0001);
+}
+
+#[test]
+fn jz_jnz() {
+    let mut cpu = init_cpu(0x0000);
+
+    // (insn, ACC, rel, PC after the branch at 0x0100)
+    let cases = [
+        (Instruction::JZ, 0x00, 0x10, 0x0112),
+        (Instruction::JZ, 0x00, 0xf0, 0x00f2),
+        (Instruction::JZ, 0x01, 0xf0, 0x0102),
+        (Instruction::JNZ, 0x01, 0x10, 0x0112),
+        (Instruction::JNZ, 0x01, 0xf0, 0x00f2),
+        (Instruction::JNZ, 0x00, 0x10, 0x0102),
+    ];
+
+    for (insn, acc, rel, pc) in cases {
+        cpu.set_pc(0x0100);
+        cpu.set_registers(&[(SFR::ACC, acc)]);
+        cpu.run_instruction_test(insn, rel, 0);
+
+        assert_eq!(cpu.get_pc(), pc, "{:?} with ACC={:#04x}, rel={:#04x}", insn, acc, rel);
+    }
+}
+
+#[test]
+fn bit_manipulation() {
+    let mut cpu = init_cpu(0x0000);
+
+    // P1.3 is bit 0x93
+    cpu.set_registers(&[(SFR::P1, 0xff)]);
+    cpu.run_instruction_test(Instruction::CLR_BIT, 0x93, 0);
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::P1), 0xf7);
+
+    cpu.run_instruction_test(Instruction::CPL_BIT, 0x93, 0);
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::P1), 0xff);
+
+    // CPL works on the latch even while the pin is held low from outside
+    cpu.drive_pin(1, 3, false);
+    cpu.run_instruction_test(Instruction::CPL_BIT, 0x93, 0);
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::P1), 0xf7);
+
+    cpu.run_instruction_test(Instruction::SETB_BIT, 0x93, 0);
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::P1), 0xff);
+
+    // bit 0x0a is bit 2 of RAM 0x21
+    cpu.run_instruction_test(Instruction::SETB_BIT, 0x0a, 0);
+
+    assert_eq!(cpu.get_memory().read(0x21), 0x04);
+
+    cpu.set_registers(&[(SFR::PSW, 0x00)]);
+    cpu.run_instruction_test(Instruction::CPL_C, 0, 0);
+
+    assert!(cpu.carry());
+
+    cpu.run_instruction_test(Instruction::CPL_C, 0, 0);
+
+    assert!(!cpu.carry());
+
+    cpu.run_instruction_test(Instruction::SETB_C, 0, 0);
+
+    assert!(cpu.carry());
+
+    cpu.run_instruction_test(Instruction::CLR_C, 0, 0);
+
+    assert!(!cpu.carry());
+}
+
+#[test]
+fn dec() {
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.set_registers(&[(SFR::ACC, 0x00), (SFR::PSW, 0x00)]);
+    cpu.run_instruction_test(Instruction::DEC_A, 0, 0);
+
+    assert_eq!(cpu.acc(), 0xff);
+    // only P follows ACC
+    assert_eq!(cpu.psw(), 0x00);
+
+    cpu.set_ram(&[(0x30, 0x00), (0x00, 0x31), (0x31, 0x10)]);
+    cpu.run_instruction_test(Instruction::DEC_ADDR, 0x30, 0);
+    cpu.run_instruction_test(Instruction::DEC_INDIRECT_R0, 0, 0);
+
+    assert_eq!(cpu.get_memory().read(0x30), 0xff);
+    assert_eq!(cpu.get_memory().read(0x31), 0x0f);
+
+    cpu.get_memory().set_gpr_reg(Register::R6, 0x00);
+    cpu.run_instruction_test(Instruction::DEC_R6, 0, 0);
+
+    assert_eq!(cpu.get_memory().get_gpr_reg(Register::R6), 0xff);
+    assert_eq!(cpu.psw(), 0x00);
+
+    // every form wraps like INC does, through the overflow trap
+    let forms = [
+        (Instruction::DEC_A, 0),
+        (Instruction::DEC_ADDR, 0x30),
+        (Instruction::DEC_INDIRECT_R0, 0),
+        (Instruction::DEC_R6, 0),
+    ];
+
+    for (insn, arg0) in forms {
+        let mut cpu = init_cpu(0x0000);
+
+        cpu.set_registers(&[(SFR::ACC, 0x00)]);
+        cpu.set_ram(&[(0x30, 0x00), (0x00, 0x31), (0x31, 0x00), (0x06, 0x00)]);
+        cpu.set_overflow_trap(true);
+        load_program(&mut cpu, 0x0000, &[insn.into(), arg0]);
+
+        assert_eq!(cpu.run(), ExitReason::CounterOverflow, "{:?}", insn);
+    }
+}
+
+#[test]
+fn anl() {
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.set_ram(&[(0x40, 0b1100_1100)]);
+    cpu.set_registers(&[(SFR::PSW, 0xc4)]);
+    cpu.run_instruction_test(Instruction::ANL_DATA_CONST, 0x40, 0b1010_1010);
+
+    assert_eq!(cpu.get_memory().read(0x40), 0b1000_1000);
+    assert_eq!(cpu.psw(), 0xc4);
+
+    cpu.set_registers(&[(SFR::ACC, 0xff)]);
+    cpu.run_instruction_test(Instruction::ANL_A_CONST, 0xf3, 0);
+
+    assert_eq!(cpu.acc(), 0xf3);
+
+    cpu.run_instruction_test(Instruction::ANL_A_DATA, 0x40, 0);
+
+    assert_eq!(cpu.acc(), 0x80);
+
+    cpu.set_ram(&[(0x01, 0x41), (0x41, 0x81)]);
+    cpu.set_registers(&[(SFR::ACC, 0x0f)]);
+    cpu.run_instruction_test(Instruction::ANL_A_INDIRECT_R1, 0, 0);
+
+    assert_eq!(cpu.acc(), 0x01);
+
+    cpu.get_memory().set_gpr_reg(Register::R3, 0x3c);
+    cpu.set_registers(&[(SFR::ACC, 0xf0)]);
+    cpu.run_instruction_test(Instruction::ANL_A_R3, 0, 0);
+
+    assert_eq!(cpu.acc(), 0x30);
+}
+
+#[test]
+fn orl() {
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.set_ram(&[(0x40, 0x01)]);
+    cpu.set_registers(&[(SFR::ACC, 0x80), (SFR::PSW, 0xc4)]);
+    cpu.run_instruction_test(Instruction::ORL_DATA_A, 0x40, 0);
+
+    assert_eq!(cpu.get_memory().read(0x40), 0x81);
+    assert_eq!(cpu.psw(), 0xc4);
+
+    cpu.run_instruction_test(Instruction::ORL_A_CONST, 0x06, 0);
+
+    assert_eq!(cpu.acc(), 0x86);
+
+    cpu.run_instruction_test(Instruction::ORL_A_DATA, 0x40, 0);
+
+    assert_eq!(cpu.acc(), 0x87);
+
+    // @R1 points at 0x41
+    cpu.set_ram(&[(0x01, 0x41), (0x41, 0x30)]);
+    cpu.run_instruction_test(Instruction::ORL_A_INDIRECT_R1, 0, 0);
+
+    assert_eq!(cpu.acc(), 0xb7);
+
+    cpu.get_memory().set_gpr_reg(Register::R0, 0x48);
+    cpu.run_instruction_test(Instruction::ORL_A_R0, 0, 0);
+
+    assert_eq!(cpu.acc(), 0xff);
+}
+
+#[test]
+fn xrl() {
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.set_registers(&[(SFR::ACC, 0b1111_0000), (SFR::PSW, 0xc4)]);
+    cpu.run_instruction_test(Instruction::XRL_A_CONST, 0b1010_1010, 0);
+
+    assert_eq!(cpu.acc(), 0b0101_1010);
+    assert_eq!(cpu.psw(), 0xc4);
+
+    for reg in 0..8u8 {
+        cpu.set_registers(&[(SFR::ACC, 0xff)]);
+        cpu.get_memory().set_gpr_reg(Register::try_from(reg).unwrap(), 1 << reg);
+        cpu.run_instruction_test(Instruction::try_from(0x68 + reg).unwrap(), 0, 0);
+
+        assert_eq!(cpu.acc(), !(1 << reg));
+    }
+
+    cpu.set_ram(&[(0x40, 0x0f), (0x00, 0x41), (0x41, 0x11)]);
+    cpu.run_instruction_test(Instruction::XRL_DATA_CONST, 0x40, 0xff);
+
+    assert_eq!(cpu.get_memory().read(0x40), 0xf0);
+
+    cpu.set_registers(&[(SFR::ACC, 0x33)]);
+    cpu.run_instruction_test(Instruction::XRL_DATA_A, 0x40, 0);
+    cpu.run_instruction_test(Instruction::XRL_A_DATA, 0x40, 0);
+
+    assert_eq!(cpu.get_memory().read(0x40), 0xc3);
+    assert_eq!(cpu.acc(), 0xf0);
+
+    cpu.run_instruction_test(Instruction::XRL_A_INDIRECT_R0, 0, 0);
+
+    assert_eq!(cpu.acc(), 0xe1);
+}
+
+#[test]
+fn parity_follows_acc() {
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.set_acc(0x07);
+
+    assert!(cpu.parity());
+
+    // MOV ACC, #0x03; MOV PSW, #0x01; POP ACC with 0x01 on the stack
+    load_program(&mut cpu, 0x0000, &[0x75, 0xe0, 0x03, 0x75, 0xd0, 0x01, 0xd0, 0xe0]);
+    cpu.set_ram(&[(0x07, 0x01)]);
+
+    cpu.cycle().unwrap();
+
+    assert!(!cpu.parity());
+
+    // P is read-only
+    cpu.cycle().unwrap();
+
+    assert!(!cpu.parity());
+
+    cpu.cycle().unwrap();
+
+    assert_eq!(cpu.acc(), 0x01);
+    assert!(cpu.parity());
+}
+
+#[test]
+fn xch() {
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.write_gpr(Register::R0, 0x12);
+    cpu.set_registers(&[(SFR::ACC, 0x34)]);
+    cpu.run_instruction_test(Instruction::XCH_A_R0, 0, 0);
+
+    assert_eq!((cpu.acc(), cpu.read_gpr(Register::R0)), (0x12, 0x34));
+
+    cpu.set_ram(&[(0x40, 0x56)]);
+    cpu.run_instruction_test(Instruction::XCH_A_DATA, 0x40, 0);
+
+    assert_eq!((cpu.acc(), cpu.get_memory().read(0x40)), (0x56, 0x12));
+
+    // R1 points at 0x41
+    cpu.set_ram(&[(0x01, 0x41), (0x41, 0x78)]);
+    cpu.run_instruction_test(Instruction::XCH_A_INDIRECT_R1, 0, 0);
+
+    assert_eq!((cpu.acc(), cpu.get_memory().read(0x41)), (0x78, 0x56));
+
+    cpu.set_registers(&[(SFR::ACC, 0xab)]);
+    cpu.set_ram(&[(0x41, 0xcd)]);
+    cpu.run_instruction_test(Instruction::XCHD_A_INDIRECT_R1, 0, 0);
+
+    assert_eq!((cpu.acc(), cpu.get_memory().read(0x41)), (0xad, 0xcb));
+}
+
+#[test]
+fn inc() {
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.set_registers(&[(SFR::ACC, 0xff), (SFR::PSW, 0x00)]);
+    cpu.run_instruction_test(Instruction::INC_A, 0, 0);
+
+    assert_eq!(cpu.acc(), 0x00);
+    assert!(!cpu.carry());
+
+    // R0 points at 0x40
+    cpu.set_ram(&[(0x00, 0x40), (0x40, 0x7f)]);
+    cpu.run_instruction_test(Instruction::INC_INDIRECT_R0, 0, 0);
+
+    assert_eq!(cpu.get_memory().read(0x40), 0x80);
+
+    for reg in 0..8u8 {
+        cpu.write_gpr(Register::try_from(reg).unwrap(), reg);
+        cpu.run_instruction_test(Instruction::try_from(0x08 + reg).unwrap(), 0, 0);
+
+        assert_eq!(cpu.read_gpr(Register::try_from(reg).unwrap()), reg + 1);
+    }
+
+    for (before, after) in [(0x00ff, 0x0100), (0x1234, 0x1235), (0xffff, 0x0000)] {
+        let [dph, dpl] = u16::to_be_bytes(before);
+
+        cpu.set_registers(&[(SFR::DPH, dph), (SFR::DPL, dpl)]);
+        cpu.run_instruction_test(Instruction::INC_DPTR, 0, 0);
+
+        assert_eq!(cpu.dptr(), after);
+    }
+}
+
+#[test]
+pub fn orl_data_const() {
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.get_memory().write(0x4f, 0xdb);
+
+    cpu.run_instruction_test(Instruction::ORL_DATA_CONST, 0x4f, 0xf2);
+
+    assert_eq!(cpu.get_memory().read(0x4f), 0xdb | 0xf2);
+}
+
+#[test]
+fn subb() {
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.set_registers(&[(SFR::ACC, 0xc9), (SFR::PSW, 0x80)]);
+    cpu.set_ram(&[(0x02, 0x54)]);
+
+    cpu.run_instruction_test(Instruction::SUBB_A_R2, 0, 0);
+
+    // OV set, AC and CY clear
+    assert_eq!(cpu.assert_registers(&[(SFR::ACC, 0x74), (SFR::PSW, 0x04)]), vec![]);
+}
+
+#[test]
+fn subb_forms() {
+    let mut cpu = init_cpu(0x0000);
+
+    // no borrow out, but one out of the low nibble
+    cpu.set_registers(&[(SFR::ACC, 0b1100_1010), (SFR::PSW, 0x00)]);
+    cpu.run_instruction_test(Instruction::SUBB_A_CONST, 0b1001_1011, 0);
+
+    assert_eq!(cpu.acc(), 0b0010_1111);
+    assert!(!cpu.carry() && cpu.aux_carry() && !cpu.overflow());
+
+    // 0xc9 - 0x54 - C through @R1
+    cpu.set_ram(&[(0x01, 0x40), (0x40, 0x54)]);
+    cpu.set_registers(&[(SFR::ACC, 0xc9), (SFR::PSW, 0x80)]);
+    cpu.run_instruction_test(Instruction::SUBB_A_INDIRECT_R1, 0, 0);
+
+    assert_eq!(cpu.acc(), 0x74);
+    assert!(!cpu.carry() && !cpu.aux_carry() && cpu.overflow());
+
+    // borrowing through zero
+    cpu.set_registers(&[(SFR::ACC, 0x00), (SFR::PSW, 0x80)]);
+    cpu.run_instruction_test(Instruction::SUBB_A_CONST, 0x00, 0);
+
+    assert_eq!(cpu.acc(), 0xff);
+    assert!(cpu.carry() && cpu.aux_carry() && !cpu.overflow());
+}
+
+#[test]
+fn cjne() {
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.set_ram(&[(0x30, 0x40), (0x00, 0x30), (0x03, 0x40)]);
+    cpu.set_registers(&[(SFR::ACC, 0x40)]);
+
+    // (insn, operand, rel, PC after the CJNE at 0x0100, C)
+    let cases = [
+        (Instruction::CJNE_A_CONST_CODE, 0x40, 0x10, 0x0103, false),
+        (Instruction::CJNE_A_CONST_CODE, 0x41, 0x10, 0x0113, true),
+        (Instruction::CJNE_A_CONST_CODE, 0x3f, 0xf0, 0x00f3, false),
+        (Instruction::CJNE_A_DATA_CODE, 0x30, 0x10, 0x0103, false),
+        (Instruction::CJNE_A_DATA_CODE, SFR::ACC.into(), 0x10, 0x0103, false),
+        (Instruction::CJNE_INDIRECT_R0_CONST_CODE, 0x40, 0x10, 0x0103, false),
+        (Instruction::CJNE_INDIRECT_R0_CONST_CODE, 0xff, 0x10, 0x0113, true),
+        (Instruction::CJNE_R3_CONST_CODE, 0x40, 0x10, 0x0103, false),
+        (Instruction::CJNE_R3_CONST_CODE, 0x00, 0x10, 0x0113, false),
+        (Instruction::CJNE_R3_CONST_CODE, 0x80, 0xfd, 0x0100, true),
+    ];
+
+    for (insn, operand, rel, pc, carry) in cases {
+        cpu.set_pc(0x0100);
+        cpu.set_carry(!carry);
+        cpu.run_instruction_test(insn, operand, rel);
+
+        assert_eq!(cpu.get_pc(), pc, "{:?} {:#04x}", insn, operand);
+        assert_eq!(cpu.carry(), carry, "{:?} {:#04x}", insn, operand);
+    }
+}
+
+#[test]
+fn sjmp() {
+    let mut cpu = init_cpu(0x0100);
+
+    cpu.run_instruction_test(Instruction::SJMP, 2, 0);
+
+    assert_eq!(cpu.get_pc(), 0x0104);
+
+    // backwards, SJMP $ stays put
+    cpu.run_instruction_test(Instruction::SJMP, 0xfe, 0);
+
+    assert_eq!(cpu.get_pc(), 0x0104);
+
+    cpu.run_instruction_test(Instruction::SJMP, 0x80, 0);
+
+    assert_eq!(cpu.get_pc(), 0x0086);
+
+    // across the end of the code space, both ways
+    cpu.set_pc(0xfffe);
+    cpu.run_instruction_test(Instruction::SJMP, 0x04, 0);
+
+    assert_eq!(cpu.get_pc(), 0x0004);
+
+    cpu.run_instruction_test(Instruction::SJMP, 0xf0, 0);
+
+    assert_eq!(cpu.get_pc(), 0xfff6);
+}
+
+#[test]
+fn backward_bit_branches() {
+    let mut cpu = init_cpu(0x0100);
+
+    // JB P1.0, JNB P1.1 and JNC, each taken 4 bytes back
+    cpu.set_registers(&[(SFR::P1, 0x01), (SFR::PSW, 0x00)]);
+    cpu.run_instruction_test(Instruction::JB_BIT_CODE, 0x90, 0xfc);
+
+    assert_eq!(cpu.get_pc(), 0x00ff);
+
+    cpu.run_instruction_test(Instruction::JNB_BIT_CODE, 0x91, 0xfc);
+
+    assert_eq!(cpu.get_pc(), 0x00fe);
+
+    cpu.run_instruction_test(Instruction::JNC, 0xfc, 0);
+
+    assert_eq!(cpu.get_pc(), 0x00fc);
+}
+
+#[test]
+fn pc_wraps_around() {
+    let mut cpu = init_cpu(0xfffe);
+
+    // MOV 0x30, #0x42, its last operand at 0x0000
+    load_program(&mut cpu, 0xfffe, &[Instruction::MOV_DATA_CONST.into(), 0x30]);
+    load_program(&mut cpu, 0x0000, &[0x42]);
+
+    cpu.cycle().unwrap();
+
+    assert_eq!(cpu.get_pc(), 0x0001);
+    assert_eq!(cpu.get_memory().read(0x30), 0x42);
+
+    // the same for instructions moving the PC themselves: LCALL pushes 0x0001
+    cpu.set_pc(0xfffe);
+    load_program(&mut cpu, 0xfffe, &[Instruction::LCALL.into(), 0x00]);
+    load_program(&mut cpu, 0x0000, &[0x40]);
+
+    cpu.cycle().unwrap();
+
+    let sp = cpu.sp();
+
+    assert_eq!(cpu.get_pc(), 0x0040);
+    assert_eq!(cpu.get_memory().read(sp - 1), 0x01);
+    assert_eq!(cpu.get_memory().read(sp), 0x00);
+}
+
+#[test]
+fn push_data() {
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.get_memory().set_sfr_reg(SFR::SP, 0x09);
+    cpu.get_memory().set_sfr_reg(SFR::DPH, 0x01);
+    cpu.get_memory().set_sfr_reg(SFR::DPL, 0x23);
+
+    cpu.run_instruction_test(Instruction::PUSH_DATA, SFR::DPL.into(), 0);
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SP), 0x0a);
+    assert_eq!(cpu.get_memory().read(0x0a), 0x23);
+
+    cpu.run_instruction_test(Instruction::PUSH_DATA, SFR::DPH.into(), 0);
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SP), 0x0b);
+    assert_eq!(cpu.get_memory().read(0x0b), 0x01);
+}
+
+#[test]
+fn pop_data() {
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.get_memory().write(0x30, 0x20);
+    cpu.get_memory().write(0x31, 0x23);
+    cpu.get_memory().write(0x32, 0x01);
+
+    cpu.get_memory().set_sfr_reg(SFR::SP, 0x32);
+
+    cpu.run_instruction_test(Instruction::POP_DATA, SFR::DPH.into(), 0);
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SP), 0x31);
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::DPH), 0x01);
+
+    cpu.run_instruction_test(Instruction::POP_DATA, SFR::DPL.into(), 0);
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SP), 0x30);
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::DPL), 0x23);
+
+    cpu.run_instruction_test(Instruction::POP_DATA, SFR::SP.into(), 0);
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SP), 0x20);
+}
+
+#[test]
+fn push_pop_wrap() {
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.set_registers(&[(SFR::SP, 0xfe)]);
+    cpu.set_ram(&[(0x40, 0xaa), (0x41, 0xbb)]);
+
+    cpu.run_instruction_test(Instruction::PUSH_DATA, 0x40, 0);
+
+    assert_eq!(cpu.sp(), 0xff);
+    assert_eq!(cpu.get_memory().read_indirect(0xff), 0xaa);
+
+    // SP wraps to 0x00, which is R0 of bank 0
+    cpu.run_instruction_test(Instruction::PUSH_DATA, 0x41, 0);
+
+    assert_eq!(cpu.sp(), 0x00);
+    assert_eq!(cpu.read_gpr(Register::R0), 0xbb);
+
+    cpu.run_instruction_test(Instruction::POP_DATA, 0x42, 0);
+    cpu.run_instruction_test(Instruction::POP_DATA, 0x43, 0);
+
+    assert_eq!(cpu.sp(), 0xfe);
+    assert_eq!(cpu.get_memory().read(0x42), 0xbb);
+    assert_eq!(cpu.get_memory().read(0x43), 0xaa);
+}
+
+#[test]
+fn stack_in_upper_ram() {
+    let mut cpu = init_cpu(0x0000);
+
+    // SP 0x80 shares its address with SP itself, the stack lives in the upper RAM instead
+    cpu.set_registers(&[(SFR::SP, 0x80), (SFR::ACC, 0x42), (SFR::P1, 0x0f)]);
+    cpu.run_instruction_test(Instruction::PUSH_DATA, SFR::ACC.into(), 0);
+
+    assert_eq!(cpu.sp(), 0x81);
+    assert_eq!(cpu.get_memory().read_indirect(0x81), 0x42);
+
+    // so does @Ri, P1 is left alone
+    cpu.set_registers(&[(SFR::ACC, 0x99)]);
+    cpu.write_gpr(Register::R0, SFR::P1.into());
+    cpu.run_instruction_test(Instruction::MOV_INDIRECT_R0_A, 0, 0);
+    cpu.run_instruction_test(Instruction::INC_INDIRECT_R0, 0, 0);
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::P1), 0x0f);
+    assert_eq!(cpu.get_memory().read_indirect(0x90), 0x9a);
+
+    cpu.run_instruction_test(Instruction::POP_DATA, 0x30, 0);
+
+    assert_eq!(cpu.sp(), 0x80);
+    assert_eq!(cpu.get_memory().read(0x30), 0x42);
+
+    // as do calls
+    cpu.set_pc(0x0123);
+    cpu.run_instruction_test(Instruction::LCALL, 0x02, 0x00);
+
+    assert_eq!(cpu.sp(), 0x82);
+    assert_eq!(cpu.get_memory().read_indirect(0x81), 0x26);
+    assert_eq!(cpu.get_memory().read_indirect(0x82), 0x01);
+}
+
+#[test]
+fn push_pop_sp() {
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.get_memory().set_sfr_reg(SFR::SP, 0x30);
+
+    // the incremented SP is pushed
+    cpu.run_instruction_test(Instruction::PUSH_DATA, SFR::SP.into(), 0);
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SP), 0x31);
+    assert_eq!(cpu.get_memory().read(0x31), 0x31);
+
+    cpu.run_instruction_test(Instruction::PUSH_DATA, SFR::SP.into(), 0);
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SP), 0x32);
+    assert_eq!(cpu.get_memory().read(0x32), 0x32);
+
+    // the popped value wins over the decrement
+    cpu.get_memory().write(0x32, 0x40);
+    cpu.run_instruction_test(Instruction::POP_DATA, SFR::SP.into(), 0);
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SP), 0x40);
+
+    cpu.get_memory().write(0x40, 0x10);
+    cpu.run_instruction_test(Instruction::POP_DATA, 0x50, 0);
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SP), 0x3f);
+    assert_eq!(cpu.get_memory().read(0x50), 0x10);
+}
+
+#[test]
+fn djnz() {
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.get_memory().write(0x40, 0x01);
+    cpu.get_memory().write(0x50, 0x70);
+    cpu.get_memory().write(0x60, 0x15);
+
+    cpu.run_instruction_test(Instruction::DJNZ_DATA_CODE, 0x40, 1);
+    assert_eq!(cpu.get_pc(), 3);
+    cpu.run_instruction_test(Instruction::DJNZ_DATA_CODE, 0x50, 1);
+    assert_eq!(cpu.get_pc(), 7);
+
+    assert_eq!(cpu.get_memory().read(0x40), 0x00);
+    assert_eq!(cpu.get_memory().read(0x50), 0x6f);
+    assert_eq!(cpu.get_memory().read(0x60), 0x15);
+}
+
+#[test]
+fn djnz_counts_down() {
+    let mut cpu = init_cpu(0x0000);
+
+    // MOV 0x30, #3; loop: INC R7; DJNZ 0x30, loop; NOP
+    load_program(&mut cpu, 0x0000, &[0x75, 0x30, 0x03, 0x0f, 0xd5, 0x30, 0xfc, 0x00]);
+    cpu.break_after_instructions(1 + 3 * 2);
+
+    assert_eq!(cpu.run(), ExitReason::InstructionCount);
+    assert_eq!(cpu.get_pc(), 0x0007);
+    assert_eq!(cpu.get_memory().read(0x30), 0x00);
+    assert_eq!(cpu.read_gpr(Register::R7), 0x03);
+}
+
+#[test]
+fn djnz_decrements_zero() {
+    let mut cpu = init_cpu(0x0100);
+
+    // the decrement doesn't depend on the old value, 0 wraps around and keeps looping
+    cpu.set_ram(&[(0x30, 0x00)]);
+    cpu.run_instruction_test(Instruction::DJNZ_DATA_CODE, 0x30, 0xfa);
+    assert_eq!(cpu.get_pc(), 0x00fd);
+    assert_eq!(cpu.get_memory().read(0x30), 0xff);
+
+    cpu.set_ram(&[(0x30, 0x01)]);
+    cpu.run_instruction_test(Instruction::DJNZ_DATA_CODE, 0x30, 0xfa);
+    assert_eq!(cpu.get_pc(), 0x0100);
+    assert_eq!(cpu.get_memory().read(0x30), 0x00);
+}
+
+#[test]
+fn djnz_rn() {
+    let mut cpu = init_cpu(0x0100);
+
+    cpu.set_ram(&[(0x03, 0x02)]);
+
+    // backwards
+    cpu.run_instruction_test(Instruction::DJNZ_R3_CODE, 0xfb, 0);
+    assert_eq!(cpu.get_pc(), 0x00fd);
+    assert_eq!(cpu.get_memory().read(0x03), 0x01);
+
+    cpu.run_instruction_test(Instruction::DJNZ_R3_CODE, 0xfb, 0);
+    assert_eq!(cpu.get_pc(), 0x00ff);
+    assert_eq!(cpu.get_memory().read(0x03), 0x00);
+
+    // 0 wraps around to 0xff and keeps looping
+    cpu.run_instruction_test(Instruction::DJNZ_R3_CODE, 0x10, 0);
+    assert_eq!(cpu.get_pc(), 0x0111);
+    assert_eq!(cpu.get_memory().read(0x03), 0xff);
+}
+
+#[test]
+fn delay_loop_acceleration() {
+    // DJNZ R2, $ (R2 is 0, so 256 times); MOV 0x30, #0x20; DJNZ 0x30, $; NOP
+    let program = [0xda, 0xfe, 0x75, 0x30, 0x20, 0xd5, 0x30, 0xfd, 0x00];
+    let script = "100: pin P1.0 low\n200: irq timer0\n250: uart0 0x41";
+    let mut states = Vec::new();
+
+    for accelerate in [false, true] {
+        let mut cpu = init_cpu(0x0000);
+
+        load_program(&mut cpu, 0x0000, &program);
+        cpu.get_memory().set_sfr_reg(SFR::P1, 0xff);
+        cpu.load_script(Script::parse(script).unwrap());
+        cpu.set_loop_acceleration(accelerate);
+        cpu.break_after_instructions(256 + 1 + 32 + 1);
+
+        assert_eq!(cpu.run(), ExitReason::InstructionCount);
+        assert_eq!(cpu.get_pc(), 0x0009);
+
+        states.push((cpu.state(), cpu.instructions_retired(), cpu.cycle_count()));
+    }
+
+    assert_eq!(states[0], states[1]);
+    assert_eq!(states[0].2, 256 * 2 + 2 + 32 * 2 + 1);
+
+    // breaks land inside the loop like they would without acceleration
+    let mut cpu = init_cpu(0x0000);
+
+    load_program(&mut cpu, 0x0000, &program[..2]);
+    cpu.set_loop_acceleration(true);
+    cpu.break_after_instructions(100);
+
+    assert_eq!(cpu.run(), ExitReason::InstructionCount);
+    assert_eq!(cpu.get_memory().read(0x02), 156);
+    assert_eq!(cpu.cycle_count(), 200);
+
+    let mut cpu = init_cpu(0x0000);
+
+    load_program(&mut cpu, 0x0000, &program[..2]);
+    cpu.set_loop_acceleration(true);
+    cpu.break_after_cycles(10);
+
+    assert_eq!(cpu.run(), ExitReason::CycleCount);
+    assert_eq!(cpu.get_memory().read(0x02), 251);
+    assert_eq!(cpu.cycle_count(), 10);
+}
+
+#[test]
+fn delay_loop_acceleration_interrupted() {
+    // LJMP 0x0100; Timer 0 handler: INC R7; RETI; at 0x0100: DJNZ R2, $; DJNZ R2, $
+    let mut states = Vec::new();
+
+    for accelerate in [false, true] {
+        let mut cpu = init_cpu(0x0000);
+
+        load_program(&mut cpu, 0x0000, &[0x02, 0x01, 0x00]);
+        load_program(&mut cpu, 0x000b, &[0x0f, 0x32]);
+        load_program(&mut cpu, 0x0100, &[0xda, 0xfe, 0xda, 0xfe]);
+        // Timer 0 in 8-bit auto-reload mode, overflowing every 256 cycles
+        cpu.set_registers(&[
+            (SFR::TMOD, 0x02),
+            (SFR::TL0, 0xc0),
+            (SFR::TCON, 0x10),
+            (SFR::IE, 0x82),
+        ]);
+        cpu.set_loop_acceleration(accelerate);
+        cpu.break_after_cycles(1200);
+
+        assert_eq!(cpu.run(), ExitReason::CycleCount);
+
+        states.push((cpu.state(), cpu.instructions_retired(), cpu.cycle_count()));
+        assert_eq!(cpu.read_gpr(Register::R7), 5);
+    }
+
+    assert_eq!(states[0], states[1]);
+}
+
+#[test]
+fn delay_loop_acceleration_with_timers() {
+    // DJNZ R2, $; DJNZ 0x30, $, with both timers overflowing during the loops
+    let mut states = Vec::new();
+
+    for accelerate in [false, true] {
+        let mut cpu = init_cpu(0x0000);
+
+        load_program(&mut cpu, 0x0000, &[0xda, 0xfe, 0xd5, 0x30, 0xfd, 0x00]);
+        cpu.set_ram(&[(0x30, 0x90)]);
+        // Timer 0 in 8-bit auto-reload mode, Timer 1 in 16-bit mode, interrupts disabled
+        cpu.set_registers(&[
+            (SFR::TMOD, 0x12),
+            (SFR::TH0, 0xf0),
+            (SFR::TH1, 0xfe),
+            (SFR::TL1, 0x80),
+            (SFR::TCON, 0x50),
+        ]);
+        cpu.set_loop_acceleration(accelerate);
+        cpu.break_after_instructions(256 + 0x90 + 1);
+
+        assert_eq!(cpu.run(), ExitReason::InstructionCount);
+        assert_eq!(cpu.get_pc(), 0x0006);
+
+        states.push((cpu.state(), cpu.instructions_retired(), cpu.cycle_count()));
+    }
+
+    assert_eq!(states[0], states[1]);
+    assert_eq!(states[0].2, 256 * 2 + 0x90 * 2 + 1);
+}
+
+#[test]
+fn break_after_instructions() {
+    // flash is zeroed, so the CPU runs through a NOP sled
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.break_after_instructions(10);
+
+    assert_eq!(cpu.run(), ExitReason::InstructionCount);
+    assert_eq!(cpu.instructions_retired(), 10);
+    assert_eq!(cpu.get_pc(), 10);
+}
+
+#[test]
+fn missing_clock_reset() {
+    let mut cpu = init_cpu(0x0100);
+
+    // ORL PCON, #0x02 (enter stop mode)
+    cpu.get_memory().write_flash(0x0100, Instruction::ORL_DATA_CONST.into());
+    cpu.get_memory().write_flash(0x0101, SFR::PCON.into());
+    cpu.get_memory().write_flash(0x0102, 0x02);
+
+    cpu.get_memory().set_sfr_reg(SFR::RSTSRC, 0x04);
+
+    cpu.cycle().unwrap();
+
+    assert_eq!(cpu.get_pc(), 0x0000);
+    assert_eq!(cpu.reset_source(), ResetSource::MISSING_CLOCK);
+    assert!(cpu.get_memory().get_sfr_reg(SFR::RSTSRC).is_bit_set(2));
+    assert!(!cpu.get_memory().get_sfr_reg(SFR::PCON).is_bit_set(1));
+    assert!(cpu.clock_running());
+}
+
+#[test]
+fn missing_clock_detector_disabled() {
+    let mut cpu = init_cpu(0x0100);
+
+    // ORL PCON, #0x02 (enter stop mode)
+    cpu.get_memory().write_flash(0x0100, Instruction::ORL_DATA_CONST.into());
+    cpu.get_memory().write_flash(0x0101, SFR::PCON.into());
+    cpu.get_memory().write_flash(0x0102, 0x02);
+
+    cpu.cycle().unwrap();
+
+    assert_eq!(cpu.get_pc(), 0x0103);
+    assert_eq!(cpu.reset_source(), ResetSource::POWER_ON);
+    assert!(!cpu.clock_running());
+}
+
+#[test]
+fn psw_flags() {
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.get_memory().set_sfr_reg(SFR::PSW, 0x00);
+
+    cpu.set_carry(true);
+    assert!(cpu.carry());
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::PSW), 0b10000000);
+
+    cpu.set_aux_carry(true);
+    assert!(cpu.aux_carry());
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::PSW), 0b11000000);
+
+    cpu.set_f0(true);
+    assert!(cpu.f0());
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::PSW), 0b11100000);
+
+    cpu.set_reg_bank(3);
+    assert_eq!(cpu.reg_bank(), 3);
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::PSW), 0b11111000);
+
+    cpu.set_overflow(true);
+    assert!(cpu.overflow());
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::PSW), 0b11111100);
+
+    cpu.set_f1(true);
+    assert!(cpu.f1());
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::PSW), 0b11111110);
+
+    cpu.set_parity(true);
+    assert!(cpu.parity());
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::PSW), 0b11111111);
+
+    cpu.set_reg_bank(1);
+    assert_eq!(cpu.reg_bank(), 1);
+    cpu.set_carry(false);
+    cpu.set_overflow(false);
+    assert!(!cpu.carry());
+    assert!(!cpu.overflow());
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::PSW), 0b01101011);
+}
+
+#[test]
+fn psw_write_switches_reg_bank() {
+    let mut cpu = init_cpu(0x0000);
+
+    let program: [u8; 6] = [
+        Instruction::MOV_R0_CONST.into(),
+        0x11,
+        Instruction::MOV_DATA_CONST.into(),
+        SFR::PSW.into(),
+        0x08,
+        Instruction::MOV_R0_CONST.into(),
+    ];
+
+    for (addr, byte) in program.iter().enumerate() {
+        cpu.get_memory().write_flash(addr as u16, *byte);
+    }
+    cpu.get_memory().write_flash(0x0006, 0x22);
+
+    cpu.cycle().unwrap();
+    assert_eq!(cpu.get_memory().read(0x00), 0x11);
+
+    cpu.cycle().unwrap();
+    assert_eq!(cpu.reg_bank(), 1);
+    assert_eq!(cpu.get_memory().get_gpr_reg(Register::R0), 0x00);
+
+    cpu.cycle().unwrap();
+    assert_eq!(cpu.get_memory().get_gpr_reg(Register::R0), 0x22);
+    assert_eq!(cpu.get_memory().read(0x08), 0x22);
+    assert_eq!(cpu.get_memory().read(0x00), 0x11);
+
+    cpu.set_reg_bank(2);
+    cpu.get_memory().set_gpr_reg(Register::R7, 0x33);
+    assert_eq!(cpu.get_memory().read(0x17), 0x33);
+
+    cpu.set_reg_bank(3);
+    cpu.get_memory().set_gpr_reg(Register::R1, 0x44);
+    assert_eq!(cpu.get_memory().read(0x19), 0x44);
+}
+
+#[test]
+fn gpr_addr() {
+    let mut cpu = init_cpu(0x0000);
+
+    for bank in 0..4u8 {
+        // RS1:RS0 are PSW bits 4:3
+        cpu.set_registers(&[(SFR::PSW, bank << 3)]);
+
+        for reg in 0..8u8 {
+            let reg = Register::try_from(reg).unwrap();
+            let addr = bank * 8 + u8::from(reg);
+
+            assert_eq!(cpu.gpr_addr(reg), addr);
+
+            cpu.get_memory().set_gpr_reg(reg, addr | 0x80);
+            assert_eq!(cpu.get_memory().read(addr), addr | 0x80);
+        }
+    }
+}
+
+#[test]
+fn gpr_follows_bank() {
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.set_reg_bank(1);
+    cpu.write_gpr(Register::R0, 0x5a);
+
+    assert_eq!(cpu.get_memory().read(0x08), 0x5a);
+    assert_eq!(cpu.get_memory().read(0x00), 0x00);
+
+    // ADD, ORL and ANL pick up R0 of bank 1
+    cpu.set_registers(&[(SFR::ACC, 0x01)]);
+    cpu.run_instruction_test(Instruction::ADD_A_R0, 0, 0);
+
+    assert_eq!(cpu.acc(), 0x5b);
+
+    cpu.run_instruction_test(Instruction::ORL_A_R0, 0, 0);
+    cpu.run_instruction_test(Instruction::ANL_A_R0, 0, 0);
+
+    assert_eq!(cpu.acc(), 0x5a);
+
+    cpu.set_reg_bank(0);
+
+    assert_eq!(cpu.read_gpr(Register::R0), 0x00);
+}
+
+#[test]
+fn last_code_byte() {
+    let mut cpu = init_cpu(0xffff);
+
+    cpu.get_memory().write_flash(0xffff, Instruction::NOP.into());
+    cpu.cycle().unwrap();
+
+    assert_eq!(cpu.get_pc(), 0x0000);
+
+    // a two-byte instruction ending on the last byte
+    cpu.set_pc(0xfffe);
+    cpu.get_memory().write_flash(0xfffe, Instruction::MOV_A_CONST.into());
+    cpu.get_memory().write_flash(0xffff, 0x42);
+    cpu.cycle().unwrap();
+
+    assert_eq!(cpu.acc(), 0x42);
+    assert_eq!(cpu.get_pc(), 0x0000);
+}
+
+#[test]
+fn operands_come_from_flash() {
+    let mut cpu = init_cpu(0x0010);
+
+    // RAM at the same addresses holds something else
+    load_program(&mut cpu, 0x0010, &[Instruction::LJMP.into(), 0x12, 0x34]);
+    cpu.set_ram(&[(0x10, 0xee), (0x11, 0xee), (0x12, 0xee)]);
+
+    assert_eq!(cpu.peek_instruction().unwrap(), (Instruction::LJMP, 3, Some(0x12), Some(0x34)));
+
+    cpu.cycle().unwrap();
+
+    assert_eq!(cpu.get_pc(), 0x1234);
+
+    // one-byte instructions take no operands
+    load_program(&mut cpu, 0x1234, &[Instruction::INC_A.into(), 0xff]);
+
+    assert_eq!(cpu.peek_instruction().unwrap(), (Instruction::INC_A, 1, None, None));
+}
+
+#[test]
+fn unimplemented_policy() {
+    let mut cpu = init_cpu(0x0000);
+
+    // 0xa5 is reserved
+    cpu.get_memory().write_flash(0x0000, 0xa5);
+
+    assert_eq!(cpu.run(), ExitReason::ReservedOpcode);
+    assert_eq!(cpu.get_pc(), 0x0000);
+
+    // known but unimplemented instructions halt without the specific reason
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.get_memory().write_flash(0x0000, Instruction::JBC_BIT_ADDR.into());
+
+    assert_eq!(cpu.run(), ExitReason::Halted);
+
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.get_memory().write_flash(0x0000, 0xa5);
+    cpu.set_unimplemented_policy(UnimplementedPolicy::SkipAsNop);
+    cpu.break_after_instructions(3);
+
+    assert_eq!(cpu.run(), ExitReason::InstructionCount);
+    assert_eq!(cpu.get_pc(), 0x0003);
+
+    cpu.set_pc(0x0000);
+    cpu.set_unimplemented_policy(UnimplementedPolicy::LogAndSkip);
+    cpu.break_after_instructions(2);
+
+    assert_eq!(cpu.run(), ExitReason::InstructionCount);
+    assert_eq!(cpu.get_pc(), 0x0002);
+}
+
+#[test]
+fn halt_state() {
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.get_memory().write_flash(0x0000, Instruction::JBC_BIT_ADDR.into());
+    cpu.cycle().unwrap();
+
+    assert!(cpu.is_halted());
+    assert_eq!(cpu.halt_reason(), Some(&HaltReason::Unimplemented(Instruction::JBC_BIT_ADDR)));
+
+    // further cycles do nothing
+    cpu.cycle().unwrap();
+    cpu.cycle().unwrap();
+
+    assert_eq!(cpu.get_pc(), 0x0000);
+    assert_eq!(cpu.instructions_retired(), 0);
+    assert_eq!(cpu.run(), ExitReason::Halted);
+
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.stop();
+
+    assert_eq!(cpu.halt_reason(), Some(&HaltReason::Stopped));
+    assert_eq!(cpu.run(), ExitReason::Halted);
+    assert_eq!(cpu.instructions_retired(), 0);
+}
+
+#[test]
+fn flash_checksum() {
+    let mut cpu = init_cpu(0x0000);
+
+    for (offset, byte) in b"123456789".iter().enumerate() {
+        cpu.get_memory().write_flash(0x0200 + offset as u16, *byte);
+    }
+
+    assert_eq!(cpu.flash_checksum(0x0200, 9, ChecksumAlgo::Sum8), 0xdd);
+    assert_eq!(cpu.flash_checksum(0x0200, 9, ChecksumAlgo::Crc16Ccitt), 0x29b1);
+    // the region is clipped at the end of flash
+    assert_eq!(cpu.flash_checksum(0xfff0, 0x100, ChecksumAlgo::Sum8), 0x00);
+}
+
+#[test]
+fn mov_dptr_const_through_cycle() {
+    let mut cpu = init_cpu(0x0000);
+
+    // MOV DPTR, #0x1234
+    load_program(&mut cpu, 0x0000, &[Instruction::MOV_DPTR_CONST.into(), 0x12, 0x34]);
+
+    cpu.cycle().unwrap();
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::DPH), 0x12);
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::DPL), 0x34);
+    assert_eq!(cpu.get_pc(), 0x0003);
+}
+
+#[test]
+fn trap_on_reentry() {
+    let mut cpu = init_cpu(0x0100);
+
+    load_program(&mut cpu, 0x0100, &[Instruction::RET.into()]);
+
+    // corrupt return address of 0x0000 on the stack
+    cpu.get_memory().set_sfr_reg(SFR::SP, 0x09);
+    cpu.get_memory().write(0x08, 0x00);
+    cpu.get_memory().write(0x09, 0x00);
+
+    cpu.set_trap_on_reentry(true);
+
+    assert_eq!(cpu.run(), ExitReason::UnexpectedReset);
+    assert_eq!(cpu.get_pc(), 0x0000);
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SP), 0x07);
+
+    // without the trap, execution simply continues at the reset vector
+    let mut cpu = init_cpu(0x0100);
+
+    load_program(&mut cpu, 0x0100, &[Instruction::RET.into()]);
+    cpu.get_memory().set_sfr_reg(SFR::SP, 0x09);
+    cpu.break_after_instructions(3);
+
+    assert_eq!(cpu.run(), ExitReason::InstructionCount);
+    assert_eq!(cpu.get_pc(), 0x0002);
+}
+
+#[test]
+fn assert_registers_reports_mismatches() {
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.set_registers(&[(SFR::ACC, 0x12), (SFR::B, 0x34)]);
+
+    assert_eq!(cpu.assert_registers(&[(SFR::ACC, 0x12), (SFR::B, 0x34)]), vec![]);
+    assert_eq!(
+        cpu.assert_registers(&[(SFR::ACC, 0x12), (SFR::B, 0x35), (SFR::SP, 0x07)]),
+        vec![RegisterMismatch { sfr: SFR::B, expected: 0x35, actual: 0x34 }]
+    );
+}
+
+#[test]
+fn bit_addresses_map_to_bit_addressable_sfrs() {
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.get_memory().set_sfr_reg(SFR::TCON, 0b00000010);
+    cpu.get_memory().set_sfr_reg(SFR::TMOD, 0b11111111);
+
+    // bit address 0x89 is TCON.1, TMOD can't be reached through a bit address
+    assert_eq!(cpu.get_memory().get_bit(0x89), Some(1));
+    assert_eq!(cpu.get_memory().get_bit(0x88), Some(0));
+    assert_eq!(cpu.get_memory().get_bit(0x8a), Some(0));
+}
+
+#[test]
+fn pc_outside_code_space() {
+    let mut cpu = init_cpu(0x3fff);
+
+    cpu.set_code_size(0x4000);
+
+    assert_eq!(cpu.cycle(), Ok(()));
+    assert_eq!(cpu.get_pc(), 0x4000);
+    assert_eq!(cpu.cycle(), Err(ErrorType::InvalidAddr(0x4000)));
+    assert_eq!(cpu.run(), ExitReason::Error(ErrorType::InvalidAddr(0x4000)));
+}
+
+#[test]
+fn script_events() {
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.get_memory().set_sfr_reg(SFR::P1, 0xff);
+    cpu.load_script(Script::parse("2: uart0 0x41\n3: pin P1.0 low\n3: irq timer0").unwrap());
+
+    cpu.cycle().unwrap();
+    cpu.cycle().unwrap();
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SBUF0), 0x00);
+    assert!(!cpu.get_memory().get_sfr_reg(SFR::SCON0).is_bit_set(0));
+
+    // the byte arrives before the third instruction executes
+    cpu.cycle().unwrap();
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SBUF0), 0x41);
+    assert!(cpu.get_memory().get_sfr_reg(SFR::SCON0).is_bit_set(0));
+    assert!(cpu.port_bit(1, 0));
+
+    // the pin is driven from the outside, the latch stays as firmware left it
+    cpu.cycle().unwrap();
+    assert!(!cpu.port_bit(1, 0));
+    assert_eq!(cpu.port_latch(1), 0xff);
+    assert!(cpu.get_memory().get_sfr_reg(SFR::TCON).is_bit_set(5));
+}
+
+#[test]
+fn script_uart_echo() {
+    let mut cpu = init_cpu(0x0000);
+
+    // JNB RI, $; MOV A, SBUF0; CLR RI; MOV SBUF0, A; LJMP $
+    let program = [0x30, 0x98, 0xfd, 0xe5, 0x99, 0xc2, 0x98, 0xf5, 0x99, 0x02, 0x00, 0x09];
+
+    load_program(&mut cpu, 0x0000, &program);
+    // mode 1, REN0
+    cpu.set_registers(&[(SFR::SCON0, 0b0101_0000)]);
+    cpu.load_script(Script::parse("40: uart0 0x41").unwrap());
+    cpu.break_after_cycles(39);
+
+    assert_eq!(cpu.run(), ExitReason::CycleCount);
+    assert_eq!(cpu.uart0_drain(), []);
+    assert!(!cpu.get_memory().get_sfr_reg(SFR::SCON0).is_bit_set(0));
+
+    cpu.break_after_cycles(40);
+
+    assert_eq!(cpu.run(), ExitReason::CycleCount);
+    assert_eq!(cpu.uart0_drain(), [0x41]);
+}
+
+#[test]
+fn request_interrupt() {
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.request_interrupt(InterruptSource::Int1);
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::TCON), 0b00001000);
+}
+
+#[test]
+fn simultaneous_interrupts_are_ordered_by_priority() {
+    // without and with Timer 1 set to high priority (PT1)
+    for (ip, order, waiting) in [
+        (0x00, [0x0b, 0x1b], InterruptSource::Timer1),
+        (0x08, [0x1b, 0x0b], InterruptSource::Timer0),
+    ] {
+        let mut cpu = init_cpu(0x0000);
+
+        // both handlers log their vector: MOV @R0, #vector; INC R0; RETI
+        load_program(&mut cpu, 0x000b, &[0x76, 0x0b, 0x08, 0x32]);
+        load_program(&mut cpu, 0x001b, &[0x76, 0x1b, 0x08, 0x32]);
+        cpu.set_pc(0x0100);
+        cpu.write_gpr(Register::R0, 0x40);
+        // Timer 0 and Timer 1 in 16-bit mode, both two cycles away from overflowing
+        cpu.set_registers(&[
+            (SFR::TMOD, 0x11),
+            (SFR::TH0, 0xff),
+            (SFR::TL0, 0xfe),
+            (SFR::TH1, 0xff),
+            (SFR::TL1, 0xfe),
+            (SFR::TCON, 0x50),
+            (SFR::IE, 0x8a),
+            (SFR::IP, ip),
+        ]);
+
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.pending_interrupts(), []);
+
+        // both overflow on the third cycle, the first handler is entered right away
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.get_pc(), order[0] as usize);
+        assert_eq!(cpu.pending_interrupts(), [waiting]);
+
+        // the other one follows its RETI
+        cpu.break_after_cycles(20);
+        assert_eq!(cpu.run(), ExitReason::CycleCount);
+
+        assert_eq!(cpu.get_memory().read(0x40), order[0]);
+        assert_eq!(cpu.get_memory().read(0x41), order[1]);
+        assert_eq!(cpu.read_gpr(Register::R0), 0x42);
+    }
+}
+
+#[test]
+fn interrupt_vectoring() {
+    let mut cpu = init_cpu(0x0000);
+
+    load_program(&mut cpu, 0x0100, &[Instruction::NOP.into(), Instruction::NOP.into()]);
+    load_program(&mut cpu, 0x0003, &[Instruction::RETI.into()]);
+    cpu.set_pc(0x0100);
+
+    // edge triggered INT0, enabled but with EA off
+    cpu.set_registers(&[(SFR::TCON, 0x01), (SFR::IE, 0x01)]);
+    cpu.request_interrupt(InterruptSource::Int0);
+    cpu.cycle().unwrap();
+
+    assert_eq!(cpu.get_pc(), 0x0101);
+
+    cpu.set_registers(&[(SFR::IE, 0x81)]);
+    cpu.cycle().unwrap();
+
+    assert_eq!(cpu.get_pc(), 0x0003);
+    assert_eq!(cpu.sp(), 0x09);
+    assert_eq!(cpu.get_memory().read(0x08), 0x01);
+    assert_eq!(cpu.get_memory().read(0x09), 0x01);
+    // IE0 is cleared by vectoring to an edge triggered INT0
+    assert_eq!(cpu.pending_interrupts(), []);
+
+    cpu.cycle().unwrap();
+
+    assert_eq!(cpu.get_pc(), 0x0101);
+    assert_eq!(cpu.sp(), 0x07);
+}
+
+#[test]
+fn interrupt_priority() {
+    let mut cpu = init_cpu(0x0000);
+
+    // every handler just spins
+    for source in [InterruptSource::Int0, InterruptSource::Timer0, InterruptSource::Int1] {
+        let [high, low] = source.vector().to_be_bytes();
+
+        load_program(&mut cpu, source.vector(), &[Instruction::LJMP.into(), high, low]);
+    }
+
+    // INT1 has high priority
+    cpu.set_registers(&[(SFR::IE, 0x87), (SFR::IP, 0x04)]);
+    cpu.request_interrupt(InterruptSource::Timer0);
+    cpu.cycle().unwrap();
+
+    assert_eq!(cpu.get_pc(), 0x000b);
+
+    // INT0 has the same level as the running handler
+    cpu.request_interrupt(InterruptSource::Int0);
+    cpu.cycle().unwrap();
+
+    assert_eq!(cpu.get_pc(), 0x000b);
+
+    // INT1 preempts it
+    cpu.request_interrupt(InterruptSource::Int1);
+    cpu.cycle().unwrap();
+
+    assert_eq!(cpu.get_pc(), 0x0013);
+
+    // nothing preempts INT1, not even another, level triggered, INT1
+    cpu.cycle().unwrap();
+
+    assert_eq!(cpu.get_pc(), 0x0013);
+    assert_eq!(cpu.pending_interrupts(), [InterruptSource::Int0, InterruptSource::Int1]);
+}
+
+#[test]
+fn uart_tx_interrupt() {
+    for (source, scon, enable) in [
+        (InterruptSource::Uart0, SFR::SCON0, (SFR::IE, 0x90)),
+        (InterruptSource::Uart1, SFR::SCON1, (SFR::EIE2, 0x02)),
+    ] {
+        let mut cpu = init_cpu(0x0000);
+
+        load_program(&mut cpu, 0x0100, &[Instruction::NOP.into()]);
+        cpu.set_pc(0x0100);
+
+        // only TI set, as after a frame went out
+        cpu.set_registers(&[(SFR::IE, 0x80), enable, (scon, 0x02)]);
+
+        assert_eq!(cpu.pending_interrupts(), [source]);
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.get_pc(), source.vector() as usize);
+        // the handler has to clear TI itself
+        assert_eq!(cpu.get_memory().get_sfr_reg(scon), 0x02);
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn load_hex_at_record_addresses() {
+    let path = std::env::temp_dir().join("shiro51_load_hex_at_record_addresses.hex");
+
+    // LJMP 0x0100 at the reset vector, CLR A; RET at 0x0100
+    std::fs::write(&path, ":03000000020100FA\n:02010000E422F7\n:00000001FF\n").unwrap();
+
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.load_from_file(path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(cpu.get_memory().flash_range(0x0000, 4), &[0x02, 0x01, 0x00, 0x00]);
+    assert_eq!(cpu.get_memory().flash_range(0x00ff, 4), &[0x00, 0xe4, 0x22, 0x00]);
+}
+
+#[test]
+fn load_hex_segments() {
+    let mut cpu = init_cpu(0x0000);
+
+    // the second segment starts at 0xf000 through an extended segment address record
+    let hex = ":03000000020100FA\n:020000020F00ED\n:02000000E422F8\n:00000001FF\n";
+
+    cpu.load_hex_str(hex).unwrap();
+
+    assert_eq!(cpu.get_memory().flash_range(0x0000, 3), &[0x02, 0x01, 0x00]);
+    assert_eq!(cpu.get_memory().flash_range(0xf000, 2), &[0xe4, 0x22]);
+
+    // 0x10000 is past the code space, nothing gets written
+    let hex = ":0100100011DE\n:020000040001F9\n:0100000011EE\n:00000001FF\n";
+
+    assert_eq!(
+        cpu.load_hex_str(hex),
+        Err(ErrorType::InvalidHex(3, "data beyond the code space".to_string()))
+    );
+    assert_eq!(cpu.get_memory().flash_range(0x0010, 1), &[0x00]);
+}
+
+#[test]
+fn load_hex_banks() {
+    let mut cpu = CPU::with_config(CpuConfig { code_banks: 3, bank_select: 0xab });
+
+    // DEC A into bank 1 at 0x18000, INC A; INC A into bank 2 at 0x28000
+    let hex = ":020000040001F9\n:01800000146B\n:020000040002F8\n:02800000040476\n:00000001FF\n";
+
+    cpu.load_hex_str(hex).unwrap();
+
+    for (bank, code) in [(0, [0x00, 0x00]), (1, [0x14, 0x00]), (2, [0x04, 0x04])] {
+        cpu.get_memory().write(0xab, bank);
+
+        assert_eq!(cpu.get_memory().flash_range(0x8000, 2), code);
+    }
+
+    // the common area only exists once, and there is no bank 3
+    for hex in [
+        ":020000040001F9\n:017FFF001170\n:00000001FF\n",
+        ":020000040003F7\n:01800000146B\n:00000001FF\n",
+    ] {
+        assert_eq!(
+            cpu.load_hex_str(hex),
+            Err(ErrorType::InvalidHex(2, "data beyond the code space".to_string()))
+        );
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn load_hex_from_reader() {
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.load_hex_from_reader(&b":02010000E422F7\n:00000001FF\n"[..]).unwrap();
+
+    assert_eq!(cpu.get_memory().flash_range(0x0100, 2), &[0xe4, 0x22]);
+
+    // not UTF-8
+    assert!(matches!(cpu.load_hex_from_reader(&[0xff, 0xfe][..]), Err(ErrorType::Io(_))));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn load_missing_file() {
+    let path = std::env::temp_dir().join("shiro51_load_missing_file.hex");
+    let mut cpu = init_cpu(0x0000);
+
+    assert!(matches!(
+        cpu.load_from_file(path.to_str().unwrap()),
+        Err(ErrorType::FileLoad(file, _)) if file == path.to_str().unwrap()
+    ));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn run_firmware_captures_uart0() {
+    use crate::lib::firmware::run_firmware;
+
+    let path = std::env::temp_dir().join("shiro51_run_firmware_captures_uart0.hex");
+
+    // MOV SBUF0, #'O'; MOV SBUF0, #'K'; LJMP 0x0006
+    std::fs::write(&path, ":0900000075994F75994B02000639\n:00000001FF\n").unwrap();
+
+    let result = run_firmware(path.to_str().unwrap(), 100).unwrap();
+
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(result.exit, ExitReason::CycleCount);
+    assert_eq!(result.uart0, b"OK");
+}
+
+#[test]
+fn cycles_accumulate() {
+    let mut cpu = init_cpu(0x0000);
+
+    load_program(&mut cpu, 0x0000, &[Instruction::NOP.into(), Instruction::MUL_AB.into()]);
+
+    cpu.cycle().unwrap();
+    assert_eq!(cpu.cycle_count(), 1);
+
+    cpu.cycle().unwrap();
+    assert_eq!(cpu.cycle_count(), 5);
+    assert_eq!(cpu.instructions_retired(), 2);
+}
+
+#[test]
+fn break_after_cycles() {
+    let mut cpu = init_cpu(0x0000);
+
+    // NOP; LJMP 0x0000
+    load_program(&mut cpu, 0x0000, &[0x00, 0x02, 0x00, 0x00]);
+    cpu.break_after_cycles(8);
+
+    // the LJMP crossing the limit completes
+    assert_eq!(cpu.run(), ExitReason::CycleCount);
+    assert_eq!(cpu.cycle_count(), 9);
+    assert_eq!(cpu.instructions_retired(), 6);
+}
+
+#[test]
+fn interrupt_vectors() {
+    let mut cpu = init_cpu(0x0000);
+
+    // INT0: LJMP 0x1234
+    load_program(&mut cpu, 0x0003, &[Instruction::LJMP.into(), 0x12, 0x34]);
+    // Timer 0: AJMP 0x0345
+    load_program(&mut cpu, 0x000b, &[Instruction::AJMP4.into(), 0x45]);
+    // INT1: MOV A, #0x42 in place
+    load_program(&mut cpu, 0x0013, &[Instruction::MOV_A_CONST.into(), 0x42]);
+    // UART0: LJMP 0x0200
+    load_program(&mut cpu, 0x0023, &[Instruction::LJMP.into(), 0x02, 0x00]);
+
+    load_program(&mut cpu, 0x1234, &[Instruction::RETI.into()]);
+    load_program(&mut cpu, 0x0345, &[Instruction::INC_A.into()]);
+    load_program(&mut cpu, 0x0200, &[0xa5]);
+
+    let vectors = cpu.interrupt_vectors();
+
+    assert_eq!(vectors.len(), 17);
+    assert_eq!(vectors[0], (InterruptSource::Int0, 0x1234, "RETI".to_string()));
+    assert_eq!(vectors[1], (InterruptSource::Timer0, 0x0345, "INC A".to_string()));
+    // anything but a jump resolves to the vector itself
+    assert_eq!(vectors[2], (InterruptSource::Int1, 0x0013, "MOV A,#0x42".to_string()));
+    assert_eq!(vectors[4], (InterruptSource::Uart0, 0x0200, "DB 0xa5".to_string()));
+    assert_eq!(vectors[16], (InterruptSource::Uart1, 0x0083, "NOP".to_string()));
+}
+
+#[test]
+fn overflow_trap() {
+    let mut cpu = init_cpu(0x0000);
+
+    load_program(&mut cpu, 0x0000, &[Instruction::INC_A.into(), Instruction::INC_A.into()]);
+    cpu.get_memory().set_sfr_reg(SFR::ACC, 0xfe);
+    cpu.set_overflow_trap(true);
+
+    assert_eq!(cpu.run(), ExitReason::CounterOverflow);
+    assert_eq!(cpu.get_pc(), 0x0002);
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::ACC), 0x00);
+
+    // without the trap INC wraps silently
+    let mut cpu = init_cpu(0x0000);
+
+    load_program(&mut cpu, 0x0000, &[Instruction::INC_DATA.into(), 0x30]);
+    cpu.get_memory().write(0x30, 0xff);
+    cpu.break_after_instructions(1);
+
+    assert_eq!(cpu.run(), ExitReason::InstructionCount);
+    assert_eq!(cpu.get_memory().read(0x30), 0x00);
+
+    // DEC traps when wrapping from 0x00 to 0xFF
+    let mut cpu = init_cpu(0x0000);
+
+    load_program(&mut cpu, 0x0000, &[Instruction::DEC_ADDR.into(), 0x30, 0x00]);
+    cpu.get_memory().write(0x30, 0x00);
+    cpu.set_overflow_trap(true);
+
+    assert_eq!(cpu.run(), ExitReason::CounterOverflow);
+    assert_eq!(cpu.get_pc(), 0x0002);
+    assert_eq!(cpu.get_memory().read(0x30), 0xff);
+}
+
+#[test]
+fn view_around_dptr() {
+    let mut cpu = init_cpu(0x0000);
+
+    load_program(&mut cpu, 0x0100, &[0x10, 0x11, 0x12, 0x13, 0x14]);
+    for (offset, val) in [0xa0, 0xa1, 0xa2, 0xa3, 0xa4].iter().enumerate() {
+        cpu.get_memory().write_xram(0x0100 + offset as u16, *val);
+    }
+    cpu.set_registers(&[(SFR::DPH, 0x01), (SFR::DPL, 0x02)]);
+
+    let (flash, xram) = cpu.view_around_dptr(4);
+
+    assert_eq!(flash, [0x10, 0x11, 0x12, 0x13]);
+    assert_eq!(xram, [0xa0, 0xa1, 0xa2, 0xa3]);
+
+    // the window is clamped at the bottom of the address space
+    load_program(&mut cpu, 0x0000, &[0x20, 0x21, 0x22, 0x23]);
+    cpu.set_registers(&[(SFR::DPH, 0x00), (SFR::DPL, 0x01)]);
+
+    let (flash, _) = cpu.view_around_dptr(4);
+
+    assert_eq!(flash, [0x20, 0x21, 0x22, 0x23]);
+
+    // XRAM mirrors every 4K
+    cpu.set_registers(&[(SFR::DPH, 0x11), (SFR::DPL, 0x02)]);
+
+    let (_, xram) = cpu.view_around_dptr(4);
+
+    assert_eq!(xram, [0xa0, 0xa1, 0xa2, 0xa3]);
+}
+
+#[test]
+fn inc_dec_a_only_update_parity() {
+    let mut cpu = init_cpu(0x0000);
+
+    load_program(&mut cpu, 0x0000, &[Instruction::INC_A.into(), Instruction::DEC_A.into()]);
+    cpu.get_memory().set_sfr_reg(SFR::ACC, 0x06);
+    cpu.set_carry(true);
+    cpu.set_aux_carry(true);
+    cpu.set_overflow(true);
+
+    cpu.cycle().unwrap();
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::ACC), 0x07);
+    assert!(cpu.parity());
+    assert!(cpu.carry() && cpu.aux_carry() && cpu.overflow());
+
+    cpu.cycle().unwrap();
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::ACC), 0x06);
+    assert!(!cpu.parity());
+    assert!(cpu.carry() && cpu.aux_carry() && cpu.overflow());
+}
+
+#[test]
+fn stack_guard() {
+    let mut cpu = init_cpu(0x0000);
+
+    load_program(&mut cpu, 0x0000, &[Instruction::RET.into()]);
+    // a data byte pushed over the return address, checked without setting the code size
+    cpu.set_ram(&[(0x08, 0x34), (0x09, 0x80)]);
+    cpu.get_memory().set_sfr_reg(SFR::SP, 0x09);
+    cpu.set_stack_guard(Some(0x0000..0x4000));
+
+    assert_eq!(cpu.cycle(), Err(ErrorType::CorruptReturnAddress(0x8034)));
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SP), 0x07);
+
+    // valid return addresses pass
+    let mut cpu = init_cpu(0x0000);
+
+    load_program(&mut cpu, 0x0000, &[Instruction::RETI.into()]);
+    cpu.set_ram(&[(0x08, 0x34), (0x09, 0x12)]);
+    cpu.get_memory().set_sfr_reg(SFR::SP, 0x09);
+    cpu.set_stack_guard(Some(0x0000..0x4000));
+
+    assert_eq!(cpu.cycle(), Ok(()));
+    assert_eq!(cpu.get_pc(), 0x1234);
+}
+
+#[test]
+fn stack_start() {
+    let mut cpu = init_cpu(0x0000);
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SP), 0x07);
+
+    cpu.set_stack_start(0x5f);
+    cpu.reset(ResetSource::PIN);
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SP), 0x5f);
+}
+
+#[test]
+fn carry_logic_port_bits() {
+    let mut cpu = init_cpu(0x0000);
+
+    // P1.0 is high, P1.1 is low: bits 0x90 and 0x91
+    cpu.set_registers(&[(SFR::P1, 0b0000_0001)]);
+
+    // (insn, bit, C before, C after)
+    let cases = [
+        (Instruction::ANL_C_BIT, 0x90, true, true),
+        (Instruction::ANL_C_BIT, 0x91, true, false),
+        (Instruction::ANL_C_COMPLEMENT_BIT, 0x90, true, false),
+        (Instruction::ANL_C_COMPLEMENT_BIT, 0x91, true, true),
+        (Instruction::ORL_C_BIT, 0x90, false, true),
+        (Instruction::ORL_C_BIT, 0x91, false, false),
+        (Instruction::ORL_C_COMPLEMENT_BIT, 0x90, false, false),
+        (Instruction::ORL_C_COMPLEMENT_BIT, 0x91, false, true),
+    ];
+
+    for (insn, bit, before, after) in cases {
+        cpu.set_carry(before);
+        cpu.run_instruction_test(insn, bit, 0);
+
+        assert_eq!(cpu.carry(), after, "{:?} {:#04x} with C={}", insn, bit, before);
+    }
+}
+
+#[test]
+fn mov_carry_bit() {
+    let mut cpu = init_cpu(0x0000);
+
+    // MOV C, P1.2; MOV ACC.7, C
+    cpu.set_registers(&[(SFR::P1, 0b0000_0100), (SFR::ACC, 0x00), (SFR::PSW, 0x00)]);
+    cpu.run_instruction_test(Instruction::MOV_C_BIT, 0x92, 0);
+
+    assert!(cpu.carry());
+
+    cpu.run_instruction_test(Instruction::MOV_BIT_C, 0xe7, 0);
+
+    assert_eq!(cpu.acc(), 0x80);
+
+    // MOV C, P1.2 reads the pin, not the latch
+    cpu.drive_pin(1, 2, false);
+    cpu.run_instruction_test(Instruction::MOV_C_BIT, 0x92, 0);
+
+    assert!(!cpu.carry());
+
+    cpu.run_instruction_test(Instruction::MOV_BIT_C, 0xe7, 0);
+
+    assert_eq!(cpu.acc(), 0x00);
+}
+
+#[test]
+fn carry_logic_chains() {
+    // C = a AND b OR c, with a = 0x20.0, b = 0x20.1 and c = 0x21.0; then C = C AND NOT c OR NOT b
+    let program = [
+        Instruction::ORL_C_BIT.into(),
+        0x00,
+        Instruction::ANL_C_BIT.into(),
+        0x01,
+        Instruction::ORL_C_BIT.into(),
+        0x08,
+        Instruction::ANL_C_COMPLEMENT_BIT.into(),
+        0x08,
+        Instruction::ORL_C_COMPLEMENT_BIT.into(),
+        0x01,
+    ];
+
+    for (a, b, c) in [(0, 0, 0), (1, 0, 0), (1, 1, 0), (0, 1, 1), (1, 1, 1), (0, 0, 1)] {
+        let mut cpu = init_cpu(0x0000);
+
+        load_program(&mut cpu, 0x0000, &program);
+        cpu.set_ram(&[(0x20, a | b << 1), (0x21, c)]);
+        // AC, OV and P, which matches ACC
+        cpu.set_registers(&[(SFR::ACC, 0x01), (SFR::PSW, 0b0100_0101)]);
+
+        for _ in 0..3 {
+            cpu.cycle().unwrap();
+        }
+
+        let expected = (a & b) | c == 1;
+
+        assert_eq!(cpu.carry(), expected, "a={} b={} c={}", a, b, c);
+
+        for _ in 0..2 {
+            cpu.cycle().unwrap();
+        }
+
+        let expected = (expected && c == 0) || b == 0;
+
+        assert_eq!(cpu.carry(), expected, "a={} b={} c={}", a, b, c);
+        // AC, OV and P are left alone
+        assert_eq!(cpu.get_memory().get_sfr_reg(SFR::PSW) & 0x7f, 0b0100_0101);
+    }
+}
+
+#[test]
+fn state_diff() {
+    let mut cpu = init_cpu(0x0000);
+
+    load_program(&mut cpu, 0x0000, &[Instruction::MOV_DATA_CONST.into(), 0xe0, 0x42]);
+
+    let before = cpu.state();
+
+    cpu.cycle().unwrap();
+
+    let after = cpu.state();
+
+    assert_eq!(
+        before.diff(&after),
+        [
+            StateDiff::Pc { before: 0x0000, after: 0x0003 },
+            StateDiff::Sfr { sfr: SFR::ACC, before: 0x00, after: 0x42 },
+        ]
+    );
+    assert_eq!(after.diff(&after), []);
+
+    cpu.get_memory().write(0x30, 0x01);
+
+    assert_eq!(after.diff(&cpu.state()), [StateDiff::Ram { addr: 0x30, before: 0, after: 1 }]);
+}
+
+#[test]
+fn uart0_loopback() {
+    let mut cpu = init_cpu(0x0000);
+
+    // MOV SBUF0, #0x41; MOV A, SBUF0
+    load_program(&mut cpu, 0x0000, &[Instruction::MOV_DATA_CONST.into(), 0x99, 0x41]);
+    load_program(&mut cpu, 0x0003, &[Instruction::MOV_A_DATA.into(), 0x99]);
+    // mode 1, REN0
+    cpu.set_registers(&[(SFR::SCON0, 0b0101_0000)]);
+
+    cpu.cycle().unwrap();
+
+    let sent = cpu.uart0_drain();
+
+    assert_eq!(sent, [0x41]);
+    assert_eq!(cpu.uart0_drain(), []);
+    // TI
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SCON0), 0b0101_0010);
+
+    for byte in sent {
+        cpu.uart0_feed(byte);
+    }
+    cpu.cycle().unwrap();
+
+    assert_eq!(cpu.acc(), 0x41);
+    // RI, and the stop bit in RB8
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SCON0), 0b0101_0111);
+}
+
+#[test]
+fn uart0_nine_bit_mode() {
+    let mut cpu = init_cpu(0x0000);
+
+    // MOV SBUF0, #0x55 twice, once with TB8 set and once with it cleared
+    load_program(&mut cpu, 0x0000, &[Instruction::MOV_DATA_CONST.into(), 0x99, 0x55]);
+    load_program(&mut cpu, 0x0003, &[Instruction::MOV_DATA_CONST.into(), 0x99, 0x55]);
+    // mode 2, TB8
+    cpu.get_memory().set_sfr_reg(SFR::SCON0, 0b1000_1000);
+
+    cpu.cycle().unwrap();
+    cpu.get_memory().set_sfr_reg(SFR::SCON0, 0b1000_0000);
+    cpu.cycle().unwrap();
+
+    assert_eq!(
+        cpu.take_uart_tx(0),
+        [UartFrame { data: 0x55, ninth: Some(true) }, UartFrame { data: 0x55, ninth: Some(false) }]
+    );
+    // SBUF0 reads back the receive buffer, TI follows once the 704 clock frame is out
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SCON0), 0b1000_0000);
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SBUF0), 0x00);
+
+    for _ in 0..59 {
+        cpu.cycle().unwrap();
+    }
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SCON0), 0b1000_0010);
+
+    cpu.uart_receive(0, UartFrame { data: 0xaa, ninth: Some(true) });
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SBUF0), 0xaa);
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SCON0), 0b1000_0111);
+
+    cpu.uart_receive(0, UartFrame { data: 0x12, ninth: Some(false) });
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SCON0), 0b1000_0011);
+}
+
+#[test]
+fn uart_eight_bit_framing() {
+    let mut cpu = init_cpu(0x0000);
+
+    load_program(&mut cpu, 0x0000, &[Instruction::MOV_DATA_CONST.into(), 0x99, 0x55]);
+    // mode 1 transmits no 9th bit and receives the stop bit into RB8
+    cpu.get_memory().set_sfr_reg(SFR::SCON0, 0b0100_1000);
+
+    cpu.cycle().unwrap();
+    cpu.uart_receive(0, UartFrame { data: 0x41, ninth: None });
+
+    assert_eq!(cpu.take_uart_tx(0), [UartFrame { data: 0x55, ninth: None }]);
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SCON0), 0b0100_1111);
+
+    // UART1 with 6 data bits and the extra bit
+    let mut cpu = init_cpu(0x0000);
+
+    load_program(&mut cpu, 0x0000, &[Instruction::MOV_DATA_CONST.into(), 0xd3, 0xff]);
+    cpu.get_memory().set_sfr_reg(SFR::SMOD1, 0b0000_0110);
+    cpu.get_memory().set_sfr_reg(SFR::SCON1, 0b0000_1000);
+
+    cpu.cycle().unwrap();
+    cpu.uart_receive(1, UartFrame { data: 0xff, ninth: Some(true) });
+
+    assert_eq!(cpu.take_uart_tx(1), [UartFrame { data: 0x3f, ninth: Some(true) }]);
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SBUF1), 0x3f);
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SCON1), 0b0000_1111);
+}
+
+#[test]
+fn uart_multiprocessor_mode() {
+    let mut cpu = init_cpu(0x0000);
+
+    // mode 3, SM2
+    cpu.get_memory().set_sfr_reg(SFR::SCON0, 0b1110_0000);
+
+    // data frames are ignored while waiting to be addressed
+    cpu.uart_receive(0, UartFrame { data: 0x12, ninth: Some(false) });
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SBUF0), 0x00);
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SCON0), 0b1110_0000);
+
+    cpu.uart_receive(0, UartFrame { data: 0x34, ninth: Some(true) });
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SBUF0), 0x34);
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SCON0), 0b1110_0101);
+}
+
+#[derive(Default)]
+struct Loopback {
+    sent: Vec<UartFrame>,
+    pending: VecDeque<UartFrame>,
+}
+
+struct SharedLoopback(Rc<RefCell<Loopback>>);
+
+impl SerialBackend for SharedLoopback {
+    fn on_tx(&mut self, frame: UartFrame) {
+        self.0.borrow_mut().sent.push(frame);
+    }
+
+    fn poll_rx(&mut self) -> Option<UartFrame> {
+        self.0.borrow_mut().pending.pop_front()
+    }
+}
+
+#[test]
+fn serial_backend() {
+    let mut cpu = init_cpu(0x0000);
+    let backend = Rc::new(RefCell::new(Loopback::default()));
+
+    load_program(&mut cpu, 0x0000, &[Instruction::MOV_DATA_CONST.into(), 0x99, 0x55]);
+    // mode 2, SM2, TB8
+    cpu.get_memory().set_sfr_reg(SFR::SCON0, 0b1010_1000);
+    cpu.set_serial_backend(0, Box::new(SharedLoopback(backend.clone())));
+    backend.borrow_mut().pending.extend([
+        UartFrame { data: 0x01, ninth: Some(false) },
+        UartFrame { data: 0x02, ninth: Some(true) },
+        UartFrame { data: 0x03, ninth: Some(true) },
+    ]);
+
+    cpu.cycle().unwrap();
+
+    assert_eq!(backend.borrow().sent, [UartFrame { data: 0x55, ninth: Some(true) }]);
+    assert_eq!(cpu.take_uart_tx(0), []);
+
+    // the first frame was dropped, the second one raises RI, TI is still to come
+    cpu.cycle().unwrap();
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SBUF0), 0x02);
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SCON0), 0b1010_1101);
+
+    // nothing is received until RI is cleared
+    cpu.cycle().unwrap();
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::SBUF0), 0x02);
+    assert_eq!(backend.borrow().pending.len(), 1);
+}
+
+#[test]
+fn external_oscillator_lock() {
+    for present in [true, false] {
+        let mut cpu = init_cpu(0x0000);
+
+        // MOV OSCXCN, #0xe7: crystal mode, trying to set XTLVLD too; then MUL AB (4 cycles)
+        load_program(&mut cpu, 0x0000, &[Instruction::MOV_DATA_CONST.into(), 0xb1, 0xe7]);
+        load_program(&mut cpu, 0x0003, &[Instruction::MUL_AB.into(); 3]);
+        cpu.set_ext_osc_present(present);
+        cpu.set_ext_osc_lock_delay(8);
+
+        cpu.cycle().unwrap();
+        // XTLVLD can't be written
+        assert_eq!(cpu.get_memory().get_sfr_reg(SFR::OSCXCN), 0x67);
+
+        for _ in 0..2 {
+            cpu.cycle().unwrap();
+            assert_eq!(cpu.get_memory().get_sfr_reg(SFR::OSCXCN), 0x67);
+        }
+
+        // machine cycle 10, the first tick at least eight cycles after being enabled
+        assert_eq!(cpu.cycle_count(), 10);
+        cpu.cycle().unwrap();
+
+        let xtlvld = if present { 0x80 } else { 0x00 };
+
+        assert_eq!(cpu.get_memory().get_sfr_reg(SFR::OSCXCN), 0x67 | xtlvld);
+    }
+}
+
+#[test]
+fn acc_writes_update_parity() {
+    let insns = [
+        Instruction::RR_A,
+        Instruction::INC_A,
+        Instruction::DEC_A,
+        Instruction::RL_A,
+        Instruction::ADD_A_CONST,
+        Instruction::ADD_A_DATA,
+        Instruction::ADD_A_R1,
+        Instruction::ADDC_A_R0,
+        Instruction::ORL_A_R5,
+        Instruction::ANL_A_R7,
+        Instruction::SUBB_A_R2,
+        Instruction::SUBB_A_DATA,
+        Instruction::MOVX_A_INDIRECT_DPTR,
+    ];
+
+    let samples = [(0x00, 0x00), (0x01, 0x02), (0x7f, 0x01), (0xa5, 0x3c), (0xff, 0xfe)];
+
+    for insn in insns {
+        for (acc, operand) in samples {
+            let mut cpu = init_cpu(0x0000);
+
+            // P starts out wrong, every register, RAM byte and flash byte holds the operand
+            cpu.set_registers(&[(SFR::ACC, acc), (SFR::PSW, (acc.count_ones() as u8 % 2) ^ 1)]);
+            for addr in 0x00..0x80 {
+                cpu.get_memory().write(addr, operand);
+            }
+            load_program(&mut cpu, 0x0000, &[operand; 0x100]);
+
+            cpu.run_instruction_test(insn, operand, 0);
+
+            let acc = cpu.get_memory().get_sfr_reg(SFR::ACC);
+
+            assert_eq!(cpu.parity(), acc.count_ones() % 2 == 1, "{:?} -> {:#04x}", insn, acc);
+        }
+    }
+}
+
+#[test]
+fn direct_acc_writes_update_parity() {
+    let mut cpu = init_cpu(0x0000);
+
+    // INC 0xe0; MOV 0xe0, #0x07; MOV PSW, #0x00
+    load_program(&mut cpu, 0x0000, &[0x05, 0xe0, 0x75, 0xe0, 0x07, 0x75, 0xd0, 0x00]);
+
+    cpu.cycle().unwrap();
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::ACC), 0x01);
+    assert!(cpu.parity());
+
+    cpu.cycle().unwrap();
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::ACC), 0x07);
+    assert!(cpu.parity());
+
+    // P keeps following ACC
+    cpu.cycle().unwrap();
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::PSW), 0x01);
+}
+
+#[test]
+fn core_register_cache() {
+    let mut cpu = init_cpu(0x0000);
+
+    // MOV 0xe0, #0x3c; MOV 0xf0, #0x12; MOV 0x81, #0x40; MOV 0x83, #0xab; MOV 0x82, #0xcd
+    load_program(
+        &mut cpu,
+        0x0000,
+        &[0x75, 0xe0, 0x3c, 0x75, 0xf0, 0x12, 0x75, 0x81, 0x40, 0x75, 0x83, 0xab, 0x75, 0x82, 0xcd],
+    );
+
+    for _ in 0..5 {
+        cpu.cycle().unwrap();
+    }
+
+    assert_eq!(cpu.acc(), 0x3c);
+    assert_eq!(cpu.b(), 0x12);
+    assert_eq!(cpu.sp(), 0x40);
+    assert_eq!(cpu.dptr(), 0xabcd);
+
+    // writes through the direct address space land in the cache as well
+    cpu.get_memory().write(0xd0, 0x18);
+    assert_eq!(cpu.psw(), 0x18);
+    assert_eq!(cpu.reg_bank(), 3);
+
+    let memory = cpu.get_memory();
+    let core = *memory.core();
+
+    assert_eq!(core.acc, memory.get_sfr_reg(SFR::ACC));
+    assert_eq!(core.b, memory.get_sfr_reg(SFR::B));
+    assert_eq!(core.psw, memory.get_sfr_reg(SFR::PSW));
+    assert_eq!(core.sp, memory.get_sfr_reg(SFR::SP));
+    assert_eq!(core.dph, memory.get_sfr_reg(SFR::DPH));
+    assert_eq!(core.dpl, memory.get_sfr_reg(SFR::DPL));
+
+    cpu.reset(ResetSource::PIN);
+    assert_eq!(cpu.acc(), 0x00);
+    assert_eq!(cpu.sp(), 0x07);
+    assert_eq!(cpu.dptr(), 0x0000);
+}
+
+#[test]
+fn exit_sentinel() {
+    let mut cpu = init_cpu(0x0000);
+
+    // MOV 0xe0, #0x42; MOV 0xf0, #0x07; INC A; sentinel
+    load_program(&mut cpu, 0x0000, &[0x75, 0xe0, 0x42, 0x75, 0xf0, 0x07, 0x04, 0xa5, 0xa5]);
+    cpu.set_exit_sentinel(&[0xa5, 0xa5]);
+
+    assert_eq!(cpu.run(), ExitReason::Finished);
+    assert_eq!(cpu.get_pc(), 0x0007);
+    assert_eq!(cpu.instructions_retired(), 3);
+    assert_eq!(cpu.acc(), 0x43);
+    assert_eq!(cpu.b(), 0x07);
+
+    // the sentinel isn't executed, running again finishes right away
+    assert_eq!(cpu.run(), ExitReason::Finished);
+    assert_eq!(cpu.instructions_retired(), 3);
+}
+
+#[test]
+fn read_modify_write_reads_port_latch() {
+    let mut cpu = init_cpu(0x0000);
+
+    // ORL P1, #0x01; ADD A, P1
+    load_program(&mut cpu, 0x0000, &[0x43, 0x90, 0x01, 0x25, 0x90]);
+    cpu.get_memory().set_sfr_reg(SFR::P1, 0xf0);
+    cpu.drive_pin(1, 7, false);
+
+    cpu.cycle().unwrap();
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::P1), 0xf1);
+    assert!(!cpu.port_bit(1, 7));
+
+    // plain reads see the pins
+    cpu.cycle().unwrap();
+    assert_eq!(cpu.acc(), 0x71);
+
+    // once released, the pin follows the latch again
+    cpu.drive_pin(1, 7, true);
+    assert!(cpu.port_bit(1, 7));
+}
+
+#[test]
+fn port_pin_held_low() {
+    let mut cpu = init_cpu(0x0000);
+
+    // MOV C, P1.0; SETB P1.1
+    load_program(&mut cpu, 0x0000, &[0xa2, 0x90, 0xd2, 0x91]);
+    cpu.get_memory().set_sfr_reg(SFR::P1, 0x01);
+    cpu.get_memory().set_sfr_reg(SFR::PSW, 0x80);
+    cpu.drive_pin(1, 0, false);
+
+    cpu.cycle().unwrap();
+    assert!(!cpu.carry());
+    assert!(!cpu.port_bit(1, 0));
+
+    // the bit write keeps the rest of the latch, P1.0 included
+    cpu.cycle().unwrap();
+    assert_eq!(cpu.port_latch(1), 0x03);
+    assert_eq!(cpu.pin_drive(1, 0), None);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn run_for_time_budget() {
+    use crate::lib::cpu::RUN_FOR_CHECK_INTERVAL;
+    use std::time::{Duration, Instant};
+
+    let mut cpu = init_cpu(0x0000);
+
+    // LJMP 0x0000
+    load_program(&mut cpu, 0x0000, &[0x02, 0x00, 0x00]);
+
+    let start = Instant::now();
+    let (reason, cycles) = cpu.run_for(Duration::from_millis(20));
+
+    assert_eq!(reason, ExitReason::TimeBudget);
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert!(cycles >= 2 * RUN_FOR_CHECK_INTERVAL as u64);
+    assert_eq!(cycles, cpu.cycle_count());
+
+    // stops early when the run ends on its own
+    cpu.break_after_instructions(10);
+    assert_eq!(cpu.run_for(Duration::from_secs(60)), (ExitReason::InstructionCount, 20));
+}
+
+#[test]
+fn timer_read_hazard() {
+    let mut pairs = Vec::new();
+
+    for atomic in [false, true] {
+        let mut cpu = init_cpu(0x0000);
+
+        // MOV R0, TL0; MOV R1, TH0
+        load_program(&mut cpu, 0x0000, &[0xa8, 0x8a, 0xa9, 0x8c]);
+        cpu.set_atomic_timer_reads(atomic);
+        cpu.get_memory().set_sfr_reg(SFR::TMOD, 0x01);
+        cpu.get_memory().set_sfr_reg(SFR::TL0, 0xfe);
+        cpu.get_memory().set_sfr_reg(SFR::TCON, 0x10);
+
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+
+        let memory = cpu.get_memory();
+
+        pairs.push((memory.get_gpr_reg(Register::R1), memory.get_gpr_reg(Register::R0)));
+    }
+
+    // TL0 carried into TH0 between the two reads
+    assert_eq!(pairs[0], (0x01, 0xfe));
+    assert_eq!(pairs[1], (0x00, 0xfe));
+}
+
+#[test]
+fn timer0_mode1_counts_cycles() {
+    let mut cpu = init_cpu(0x0000);
+
+    // LJMP 0x0000
+    load_program(&mut cpu, 0x0000, &[0x02, 0x00, 0x00]);
+    cpu.get_memory().set_sfr_reg(SFR::TMOD, 0x01);
+    cpu.get_memory().set_sfr_reg(SFR::TH0, 0xff);
+    cpu.get_memory().set_sfr_reg(SFR::TL0, 0xfc);
+    cpu.get_memory().set_sfr_reg(SFR::TCON, 0x10);
+
+    // the timer picks up each instruction's cycles before the next one
+    cpu.cycle().unwrap();
+    cpu.cycle().unwrap();
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::TL0), 0xfe);
+    assert!(!cpu.get_memory().get_sfr_reg(SFR::TCON).is_bit_set(5));
+
+    cpu.cycle().unwrap();
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::TL0), 0x00);
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::TH0), 0x00);
+    assert!(cpu.get_memory().get_sfr_reg(SFR::TCON).is_bit_set(5));
+}
+
+#[test]
+fn timer_modes() {
+    let mut cpu = init_cpu(0x0000);
+    let tcon = |cpu: &mut CPU| cpu.get_memory().get_sfr_reg(SFR::TCON);
+
+    // mode 1 overflowing from 0xffff
+    cpu.set_registers(&[(SFR::TMOD, 0x01), (SFR::TH0, 0xff), (SFR::TL0, 0xff), (SFR::TCON, 0x10)]);
+    cpu.tick_timers(1);
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::TH0), 0x00);
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::TL0), 0x00);
+    assert_eq!(tcon(&mut cpu), 0x30);
+
+    // mode 0 carries from bit 4 of TL0 into TH0, leaving TL0's upper bits alone
+    cpu.set_registers(&[(SFR::TMOD, 0x00), (SFR::TH0, 0x12), (SFR::TL0, 0xfe), (SFR::TCON, 0x10)]);
+    cpu.tick_timers(3);
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::TH0), 0x13);
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::TL0), 0xe1);
+    assert_eq!(tcon(&mut cpu), 0x10);
+
+    cpu.set_registers(&[(SFR::TH0, 0xff), (SFR::TL0, 0x1f)]);
+    cpu.tick_timers(1);
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::TH0), 0x00);
+    assert_eq!(tcon(&mut cpu), 0x30);
+
+    // mode 2 reloads TL1 from TH1, several times over a long tick
+    cpu.set_registers(&[(SFR::TMOD, 0x20), (SFR::TH1, 0xf0), (SFR::TL1, 0xfe), (SFR::TCON, 0x40)]);
+    cpu.tick_timers(1);
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::TL1), 0xff);
+    assert_eq!(tcon(&mut cpu), 0x40);
+
+    cpu.tick_timers(1 + 16 * 3 + 5);
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::TL1), 0xf5);
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::TH1), 0xf0);
+    assert_eq!(tcon(&mut cpu), 0xc0);
+
+    // mode 3 splits Timer 0: TR1 runs TH0 into TF1, Timer 1 keeps counting without setting it
+    cpu.set_registers(&[
+        (SFR::TMOD, 0x13),
+        (SFR::TH0, 0xff),
+        (SFR::TL0, 0x10),
+        (SFR::TH1, 0xff),
+        (SFR::TL1, 0xff),
+        (SFR::TCON, 0x40),
+    ]);
+    cpu.tick_timers(1);
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::TL0), 0x10);
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::TH0), 0x00);
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::TH1), 0x00);
+    assert_eq!(tcon(&mut cpu), 0xc0);
+
+    // Timer 1 stops in mode 3
+    cpu.set_registers(&[(SFR::TMOD, 0x30), (SFR::TL1, 0x00)]);
+    cpu.tick_timers(5);
+
+    assert_eq!(cpu.get_memory().get_sfr_reg(SFR::TL1), 0x00);
+}
+
+#[test]
+fn step_decoded() {
+    let mut cpu = init_cpu(0x0000);
+
+    // NOP; INC A; LJMP 0x0010; ADD A, #0x05
+    load_program(&mut cpu, 0x0000, &[0x00, 0x04, 0x02, 0x00, 0x10]);
+    load_program(&mut cpu, 0x0010, &[0x24, 0x05]);
+
+    let steps: Vec<_> = (0..4).map(|_| cpu.step_decoded().unwrap().unwrap()).collect();
+
+    assert_eq!(steps, vec![
+        (0x0000, Instruction::NOP, "NOP".to_string()),
+        (0x0001, Instruction::INC_A, "INC A".to_string()),
+        (0x0002, Instruction::LJMP, "LJMP 0x0010".to_string()),
+        (0x0010, Instruction::ADD_A_CONST, "ADD A,#0x05".to_string()),
+    ]);
+    assert_eq!(cpu.get_pc(), 0x0012);
+    assert_eq!(cpu.acc(), 0x06);
+
+    // vectoring isn't a step of its own, the handler's first instruction is
+    load_program(&mut cpu, 0x000b, &[0x04, 0xa5]);
+    cpu.request_interrupt(InterruptSource::Timer0);
+    cpu.set_registers(&[(SFR::IE, 0x82)]);
+
+    let step = cpu.step_decoded().unwrap();
+
+    assert_eq!(step, Some((0x000b, Instruction::INC_A, "INC A".to_string())));
+
+    // nothing runs at the exit sentinel
+    cpu.set_exit_sentinel(&[0xa5]);
+
+    assert_eq!(cpu.step_decoded().unwrap(), None);
+}
+
+#[test]
+fn trace() {
+    let mut cpu = init_cpu(0x0000);
+
+    // INC A eight times, the fifth one wrapping ACC around
+    load_program(&mut cpu, 0x0000, &[Instruction::INC_A.into(); 8]);
+    cpu.set_registers(&[(SFR::ACC, 0xfc)]);
+
+    assert_eq!(cpu.trace(), []);
+
+    cpu.enable_trace(3);
+
+    for _ in 0..5 {
+        cpu.cycle().unwrap();
+    }
+
+    let entry = |pc, acc, psw| {
+        TraceEntry { pc, opcode: 0x04, instruction: Instruction::INC_A, acc, psw }
+    };
+
+    // P follows ACC
+    assert_eq!(
+        cpu.trace(),
+        [entry(0x0002, 0xff, 0x00), entry(0x0003, 0x00, 0x00), entry(0x0004, 0x01, 0x01)]
+    );
+
+    for _ in 0..3 {
+        cpu.cycle().unwrap();
+    }
+
+    assert_eq!(
+        cpu.trace(),
+        [entry(0x0005, 0x02, 0x01), entry(0x0006, 0x03, 0x00), entry(0x0007, 0x04, 0x01)]
+    );
+
+    cpu.enable_trace(0);
+
+    assert_eq!(cpu.trace(), []);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn save_and_restore_state() {
+    let mut cpu = init_cpu(0x0000);
+
+    // INC A; INC 0x30; MOVX @DPTR, A; INC DPTR; LJMP 0x0000
+    load_program(&mut cpu, 0x0000, &[0x04, 0x05, 0x30, 0xf0, 0xa3, 0x02, 0x00, 0x00]);
+    cpu.break_after_instructions(7);
+    cpu.run();
+
+    let saved = cpu.snapshot();
+    let before = (cpu.state(), cpu.read_xram(0x0001), cpu.cycle_count());
+
+    cpu.break_after_instructions(11);
+    cpu.run();
+    cpu.get_memory().write_flash(0x0000, 0x14);
+
+    assert_ne!(cpu.state(), before.0);
+
+    cpu.restore(&saved).unwrap();
+
+    assert_eq!((cpu.state(), cpu.read_xram(0x0001), cpu.cycle_count()), before);
+    assert_eq!(cpu.get_memory().read_flash(0x0000), 0x04);
+
+    // runs on as if nothing happened
+    cpu.break_after_instructions(11);
+    cpu.run();
+
+    let mut fresh = init_cpu(0x0000);
+
+    fresh.restore(&saved).unwrap();
+    fresh.break_after_instructions(11);
+    fresh.run();
+
+    assert_eq!(cpu.state(), fresh.state());
+
+    assert!(matches!(cpu.restore(&saved[..100]), Err(ErrorType::InvalidState(_))));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn snapshot_keeps_peripherals_in_flight() {
+    let mut cpu = init_cpu(0x0000);
+
+    // MOV SBUF0, #0x55; MOV R0, TL0; MOV R1, TH0; MOV A, SBUF0; NOPs
+    load_program(&mut cpu, 0x0000, &[0x75, 0x99, 0x55, 0xa8, 0x8a, 0xa9, 0x8c, 0xe5, 0x99]);
+    // UART0 in mode 1 at the Timer 1 baud rate, Timer 0 counting in 16-bit mode
+    cpu.set_registers(&[
+        (SFR::SCON0, 0x50),
+        (SFR::TMOD, 0x21),
+        (SFR::TH1, 0xfd),
+        (SFR::TL0, 0xfc),
+        (SFR::TCON, 0x10),
+    ]);
+    cpu.set_atomic_timer_reads(true);
+    cpu.uart0_feed(0x41);
+    cpu.break_after_instructions(2);
+    cpu.run();
+
+    // mid-transmission, with TH0 latched by the read of TL0
+    assert!(!cpu.get_memory().get_sfr_reg(SFR::SCON0).is_bit_set(1));
+
+    let saved = cpu.snapshot();
+    let mut fresh = init_cpu(0x0000);
+
+    fresh.set_atomic_timer_reads(true);
+    fresh.restore(&saved).unwrap();
+
+    for cpu in [&mut cpu, &mut fresh] {
+        cpu.break_after_instructions(1000);
+        cpu.run();
+    }
+
+    assert_eq!(cpu.state(), fresh.state());
+    assert!(fresh.get_memory().get_sfr_reg(SFR::SCON0).is_bit_set(1));
+    assert_eq!(fresh.read_gpr(Register::R1), 0x00);
+    assert_eq!(fresh.get_memory().get_sfr_reg(SFR::ACC), 0x41);
+}
+
+#[test]
+fn step() {
+    let mut cpu = init_cpu(0x0000);
+
+    load_program(&mut cpu, 0x0000, &[Instruction::NOP.into(); 3]);
+
+    for addr in 0..3 {
+        let outcome = StepOutcome { addr, instruction: Instruction::NOP, pc: addr + 1 };
+
+        assert_eq!(cpu.step().unwrap(), outcome);
+    }
+
+    assert_eq!(cpu.instructions_retired(), 3);
+}
+
+#[test]
+fn step_into_interrupt() {
+    let mut cpu = init_cpu(0x0000);
+
+    load_program(&mut cpu, 0x0003, &[Instruction::INC_A.into()]);
+    cpu.set_registers(&[(SFR::IE, 0x81)]);
+    cpu.request_interrupt(InterruptSource::Int0);
+
+    let outcome = StepOutcome { addr: 0x0003, instruction: Instruction::INC_A, pc: 0x0004 };
+
+    assert_eq!(cpu.step().unwrap(), outcome);
+    assert_eq!(cpu.instructions_retired(), 1);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn run_until() {
+    let mut cpu = init_cpu(0x0000);
+
+    // INC A; INC A
+    load_program(&mut cpu, 0x0000, &[0x04, 0x04]);
+
+    let breakpoints = [0x0001, 0x0002].into_iter().collect();
+
+    assert_eq!(cpu.run_until(&breakpoints), ExitReason::Breakpoint);
+    assert_eq!(cpu.get_pc(), 0x0001);
+    assert_eq!(cpu.acc(), 0x01);
+
+    // resumes from the breakpoint it stopped at
+    assert_eq!(cpu.run_until(&breakpoints), ExitReason::Breakpoint);
+    assert_eq!(cpu.get_pc(), 0x0002);
+    assert_eq!(cpu.acc(), 0x02);
+}
+
+#[test]
+fn code_banks() {
+    let mut cpu = init_cpu(0x0000);
+
+    // 0xab is unused on this part, it stands in for the bank select SFR
+    cpu.set_code_banks(3, 0xab);
+
+    // LJMP 0x8000, in the common area
+    load_program(&mut cpu, 0x0000, &[0x02, 0x80, 0x00]);
+
+    // bank 0: INC A, bank 1: DEC A, bank 2: INC A; INC A
+    let banks: [&[u8]; 3] = [&[0x04], &[0x14], &[0x04, 0x04]];
+
+    for (bank, code) in banks.iter().enumerate() {
+        cpu.get_memory().write(0xab, bank as u8);
+        load_program(&mut cpu, 0x8000, code);
+    }
+
+    let mut results = Vec::new();
+
+    for bank in [2, 0, 1, 5] {
+        cpu.set_pc(0x0000);
+        cpu.set_acc(0x10);
+        cpu.get_memory().write(0xab, bank);
+
+        assert_eq!(cpu.get_memory().read_flash(0x0000), 0x02);
+
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+
+        results.push(cpu.acc());
+    }
+
+    // bank 2 runs both INCs, the others fall through into zeroes (NOPs), bank 5 wraps to 2
+    assert_eq!(results, vec![0x12, 0x11, 0x0f, 0x12]);
+
+    // the other flash readers see the selected bank as well
+    cpu.get_memory().write(0xab, 1);
+    cpu.set_registers(&[(SFR::DPH, 0x80), (SFR::DPL, 0x01)]);
+
+    assert_eq!(cpu.get_memory().flash_range(0x8000, 2), [0x14, 0x00]);
+    assert_eq!(cpu.view_around_dptr(2).0, [0x14, 0x00]);
+    assert_eq!(cpu.flash_checksum(0x8000, 2, ChecksumAlgo::Sum8), 0x14);
+
+    // DEC A as the sentinel only ends the run in bank 1
+    cpu.set_pc(0x0000);
+    cpu.set_exit_sentinel(&[0x14]);
+    cpu.break_after_instructions(2);
+
+    assert_eq!(cpu.run(), ExitReason::Finished);
+    assert_eq!(cpu.get_pc(), 0x8000);
+
+    cpu.get_memory().write(0xab, 2);
+    cpu.set_pc(0x0000);
+    cpu.break_after_instructions(2);
+
+    assert_eq!(cpu.run(), ExitReason::InstructionCount);
+}
+
+#[test]
+fn memory_faults() {
+    let mut cpu = init_cpu(0x0000);
+
+    cpu.inject_fault(Fault::StuckHigh { at: FaultSite::Ram(0x30), bit: 2 }).unwrap();
+
+    // MOV 0x30, #0x00; ADD A, 0x30; MOV 0x30, #0xff; MOV 0x30, #0x03
+    let program = [0x75, 0x30, 0x00, 0x25, 0x30, 0x75, 0x30, 0xff, 0x75, 0x30, 0x03];
+
+    load_program(&mut cpu, 0x0000, &program);
+
+    cpu.cycle().unwrap();
+    assert_eq!(cpu.get_memory().read(0x30), 0x04);
+
+    cpu.cycle().unwrap();
+    assert_eq!(cpu.acc(), 0x04);
+
+    cpu.cycle().unwrap();
+    assert_eq!(cpu.get_memory().read(0x30), 0xff);
+
+    cpu.cycle().unwrap();
+    assert_eq!(cpu.get_memory().read(0x30), 0x07);
+
+    // XRAM faults follow the address aliasing, flash faults hit fetches
+    cpu.inject_fault(Fault::FlipOnRead { at: FaultSite::Xram(0x0010), bit: 7 }).unwrap();
+    cpu.inject_fault(Fault::StuckLow { at: FaultSite::Flash(0x0100), bit: 0 }).unwrap();
+
+    cpu.get_memory().write_xram(0x1010, 0x01);
+    cpu.get_memory().write_flash(0x0100, 0x03);
+
+    assert_eq!(cpu.get_memory().read_xram(0x0010), 0x81);
+    assert_eq!(cpu.get_memory().read_flash(0x0100), 0x02);
+
+    cpu.get_memory().clear_faults();
+    assert_eq!(cpu.get_memory().read(0x30), 0x03);
+
+    // the upper RAM is only reachable indirectly, below it both sites name the same byte
+    cpu.inject_fault(Fault::StuckLow { at: FaultSite::IndirectRam(0xc0), bit: 0 }).unwrap();
+    cpu.inject_fault(Fault::StuckHigh { at: FaultSite::IndirectRam(0x30), bit: 4 }).unwrap();
+
+    // MOV R0, #0xc0; MOV @R0, #0xff; MOV A, @R0
+    load_program(&mut cpu, 0x0004, &[0x78, 0xc0, 0x76, 0xff, 0xe6]);
+    cpu.set_pc(0x0004);
+
+    for _ in 0..3 {
+        cpu.cycle().unwrap();
+    }
+
+    assert_eq!(cpu.acc(), 0xfe);
+    assert_eq!(cpu.get_memory().read_indirect(0xc0), 0xfe);
+    assert_eq!(cpu.get_memory().read(0x30), 0x13);
+    assert_eq!(cpu.get_memory().read_indirect(0x30), 0x13);
+
+    assert_eq!(
+        cpu.inject_fault(Fault::FlipOnRead { at: FaultSite::Ram(0x30), bit: 8 }),
+        Err(ErrorType::InvalidBit(8))
+    );
+}
+
+#[test]
+fn peek_instruction() {
+    let mut cpu = init_cpu(0x0000);
+
+    // NOP; ADD A, #0x05; MOV 0x30, #0x42
+    load_program(&mut cpu, 0x0000, &[0x00, 0x24, 0x05, 0x75, 0x30, 0x42]);
+
+    let expected = [
+        (Instruction::NOP, 1, None, None),
+        (Instruction::ADD_A_CONST, 2, Some(0x05), None),
+        (Instruction::MOV_DATA_CONST, 3, Some(0x30), Some(0x42)),
+    ];
+
+    for expected in expected {
+        let pc = cpu.get_pc();
+        let peeked = cpu.peek_instruction().unwrap();
+
+        assert_eq!(peeked, expected);
+        assert_eq!(cpu.get_pc(), pc);
+        assert_eq!(cpu.peek_instruction().unwrap(), peeked);
+        assert_eq!(cpu.step_decoded().unwrap().unwrap().1, peeked.0);
+        assert_eq!(cpu.get_pc(), pc + peeked.1 as usize);
+    }
+
+    assert_eq!(cpu.get_memory().read(0x30), 0x42);
+}
+
+#[test]
+fn smod_doubles_uart0_baud_rate() {
+    let mut cpu = init_cpu(0x0000);
+
+    // mode 1, Timer 1 auto-reloading 0xfd: 9600 baud from 11.0592 MHz
+    cpu.set_registers(&[(SFR::SCON0, 0x50), (SFR::TMOD, 0x20), (SFR::TH1, 0xfd)]);
+
+    let slow = cpu.uart_frame_clocks(0).unwrap();
+
+    assert_eq!(11_059_200 * 10 / slow, 9600);
+
+    cpu.set_registers(&[(SFR::PCON, 0x80)]);
+
+    let fast = cpu.uart_frame_clocks(0).unwrap();
+
+    assert_eq!(slow, 2 * fast);
+    assert_eq!(11_059_200 * 10 / fast, 19200);
+
+    // mode 3 frames carry the 9th bit, mode 2 doesn't depend on Timer 1
+    cpu.set_registers(&[(SFR::SCON0, 0xd0)]);
+    assert_eq!(cpu.uart_frame_clocks(0), Some(11 * fast / 10));
+
+    cpu.set_registers(&[(SFR::SCON0, 0x90), (SFR::TMOD, 0x00)]);
+    assert_eq!(cpu.uart_frame_clocks(0), Some(11 * 32));
+
+    cpu.set_registers(&[(SFR::SCON0, 0x50)]);
+    assert_eq!(cpu.uart_frame_clocks(0), None);
+}
+
+#[test]
+fn uart0_ti_delay() {
+    let ti_delay = |pcon: u8| {
+        let mut cpu = init_cpu(0x0000);
+
+        // MOV SBUF0, #0x55, then NOPs
+        load_program(&mut cpu, 0x0000, &[Instruction::MOV_DATA_CONST.into(), 0x99, 0x55]);
+        // mode 1, Timer 1 auto-reloading 0xfd
+        cpu.set_registers(&[
+            (SFR::SCON0, 0x40),
+            (SFR::TMOD, 0x20),
+            (SFR::TH1, 0xfd),
+            (SFR::PCON, pcon),
+        ]);
+
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.uart0_drain(), [0x55]);
+
+        // the machine cycle TI is flagged in, counted from the write
+        loop {
+            let cycle = cpu.cycle_count();
+
+            cpu.cycle().unwrap();
+
+            if cpu.get_memory().get_sfr_reg(SFR::SCON0).is_bit_set(1) {
+                break cycle;
+            }
+        }
+    };
+
+    let slow = ti_delay(0x00);
+    let fast = ti_delay(0x80);
+
+    // 10 bits of 32 Timer 1 overflows, 3 machine cycles each
+    assert_eq!(slow, 960);
+    assert_eq!(slow, 2 * fast);
+}
+
+#[test]
+fn instruction_alignment_check() {
+    // MOV 0x30, #0x00; LJMP 0x0002, into the immediate operand (which decodes as NOP)
+    let program = [0x75, 0x30, 0x00, 0x02, 0x00, 0x02];
+
+    let mut cpu = init_cpu(0x0000);
+
+    load_program(&mut cpu, 0x0000, &program);
+    cpu.break_after_instructions(4);
+
+    assert_eq!(cpu.run(), ExitReason::InstructionCount);
+
+    let mut cpu = init_cpu(0x0000);
+
+    load_program(&mut cpu, 0x0000, &program);
+    cpu.set_instruction_alignment_check(true);
+
+    assert_eq!(cpu.run(), ExitReason::Error(ErrorType::MisalignedInstruction(0x0002)));
+    assert_eq!(cpu.instructions_retired(), 2);
 }
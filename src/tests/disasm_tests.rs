@@ -0,0 +1,57 @@
+use crate::lib::disasm::{disassemble, disassemble_range};
+
+#[test]
+fn program() {
+    let mut flash = vec![0u8; 0x10000];
+
+    let program = [
+        0x74, 0x42,
+        0xe5, 0xe0,
+        0x85, 0x30, 0x90,
+        0xd2, 0x97,
+        0xb2, 0x03,
+        0x90, 0x12, 0x34,
+        0xda, 0xfe,
+        0xb4, 0x07, 0x02,
+        0x31, 0x00,
+        0xa0, 0xd7,
+        0xa5,
+        0x22,
+    ];
+
+    flash[0x0100..0x0100 + program.len()].copy_from_slice(&program);
+
+    let listing: Vec<_> = disassemble_range(&flash, 0x0100, 13)
+        .into_iter()
+        .map(|(addr, text)| format!("{:04x}: {}", addr, text))
+        .collect();
+
+    assert_eq!(listing, [
+        "0100: MOV A,#0x42",
+        "0102: MOV A,ACC",
+        "0104: MOV P1,0x30",
+        "0107: SETB P1.7",
+        "0109: CPL 0x20.3",
+        "010b: MOV DPTR,#0x1234",
+        "010e: DJNZ R2,0x010e",
+        "0110: CJNE A,#0x07,0x0115",
+        "0113: ACALL 0x0100",
+        "0115: ORL C,/PSW.7",
+        "0117: DB 0xa5",
+        "0118: RET",
+        "0119: NOP",
+    ]);
+}
+
+#[test]
+fn wraps_around() {
+    let mut flash = vec![0u8; 0x10000];
+
+    // LJMP 0x1234, its operands at the start of the code space
+    flash[0xffff] = 0x02;
+    flash[0x0000] = 0x12;
+    flash[0x0001] = 0x34;
+
+    assert_eq!(disassemble(&flash, 0xffff), ("LJMP 0x1234".to_string(), 3));
+    assert_eq!(disassemble_range(&flash, 0xffff, 2)[1], (0x0002, "NOP".to_string()));
+}
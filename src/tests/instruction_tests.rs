@@ -0,0 +1,41 @@
+use crate::lib::{instruction::Instruction, memory::registers::PSWFlags};
+
+#[test]
+fn all_instructions() {
+    let all: Vec<_> = Instruction::all().collect();
+
+    assert_eq!(all.len(), 256);
+    assert!(all.iter().enumerate().all(|(opcode, info)| info.opcode as usize == opcode));
+
+    let nop = &all[0x00];
+
+    assert_eq!(nop.instruction, Some(Instruction::NOP));
+    assert_eq!((nop.mnemonic.as_str(), nop.bytes, nop.cycles), ("NOP", 1, 1));
+    assert_eq!(nop.flags, PSWFlags::empty());
+
+    let ljmp = &all[0x02];
+
+    assert_eq!(ljmp.instruction, Some(Instruction::LJMP));
+    assert_eq!((ljmp.mnemonic.as_str(), ljmp.bytes, ljmp.cycles), ("LJMP", 3, 2));
+    assert_eq!(ljmp.flags, PSWFlags::empty());
+
+    let mul = &all[0xa4];
+
+    assert_eq!(mul.instruction, Some(Instruction::MUL_AB));
+    assert_eq!((mul.mnemonic.as_str(), mul.bytes, mul.cycles), ("MUL", 1, 4));
+    assert_eq!(mul.flags, PSWFlags::CY | PSWFlags::OV);
+
+    assert_eq!(all[0xa5].instruction, Some(Instruction::RESERVED_A5));
+    assert_eq!((all[0xa5].mnemonic.as_str(), all[0xa5].bytes), ("RESERVED", 1));
+    assert_eq!(all[0x61].mnemonic, "AJMP");
+    assert_eq!(all[0x35].flags, PSWFlags::CY | PSWFlags::AC | PSWFlags::OV);
+    assert_eq!(all[0xa9].cycles, 2);
+}
+
+#[test]
+fn cycles() {
+    assert_eq!(Instruction::NOP.cycles(), 1);
+    assert_eq!(Instruction::MUL_AB.cycles(), 4);
+    assert_eq!(Instruction::DIV_AB.cycles(), 4);
+    assert_eq!(Instruction::LJMP.cycles(), 2);
+}
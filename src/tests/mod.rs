@@ -1,2 +1,7 @@
+pub mod assembler_tests;
+pub mod bus_tests;
 pub mod byte_tests;
-pub mod cpu_tests;
\ No newline at end of file
+pub mod cpu_tests;
+pub mod disasm_tests;
+pub mod instruction_tests;
+pub mod ws_tests;
\ No newline at end of file
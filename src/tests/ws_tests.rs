@@ -0,0 +1,107 @@
+use crate::lib::{
+    cpu::CPU,
+    error::ErrorType,
+    instruction::Instruction,
+    memory::registers::SFR,
+    ws::{PortLink, PortMessage, PROTOCOL_VERSION},
+};
+
+/// Sends `messages` over the wire as JSON and applies them to `cpu`, returning the replies.
+fn deliver(link: &mut PortLink, cpu: &mut CPU, messages: &[PortMessage]) -> Vec<PortMessage> {
+    messages
+        .iter()
+        .filter_map(|msg| {
+            let msg = PortMessage::from_json(&msg.to_json()).unwrap();
+
+            link.apply(cpu, msg).unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn mirror_p1() {
+    let mut a = CPU::init();
+    let mut b = CPU::init();
+    let mut link_a = PortLink::new();
+    let mut link_b = PortLink::new();
+
+    // MOV P1, #0x5a
+    a.get_memory().write_flash(0x0000, Instruction::MOV_DATA_CONST.into());
+    a.get_memory().write_flash(0x0001, SFR::P1.into());
+    a.get_memory().write_flash(0x0002, 0x5a);
+
+    deliver(&mut link_b, &mut b, &[link_a.hello()]);
+    deliver(&mut link_a, &mut a, &[link_b.hello()]);
+    assert!(link_a.connected() && link_b.connected());
+
+    // both start out with the reset latches, nothing changes on b
+    deliver(&mut link_b, &mut b, &link_a.outgoing(&a));
+    assert_eq!(link_b.outgoing(&b), vec![]);
+
+    a.step().unwrap();
+
+    let writes = link_a.outgoing(&a);
+
+    assert_eq!(writes, vec![PortMessage::PinWrite { port: 1, value: 0x5a }]);
+    deliver(&mut link_b, &mut b, &writes);
+
+    assert_eq!(b.port_latch(1), 0x5a);
+    assert_eq!(b.get_memory().get_sfr_reg(SFR::P1), 0x5a);
+    // not echoed back to a
+    assert_eq!(link_b.outgoing(&b), vec![]);
+
+    let replies = deliver(&mut link_b, &mut b, &[PortMessage::PinRead { port: 1 }]);
+
+    assert_eq!(replies, vec![PortMessage::PinWrite { port: 1, value: 0x5a }]);
+
+    deliver(&mut link_b, &mut b, &[link_a.sync(&a)]);
+    assert_eq!(link_b.peer_cycle(), a.cycle_count());
+}
+
+#[test]
+fn json() {
+    assert_eq!(
+        PortMessage::PinWrite { port: 1, value: 90 }.to_json(),
+        r#"{"type":"pin_write","port":1,"value":90}"#
+    );
+    assert_eq!(
+        PortMessage::from_json(r#" { "cycle" : 1200, "type" : "sync" } "#).unwrap(),
+        PortMessage::Sync { cycle: 1200 }
+    );
+
+    // unknown fields with flat values are skipped
+    assert_eq!(
+        PortMessage::from_json(r#"{"type":"sync","cycle":1,"note":"ab","ok":true,"n":-1.5}"#),
+        Ok(PortMessage::Sync { cycle: 1 })
+    );
+
+    for json in [
+        "",
+        r#"{"type":"pin_read"}"#,
+        // outside of the flat subset, even in unknown fields
+        r#"{"type":"sync","cycle":1,"note":"a,b"}"#,
+        r#"{"type":"sync","cycle":1,"note":"a\"b"}"#,
+        r#"{"type":"sync","cycle":1,"x":{"y":1}}"#,
+        r#"{"type":"sync","cycle":1,"x":[1]}"#,
+        r#"{"type":"pin_read","port":4}"#,
+        r#"{"type":"pin_write","port":0,"value":256}"#,
+        r#"{"type":"blink","port":0}"#,
+    ] {
+        let result = PortMessage::from_json(json);
+
+        assert!(matches!(result, Err(ErrorType::InvalidMessage(_))), "{}", json);
+    }
+}
+
+#[test]
+fn handshake() {
+    let mut cpu = CPU::init();
+    let mut link = PortLink::new();
+
+    assert!(link.apply(&mut cpu, PortMessage::PinRead { port: 0 }).is_err());
+    assert!(link.apply(&mut cpu, PortMessage::Hello { version: PROTOCOL_VERSION + 1 }).is_err());
+    assert!(!link.connected());
+
+    assert_eq!(link.apply(&mut cpu, PortMessage::Hello { version: PROTOCOL_VERSION }), Ok(None));
+    assert!(link.apply(&mut cpu, PortMessage::Hello { version: PROTOCOL_VERSION }).is_err());
+}